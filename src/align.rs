@@ -0,0 +1,163 @@
+use crate::width::display_width;
+
+/// Parses the delimiter given to `--align`. `\t` is accepted as a tab, since a literal tab is
+/// awkward to type in most shells.
+pub(crate) fn parse_delimiter(s: &str) -> anyhow::Result<String> {
+    match s {
+        "" => anyhow::bail!("The delimiter can't be empty"),
+        r"\t" => Ok("\t".to_owned()),
+        _ => Ok(s.to_owned()),
+    }
+}
+
+/// The column widths of the selected lines, used to align their fields like `column -t`.
+pub(crate) struct Columns {
+    delimiter: Vec<u8>,
+    widths: Vec<usize>,
+}
+
+impl Columns {
+    pub(crate) fn new(delimiter: &str) -> Self {
+        Self {
+            delimiter: delimiter.as_bytes().to_vec(),
+            widths: Vec::new(),
+        }
+    }
+
+    /// Widens the columns to fit the fields of `line`. Only the lines that will be aligned should
+    /// be measured.
+    pub(crate) fn measure(&mut self, line: &[u8]) {
+        for (i, field) in split_fields(strip_line_ending(line), &self.delimiter).enumerate() {
+            let width = display_width(field);
+            match self.widths.get_mut(i) {
+                Some(max_width) if *max_width < width => *max_width = width,
+                Some(_) => {}
+                None => self.widths.push(width),
+            }
+        }
+    }
+
+    /// Returns `line` with its fields padded to the column widths and separated by two spaces.
+    /// The last field is not padded, and the line ending is kept as is.
+    pub(crate) fn align(&self, line: &[u8]) -> Vec<u8> {
+        let mut aligned = Vec::with_capacity(line.len());
+        let content = strip_line_ending(line);
+        let mut fields = split_fields(content, &self.delimiter)
+            .enumerate()
+            .peekable();
+        while let Some((i, field)) = fields.next() {
+            aligned.extend_from_slice(field);
+            if fields.peek().is_some() {
+                let padding = self
+                    .widths
+                    .get(i)
+                    .map_or(0, |width| width - display_width(field));
+                aligned.resize(aligned.len() + padding + 2, b' ');
+            }
+        }
+        aligned.extend_from_slice(&line[content.len()..]);
+        aligned
+    }
+}
+
+fn strip_line_ending(line: &[u8]) -> &[u8] {
+    match line.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => line,
+    }
+}
+
+fn split_fields<'a>(mut line: &'a [u8], delimiter: &'a [u8]) -> impl Iterator<Item = &'a [u8]> {
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        match line
+            .windows(delimiter.len())
+            .position(|window| window == delimiter)
+        {
+            Some(i) => {
+                let field = &line[..i];
+                line = &line[i + delimiter.len()..];
+                Some(field)
+            }
+            None => {
+                done = true;
+                Some(line)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(delimiter: &str, widths: &[usize]) -> Columns {
+        Columns {
+            delimiter: delimiter.as_bytes().to_vec(),
+            widths: widths.to_vec(),
+        }
+    }
+
+    #[test]
+    fn split() {
+        let fields: Vec<_> = split_fields(b"a,,b", b",").collect();
+        assert_eq!(fields, [&b"a"[..], b"", b"b"]);
+
+        let fields: Vec<_> = split_fields(b"a::b:c", b"::").collect();
+        assert_eq!(fields, [&b"a"[..], b"b:c"]);
+
+        let fields: Vec<_> = split_fields(b"", b",").collect();
+        assert_eq!(fields, [b""]);
+    }
+
+    #[test]
+    fn align_fields() {
+        let columns = columns(",", &[5, 1, 3]);
+        assert_eq!(columns.align(b"ab,c,def\n"), b"ab     c  def\n");
+        assert_eq!(columns.align(b"abcde,c\r\n"), b"abcde  c\r\n");
+        assert_eq!(columns.align(b"no delimiter"), b"no delimiter");
+    }
+
+    #[test]
+    fn measure() {
+        let mut columns = Columns::new(", ");
+        columns.measure(b"a, bcd\n");
+        columns.measure(b"abc, d, e\n");
+        columns.measure(b"\n");
+        assert_eq!(columns.widths, [3, 3, 1]);
+    }
+
+    #[test]
+    fn align_more_fields_than_measured() {
+        let columns = columns(",", &[1]);
+        assert_eq!(columns.align(b"a,b,c"), b"a  b  c");
+    }
+
+    #[test]
+    fn align_multibyte_characters() {
+        let columns = columns("\t", &[3]);
+        assert_eq!(columns.align("é\tx".as_bytes()), "é    x".as_bytes());
+    }
+
+    #[test]
+    fn align_wide_and_colored_fields() {
+        let mut columns = Columns::new(",");
+        columns.measure("日本,x".as_bytes());
+        columns.measure(b"\x1b[31mab\x1b[0m,y");
+        assert_eq!(columns.widths, [4, 1]);
+        assert_eq!(
+            columns.align(b"\x1b[31mab\x1b[0m,y"),
+            b"\x1b[31mab\x1b[0m    y"
+        );
+    }
+
+    #[test]
+    fn delimiter() {
+        assert_eq!(parse_delimiter(",").unwrap(), ",");
+        assert_eq!(parse_delimiter(r"\t").unwrap(), "\t");
+        assert!(parse_delimiter("").is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use crate::line_counter::{PARALLEL_THRESHOLD, count_lines};
+use crate::line_index::{self, IndexedLines};
+use crate::line_reader::LineReader;
+use crate::mapped::MappedLines;
+use crate::size::format_size;
+use crate::{LineSource, paths};
+use crate::{default_read_buffer_size, open_file};
+use anyhow::Context;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Number of lines read by the random access pass.
+const N_RANDOM_LINES: usize = 100;
+
+struct Measurement {
+    backend: &'static str,
+    pass: String,
+    elapsed: Duration,
+    throughput: String,
+}
+
+/// Times the line counting pass, the extraction of random lines, and a dump of all lines of the
+/// file at `path`, read through a `BufReader` then mapped into memory like `--mmap`, and prints
+/// the results as a table. The line counting pass and the extraction of random lines are also
+/// timed with the index of `--index`, both built from scratch and loaded from disk.
+///
+/// Each pass opens the file anew, so the results include the cost of opening the file but,
+/// except for the first pass, not the cost of reading it from cold storage.
+pub(crate) fn run(path: &Path, threads: usize) -> anyhow::Result<()> {
+    let open = || -> anyhow::Result<BufReader<File>> {
+        let file = open_file(path)?;
+        let read_buffer = default_read_buffer_size(&file)?;
+        Ok(BufReader::with_capacity(read_buffer, file))
+    };
+
+    let len = open()?
+        .get_ref()
+        .metadata()
+        .context("Failed to read file metadata")?
+        .len();
+    let bytes_per_sec = |elapsed: Duration| {
+        let bytes = (len as f64 / elapsed.as_secs_f64()) as u64;
+        format!("{}/s", format_size(bytes))
+    };
+
+    let mut measurements = Vec::new();
+
+    let start = Instant::now();
+    let n_lines = count_lines(&mut open()?, 1, b'\n')?;
+    let elapsed = start.elapsed();
+    measurements.push(Measurement {
+        backend: "bufread",
+        pass: String::from("count (1 thread)"),
+        elapsed,
+        throughput: bytes_per_sec(elapsed),
+    });
+
+    // smaller files are always counted on a single thread
+    if threads > 1 && len >= PARALLEL_THRESHOLD {
+        let start = Instant::now();
+        count_lines(&mut open()?, threads, b'\n')?;
+        let elapsed = start.elapsed();
+        measurements.push(Measurement {
+            backend: "bufread",
+            pass: format!("count ({threads} threads)"),
+            elapsed,
+            throughput: bytes_per_sec(elapsed),
+        });
+    }
+
+    let line_nums = random_line_nums(n_lines);
+    let start = Instant::now();
+    let mut line_reader = LineReader::new(open()?);
+    let mut buf = Vec::new();
+    for &line_num in &line_nums {
+        buf.clear();
+        line_reader.read_specific_line(&mut buf, line_num)?;
+    }
+    let elapsed = start.elapsed();
+    measurements.push(Measurement {
+        backend: "bufread",
+        pass: format!("{} random lines", line_nums.len()),
+        elapsed,
+        throughput: format!(
+            "{:.0} lines/s",
+            line_nums.len() as f64 / elapsed.as_secs_f64()
+        ),
+    });
+
+    let start = Instant::now();
+    let mut line_reader = LineReader::new(open()?);
+    let mut sink = BufWriter::new(std::io::sink());
+    for line_num in 0..n_lines {
+        buf.clear();
+        line_reader.read_specific_line(&mut buf, line_num)?;
+        sink.write_all(&buf)?;
+    }
+    sink.flush()?;
+    let elapsed = start.elapsed();
+    measurements.push(Measurement {
+        backend: "bufread",
+        pass: String::from("dump all lines"),
+        elapsed,
+        throughput: bytes_per_sec(elapsed),
+    });
+
+    // mapping the file is part of the first pass, like opening it is for the other backend
+    let start = Instant::now();
+    let mapped_lines = MappedLines::new(open()?.get_ref(), b'\n')?;
+    mapped_lines.count_lines(1);
+    let elapsed = start.elapsed();
+    measurements.push(Measurement {
+        backend: "mmap",
+        pass: String::from("count (1 thread)"),
+        elapsed,
+        throughput: bytes_per_sec(elapsed),
+    });
+
+    if threads > 1 && len >= PARALLEL_THRESHOLD {
+        let start = Instant::now();
+        MappedLines::new(open()?.get_ref(), b'\n')?.count_lines(threads);
+        let elapsed = start.elapsed();
+        measurements.push(Measurement {
+            backend: "mmap",
+            pass: format!("count ({threads} threads)"),
+            elapsed,
+            throughput: bytes_per_sec(elapsed),
+        });
+    }
+
+    let start = Instant::now();
+    let mut mapped_lines = MappedLines::new(open()?.get_ref(), b'\n')?;
+    for &line_num in &line_nums {
+        mapped_lines.get(line_num)?;
+    }
+    let elapsed = start.elapsed();
+    measurements.push(Measurement {
+        backend: "mmap",
+        pass: format!("{} random lines", line_nums.len()),
+        elapsed,
+        throughput: format!(
+            "{:.0} lines/s",
+            line_nums.len() as f64 / elapsed.as_secs_f64()
+        ),
+    });
+
+    let start = Instant::now();
+    let mut mapped_lines = MappedLines::new(open()?.get_ref(), b'\n')?;
+    for line_num in 0..n_lines {
+        sink.write_all(mapped_lines.get(line_num)?)?;
+    }
+    sink.flush()?;
+    let elapsed = start.elapsed();
+    measurements.push(Measurement {
+        backend: "mmap",
+        pass: String::from("dump all lines"),
+        elapsed,
+        throughput: bytes_per_sec(elapsed),
+    });
+
+    // the index is removed first so that it's built from scratch, then loaded by the next pass
+    let index_path = line_index::index_path(path);
+    let had_index = index_path.exists();
+    if had_index {
+        fs::remove_file(&index_path).with_context(|| {
+            format!(
+                "Failed to remove the line index at `{}`",
+                index_path.display()
+            )
+        })?;
+    }
+    let start = Instant::now();
+    line_index::load_or_build(path, &mut open()?, b'\n')?;
+    let elapsed = start.elapsed();
+    measurements.push(Measurement {
+        backend: "index",
+        pass: String::from("count (cold index)"),
+        elapsed,
+        throughput: bytes_per_sec(elapsed),
+    });
+
+    let start = Instant::now();
+    line_index::load_or_build(path, &mut open()?, b'\n')?;
+    let elapsed = start.elapsed();
+    measurements.push(Measurement {
+        backend: "index",
+        pass: String::from("count (warm index)"),
+        elapsed,
+        throughput: bytes_per_sec(elapsed),
+    });
+
+    let start = Instant::now();
+    let mut file = open()?;
+    let index = line_index::load_or_build(path, &mut file, b'\n')?;
+    let mut indexed_lines = IndexedLines::new(file, index);
+    for &line_num in &line_nums {
+        indexed_lines.get(line_num)?;
+    }
+    let elapsed = start.elapsed();
+    measurements.push(Measurement {
+        backend: "index",
+        pass: format!("{} random lines", line_nums.len()),
+        elapsed,
+        throughput: format!(
+            "{:.0} lines/s",
+            line_nums.len() as f64 / elapsed.as_secs_f64()
+        ),
+    });
+    // benchmarking doesn't leave an index behind, but keeps an index that was there
+    if !had_index {
+        let _ = fs::remove_file(&index_path);
+    }
+
+    print_measurements(path, len, n_lines, &measurements).context("Failed to output results")
+}
+
+fn print_measurements(
+    path: &Path,
+    len: u64,
+    n_lines: usize,
+    measurements: &[Measurement],
+) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    writeln!(
+        stdout,
+        "{}: {}, {n_lines} line(s)\n",
+        paths::display(path),
+        format_size(len)
+    )?;
+
+    let pass_width = measurements
+        .iter()
+        .map(|measurement| measurement.pass.len())
+        .max()
+        .unwrap_or_default();
+    writeln!(
+        stdout,
+        "{:<8}  {:<pass_width$}  {:>12}  throughput",
+        "backend", "pass", "time"
+    )?;
+    for measurement in measurements {
+        writeln!(
+            stdout,
+            "{:<8}  {:<pass_width$}  {:>9.1} ms  {}",
+            measurement.backend,
+            measurement.pass,
+            measurement.elapsed.as_secs_f64() * 1000.0,
+            measurement.throughput,
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns up to `N_RANDOM_LINES` distinct line numbers below `n_lines` in ascending order.
+///
+/// The numbers come from a fixed-seed xorshift generator so that runs are comparable.
+fn random_line_nums(n_lines: usize) -> Vec<usize> {
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut line_nums: Vec<usize> = (0..N_RANDOM_LINES.min(n_lines))
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n_lines as u64) as usize
+        })
+        .collect();
+    line_nums.sort_unstable();
+    line_nums.dedup();
+    line_nums
+}
@@ -0,0 +1,104 @@
+use crate::line_selector::RawLineSelector;
+use anyhow::Context;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::RangeInclusive;
+
+/// A byte range of `--bytes`, written like a line selector without a step: one-based and
+/// inclusive, with negative offsets counting from the end of the file (e.g.: `1200:1300`, `-512:`,
+/// `42`, or `1200+100`).
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ByteRange(RawLineSelector);
+
+impl ByteRange {
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Self> {
+        match RawLineSelector::from_str(s)? {
+            raw @ (RawLineSelector::Single(_)
+            | RawLineSelector::Range(..)
+            | RawLineSelector::RangeWithStep(_, _, None)
+            | RawLineSelector::RangeWithCount(..)) => Ok(Self(raw)),
+            _ => anyhow::bail!(
+                "Byte range `{s}` isn't of the form START:END, START, or START+N (e.g.: 1200:1300)"
+            ),
+        }
+    }
+
+    /// Returns the zero-based offsets of the first and the last byte of the range in a file of
+    /// `len` bytes, which isn't empty.
+    pub(crate) fn resolve(self, len: u64) -> anyhow::Result<RangeInclusive<u64>> {
+        let to_offset = |num: isize| {
+            if num.unsigned_abs() as u64 > len {
+                anyhow::bail!("Byte {num} is out of range (input has {len} byte(s) only)");
+            }
+            Ok(if num < 0 {
+                len - num.unsigned_abs() as u64
+            } else {
+                num as u64 - 1
+            })
+        };
+        let (start, end) = match self.0 {
+            RawLineSelector::Single(num) => (to_offset(num)?, to_offset(num)?),
+            RawLineSelector::Range(start, end) | RawLineSelector::RangeWithStep(start, end, _) => (
+                start.map(to_offset).unwrap_or(Ok(0))?,
+                end.map(to_offset).unwrap_or(Ok(len - 1))?,
+            ),
+            RawLineSelector::RangeWithCount(start, count) => {
+                let start_offset = to_offset(start)?;
+                let end = start_offset.saturating_add(count as u64 - 1);
+                if end >= len {
+                    anyhow::bail!(
+                        "Byte {} is out of range (input has {len} byte(s) only)",
+                        end.saturating_add(1)
+                    );
+                }
+                (start_offset, end)
+            }
+            RawLineSelector::Random(..) => unreachable!("random ranges are rejected by `parse`"),
+        };
+        if start > end {
+            anyhow::bail!("The start of the byte range can't be more than its end");
+        }
+        Ok(start..=end)
+    }
+}
+
+/// Copies the bytes of `range` from `file` to `out` as they are, for `--exact`.
+pub(crate) fn copy_exact(
+    file: &mut File,
+    range: RangeInclusive<u64>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    file.seek(SeekFrom::Start(*range.start()))
+        .context("Failed to seek in file")?;
+    let len = range.end() - range.start() + 1;
+    std::io::copy(&mut file.take(len), out).context("Failed to output byte range")?;
+    out.flush().context("Failed to flush output")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(s: &str, len: u64) -> anyhow::Result<RangeInclusive<u64>> {
+        ByteRange::parse(s)?.resolve(len)
+    }
+
+    #[test]
+    fn resolves_like_line_selectors() {
+        assert_eq!(resolve("3:5", 10).unwrap(), 2..=4);
+        assert_eq!(resolve("7", 10).unwrap(), 6..=6);
+        assert_eq!(resolve("-3:", 10).unwrap(), 7..=9);
+        assert_eq!(resolve(":2", 10).unwrap(), 0..=1);
+        assert_eq!(resolve("4+3", 10).unwrap(), 3..=5);
+        assert_eq!(resolve("1:10:", 10).unwrap(), 0..=9);
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        assert!(resolve("11", 10).is_err());
+        assert!(resolve("9+3", 10).is_err());
+        assert!(resolve("5:3", 10).is_err());
+        assert!(resolve("1:10:2", 10).is_err());
+        assert!(resolve("rand(1:10, 2)", 10).is_err());
+    }
+}
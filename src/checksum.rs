@@ -0,0 +1,30 @@
+use crate::LineSource;
+use crate::cli::ChecksumAlgorithm;
+use crate::line_selector::LineSelector;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// Hashes the content of all selected lines, in selection order, and returns the digest as a
+/// lowercase hex string.
+pub(crate) fn compute(
+    algorithm: ChecksumAlgorithm,
+    line_selectors: &[LineSelector],
+    lines: &mut impl LineSource,
+) -> anyhow::Result<String> {
+    let digest = match algorithm {
+        ChecksumAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            for line_selector in line_selectors {
+                for line_num in line_selector.iter_in_selection_order() {
+                    hasher.update(lines.get(line_num)?);
+                }
+            }
+            hasher.finalize()
+        }
+    };
+
+    Ok(digest.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{byte:02x}").expect("writing to a String can't fail");
+        hex
+    }))
+}
@@ -1,5 +1,13 @@
-use crate::line_selector::RawLineSelector;
-use clap::{Parser, ValueEnum};
+use crate::align::parse_delimiter;
+use crate::byte_range::ByteRange;
+use crate::line_selector::{RawLineSelector, RawLineSelectors};
+use crate::output::{LATEST_JSON_VERSION, Template};
+use crate::sed;
+use crate::size::parse_size;
+use anyhow::Context;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
 // TODO: consider using https://github.com/Canop/clap-help
@@ -11,29 +19,245 @@ use std::path::PathBuf;
     about="Extract specific lines from text files with powerful indexing",
     long_about = "A fast, flexible tool for extracting lines from text files using Python-like \
     indexing.\nSupports ranges, steps, and backward counting.",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    after_help = "Exit codes: 0 on success, 1 if a selected line is out of range (or on other \
+    errors), 2 on invalid arguments, and 3 if a file doesn't exist.",
 )]
 pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+
     /// Line number(s) to extract. Supports ranges (1:5), ranges with steps (1:10:2),
-    /// unbound ranges (5:), negative indices for backward counting, and combinations (1,5:3:-1,:7)
+    /// unbound ranges (5:), counted ranges (100+20 selects lines 100 to 119), negative indices
+    /// for backward counting, random lines from a range (rand(100:2000, 5) picks 5 of them), and
+    /// combinations (1,5:3:-1,:7)
     #[arg(
         short = 'n', 
         long = "line", 
         value_name = "LINE_SELECTORS", 
-        value_parser = RawLineSelector::from_str, 
-        value_delimiter = ',', 
-        required = true,
+        value_parser = RawLineSelector::list_from_str, 
+        required_unless_present_any = [
+            "symbol", "function", "expression", "patterns", "sample", "every", "chunk",
+            "head_bytes", "tail_bytes", "bytes", "line_file"
+        ],
+        help_heading = "Selection"
+    )]
+    pub(crate) raw_line_selectors: Vec<RawLineSelectors>,
+
+    /// Read line selectors from FILE, one per line, in addition to the ones given to `-n`. Use
+    /// `-` to read them from stdin. Handy for large selections generated by other tools, which
+    /// would exceed the maximum length of a command line
+    #[arg(long, value_name = "FILE", help_heading = "Selection")]
+    pub(crate) line_file: Option<PathBuf>,
+
+    /// Select the lines spanned by the symbol called NAME (e.g.: a function, struct, or class).
+    /// Symbols are found with universal-ctags, which must be installed. Can be repeated
+    #[arg(long, value_name = "NAME", help_heading = "Selection")]
+    pub(crate) symbol: Vec<String>,
+
+    /// Select the lines spanned by the function or method called NAME. Like `--symbol`, but other
+    /// kinds of symbols are ignored. Can be repeated
+    #[arg(long, value_name = "NAME", help_heading = "Selection")]
+    pub(crate) function: Vec<String>,
+
+    /// Select the lines addressed by a sed address expression, as in `sed -n 'EXPRESSION'` (e.g.:
+    /// `12,20p`, `/start/,/end/`, `$`, `0~4`, or `/error/,+2`), to reuse existing `sed -n`
+    /// one-liners. Regexes use the syntax of the regex crate rather than POSIX basic regexes, and
+    /// can be made case insensitive with `I` (e.g.: `/error/I`). Can be repeated. To call line
+    /// exactly like `sed -n 'SCRIPT' FILE`, use `line --compat sed -n 'SCRIPT' FILE`, or call it
+    /// through a `linep` symlink
+    #[arg(
+        short = 'e',
+        long,
+        value_name = "EXPRESSION",
+        value_parser = sed::Expression::parse,
+        help_heading = "Selection"
+    )]
+    pub(crate) expression: Vec<sed::Expression>,
+
+    /// Select the lines matching the regex PATTERN, in addition to the lines selected otherwise
+    /// (e.g.: `--match 'error|warn'`). Consecutive matching lines are grouped together, and
+    /// `--before`, `--after`, and `--context` apply around every match. Lines are matched without
+    /// their line ending, using the syntax of the regex crate. Can be repeated to select the lines
+    /// matching any of the patterns
+    #[arg(
+        long = "match",
+        value_name = "PATTERN",
+        conflicts_with = "assume_line_length",
+        help_heading = "Selection"
+    )]
+    pub(crate) patterns: Vec<regex::bytes::Regex>,
+
+    /// Select the lines that aren't selected otherwise instead, like `grep -v`, e.g.: `-v -n=1`
+    /// selects every line but the first one. The unselected lines are grouped into ranges of
+    /// consecutive lines
+    #[arg(short = 'v', long, help_heading = "Selection")]
+    pub(crate) invert: bool,
+
+    /// Print each selected line once, in the order of the file, e.g.: `--unique -n=1,1:3,1:1`
+    /// prints lines 1 to 3 once. The selected lines are grouped into ranges of consecutive lines
+    #[arg(long, help_heading = "Selection")]
+    pub(crate) unique: bool,
+
+    /// Print the file without the selected lines, as is, like `sed '5d;10,20d'` (e.g.: `--delete
+    /// -n=5,10:20`). Lines are printed without decorations or colors, and files without headers
+    #[arg(
+        long,
+        conflicts_with_all = ["invert", "checksum", "count_bytes", "summary", "follow"],
+        help_heading = "Selection"
+    )]
+    pub(crate) delete: bool,
+
+    /// Select every N-th line of the selected ranges, starting with their first line, or of the
+    /// whole file if no lines are selected otherwise. This is a shorthand for the step of range
+    /// selectors, e.g.: `-n=10:50 --every 5` is `-n=10:50:5`. Ranges that have a step already
+    /// have it multiplied by N. Single lines are selected as is
+    #[arg(long, value_name = "N", help_heading = "Selection")]
+    pub(crate) every: Option<NonZeroUsize>,
+
+    /// Start the ranges that don't have a start (e.g.: `:100`) at line M instead of the first
+    /// line, when stepping through them with `--every`. `--every 5 --starting-at 3` is `3::5`
+    #[arg(long, value_name = "M", requires = "every", help_heading = "Selection")]
+    pub(crate) starting_at: Option<NonZeroUsize>,
+
+    /// Split the file into N chunks of consecutive lines, as equal as possible, and select the
+    /// K-th one (e.g.: `--chunk 3/10`), to split the review or the processing of a large file
+    #[arg(long, value_name = "K/N", value_parser = parse_chunk, help_heading = "Selection")]
+    pub(crate) chunk: Option<(usize, usize)>,
+
+    /// Select the lines holding the first SIZE bytes of the file (e.g.: 1M), including all of the
+    /// last line even if SIZE falls in its middle, so no line is cut in half
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, help_heading = "Selection")]
+    pub(crate) head_bytes: Option<usize>,
+
+    /// Select the lines holding the last SIZE bytes of the file (e.g.: 1M), including all of the
+    /// first line even if SIZE falls in its middle, so no line is cut in half
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, help_heading = "Selection")]
+    pub(crate) tail_bytes: Option<usize>,
+
+    /// Select the lines holding the bytes START to END of the file, e.g.: when an error reports a
+    /// byte offset rather than a line number. Offsets are one-based and inclusive like line
+    /// selectors: negative ones count from the end, either end can be left out, and `START+N`
+    /// selects N bytes (e.g.: `--bytes=1200:1300` or `--bytes=-512:`)
+    #[arg(
+        long,
+        value_name = "START:END",
+        value_parser = ByteRange::parse,
+        help_heading = "Selection"
+    )]
+    pub(crate) bytes: Option<ByteRange>,
+
+    /// Print exactly the bytes of `--bytes` as they are, instead of the whole lines holding them
+    #[arg(
+        long,
+        requires = "bytes",
+        conflicts_with = "follow",
         help_heading = "Selection"
     )]
-    pub(crate) raw_line_selectors: Vec<RawLineSelector>,
+    pub(crate) exact: bool,
+
+    /// Select K lines at random, in random order unless `--sample-ordered` is given. Lines are
+    /// picked in a single pass with reservoir sampling, so only K line numbers are kept in memory.
+    /// Every line is selected if the file has K lines or fewer
+    #[arg(long, value_name = "K", help_heading = "Selection")]
+    pub(crate) sample: Option<NonZeroUsize>,
+
+    /// Print the lines picked by `--sample` in file order
+    #[arg(long, requires = "sample", help_heading = "Selection")]
+    pub(crate) sample_ordered: bool,
+
+    /// Seed the random picks of `--sample` and `rand()` selectors, so that they select the same
+    /// lines on every run
+    #[arg(long, value_name = "N", help_heading = "Selection")]
+    pub(crate) seed: Option<u64>,
+
+    /// Treat the input as records ending with a NUL byte instead of lines ending with a new line,
+    /// like `grep -z` (e.g.: for the output of `find -print0`). Records are printed with their NUL
+    /// byte, and the input isn't rejected as binary for containing NUL bytes
+    #[arg(
+        long,
+        short = 'z',
+        conflicts_with_all = ["symbol", "function", "show_meta", "assume_line_length"],
+        help_heading = "Input"
+    )]
+    pub(crate) null_data: bool,
+
+    /// Treat the input as records ending with BYTE instead of lines ending with a new line. BYTE is
+    /// an ASCII character, or an escape among `\0`, `\t`, `\n`, `\r`, `\\`, and `\xHH` (e.g.:
+    /// `--delimiter=';'` or `--delimiter='\x1e'`). `--delimiter='\0'` is the same as `-z`
+    #[arg(
+        long,
+        value_name = "BYTE",
+        value_parser = parse_record_delimiter,
+        conflicts_with_all = ["null_data", "symbol", "function", "show_meta", "assume_line_length"],
+        help_heading = "Input"
+    )]
+    pub(crate) delimiter: Option<u8>,
+
+    /// Fail on the first read error instead of retrying reads that failed with a transient error
+    /// (e.g.: a stale NFS file handle), which are otherwise retried a few times with a backoff
+    #[arg(long, help_heading = "Input")]
+    pub(crate) no_retry: bool,
+
+    /// Refuse to read files larger than SIZE (e.g.: 10G), to avoid scanning a huge file by
+    /// mistake. Use `--force` to read such a file anyway
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, help_heading = "Input")]
+    pub(crate) max_file_size: Option<usize>,
 
     /// Process binary files as text
     #[arg(long, help_heading = "Input")]
     pub(crate) allow_binary_files: bool,
 
-    // TODO: respect NO_COLOR env var, and update the doc below
+    /// Read gzip-, zstd- and bzip2-compressed files as is, instead of reading their decompressed content. Compressed
+    /// files are recognized by their first bytes, whatever their extension
+    #[arg(long, help_heading = "Input")]
+    pub(crate) no_decompress: bool,
+
+    /// The character encoding of the input, which is transcoded to UTF-8. `auto` recognizes UTF-16
+    /// by its byte order mark or by the NUL bytes of its ASCII characters, and reads anything else
+    /// as is. Latin-1 can't be recognized, since any byte is valid Latin-1
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ENCODING",
+        default_value_t = Charset::Auto,
+        help_heading = "Input"
+    )]
+    pub(crate) encoding: Charset,
+
+    /// Fail if a selected line isn't valid UTF-8, reporting the line and the byte offset of the
+    /// first invalid byte, e.g.: as a data-quality check in pipelines. Lines printed before the
+    /// invalid one are still printed
+    #[arg(long, help_heading = "Input")]
+    pub(crate) strict_utf8: bool,
+
+    /// Check whether the file mixes LF and CRLF line endings, which makes lines look different
+    /// than in editors that hide carriage returns. Mixed line endings are reported along with the
+    /// lines ending with the less common one, as a warning or as an error. Counting lines is then
+    /// single-threaded
+    #[arg(
+        long,
+        value_enum,
+        value_name = "LEVEL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "warn",
+        conflicts_with_all = ["null_data", "delimiter", "assume_line_length"],
+        help_heading = "Input"
+    )]
+    pub(crate) check_line_endings: Option<LineEndingCheck>,
+
+    /// Take a shared advisory lock on the file while reading it, so writers that lock the file
+    /// (e.g.: log writers using flock or LockFileEx) can't append partial lines to the region
+    /// being extracted. Waits if a writer holds the lock
+    #[arg(long, help_heading = "Input")]
+    pub(crate) lock: bool,
+
     /// Specify when to use colored output. `auto` turns colors on when an interactive terminal is
-    /// detected, and off when a pipe is detected. `always` turns colors on all the time, even if a
-    /// pipe is detected.
+    /// detected, and off when a pipe is detected, unless NO_COLOR is set (colors off),
+    /// CLICOLOR_FORCE is set (colors on), or CLICOLOR is `0` (colors off). `always` turns colors
+    /// on all the time, even if a pipe is detected.
     #[arg(long, value_enum, help_heading = "Output", default_value_t = When::Auto)]
     pub(crate) color: When,
 
@@ -41,17 +265,350 @@ pub(crate) struct Cli {
     /// Specify when to use paging. `auto` uses paging when an interactive terminal is detected and
     /// the output is too long, and off when a pipe is detected. `always` uses paging all the time,
     /// even if a pipe is detected. This option doesn't affect decorations (e.g.: headers and line
-    /// numbers), you can use `--plain=always` to turn decorations off.
+    /// numbers), you can use `--plain=always` to turn decorations off. The pager is PAGER, or
+    /// `less -RFX` if it isn't set, and an empty PAGER turns paging off.
     #[arg(long, value_enum, help_heading = "Output", default_value_t = When::Auto)]
     pub(crate) paging: When,
 
     /// Specify when to turn off decorations (e.g.: headers and line numbers). `auto` turns
     /// decorations off when a pipe is detected, and on when an interactive terminal is detected.
     /// `always` turns decorations off all the time, even if a pipe is detected. This option
-    /// doesn't affect colors, you can use `--color=never` to turn colored output off.
-    #[arg(short, long, value_enum, help_heading = "Output", default_value_t = When::Auto)]
+    /// doesn't affect colors, you can use `--color=never` to turn colored output off. Passing
+    /// `-p`/`--plain` without a value is equivalent to `--plain=always`.
+    #[arg(
+        short,
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "always",
+        help_heading = "Output",
+        default_value_t = When::Auto
+    )]
     pub(crate) plain: When,
 
+    /// Print decorations as a grid, like `bat`: the file name in a box, a `│` between line numbers
+    /// and lines, and horizontal rules between selectors. This turns decorations on, and replaces
+    /// the `==> file <==` headers of multiple files
+    #[arg(
+        long,
+        conflicts_with_all = ["plain", "delete", "follow"],
+        help_heading = "Output"
+    )]
+    pub(crate) grid: bool,
+
+    /// Wrap decorated lines wider than WIDTH columns (the width of the terminal by default) onto
+    /// the next rows, which are marked with `↪` in the gutter and start under the content of the
+    /// line. Lines aren't wrapped without decorations
+    #[arg(
+        long,
+        value_name = "WIDTH",
+        num_args = 0..=1,
+        require_equals = true,
+        help_heading = "Output"
+    )]
+    pub(crate) wrap: Option<Option<NonZeroUsize>>,
+
+    /// Color theme. `auto` picks `light` on terminals with a light background, as told by the
+    /// COLORFGBG environment variable or by querying the terminal, and `dark` otherwise
+    #[arg(long, value_enum, help_heading = "Output", default_value_t = ThemeChoice::Auto)]
+    pub(crate) theme: ThemeChoice,
+
+    /// TOML file overriding the styles of the theme, with the optional keys `selected`,
+    /// `selected_background`, `selected_number`, `context_number`, `header`, and `match`. A
+    /// style is a color, `bold`, `dim`, `italic`, `underline`, `reverse`, and/or `on` followed by
+    /// a background color, e.g.: `selected = "#ff5f87 bold"`. Colors can be named (e.g. `red`) or
+    /// given in hex, and hex colors are downgraded to 256 or 16 colors unless COLORTERM is
+    /// `truecolor` or `24bit`. Italics and dim text are skipped on terminals that lack them
+    #[arg(long, value_name = "FILE", help_heading = "Output")]
+    pub(crate) theme_file: Option<PathBuf>,
+
+    /// Override one style of the theme, given as NAME=STYLE where NAME is a key of
+    /// `--theme-file` (e.g.: `--style 'selected=yellow bold'`). Overrides the theme file, and can
+    /// be repeated. Styles can also be set in the `[style]` table of the config file
+    #[arg(long, value_name = "NAME=STYLE", help_heading = "Output")]
+    pub(crate) style: Vec<String>,
+
+    /// How selected lines stand out. `color` prints them in the selected color, and `background`
+    /// highlights them with a background color spanning the whole width of the terminal, like
+    /// editors highlight the current line. `bold`, `underline`, and `reverse` (reverse video)
+    /// don't rely on colors, so they're easier to tell apart for color-blind users
+    #[arg(long, value_enum, help_heading = "Output", default_value_t = Emphasis::Color)]
+    pub(crate) emphasis: Emphasis,
+
+    /// Highlight the syntax of the printed lines, like `bat`, when colors are on. The language is
+    /// detected from the name of the file (e.g.: `.rs` or `Makefile`) unless `--language` is given.
+    /// With `--emphasis=color`, selected lines get a background color instead, since the syntax
+    /// colors would hide the selected color. Constructs that start above the printed lines (e.g.:
+    /// block comments) aren't recognized
+    #[arg(long, help_heading = "Output")]
+    pub(crate) highlight: bool,
+
+    /// Language to highlight the syntax of, by name or extension (e.g.: `rust` or `rs`). Implies
+    /// `--highlight`
+    #[arg(long, value_name = "LANGUAGE", help_heading = "Output")]
+    pub(crate) language: Option<String>,
+
+    /// Highlight the text matching REGEX within the printed lines when colors are on, like
+    /// `grep --color` (e.g.: `--highlight-pattern 'TODO|FIXME'`). This doesn't change which
+    /// lines are printed, and uses the syntax of the regex crate
+    #[arg(
+        long,
+        value_name = "REGEX",
+        conflicts_with_all = ["highlight", "language"],
+        help_heading = "Output"
+    )]
+    pub(crate) highlight_pattern: Option<regex::bytes::Regex>,
+
+    /// Use colors even on terminals that can't display them (i.e.: when TERM is `dumb`), where
+    /// colors are otherwise turned off, even with `--color=always`. Also reads files larger than
+    /// `--max-file-size`, and prints binary content decoded by `--decode` to terminals as is
+    #[arg(long, help_heading = "Output")]
+    pub(crate) force: bool,
+
+    /// Ignore the environment so the output is byte-identical on every machine, e.g.: to
+    /// snapshot it in tests. stdout is treated as a pipe, TERM, COLORTERM, NO_COLOR, and the
+    /// CLICOLOR variables are ignored (16 colors are assumed with `--color=always`),
+    /// `--theme=auto` picks `dark`, and messages are in English. Options given explicitly are
+    /// still honored
+    #[arg(long, help_heading = "Output")]
+    pub(crate) canonical: bool,
+
+    /// Format of the output. `msgpack` writes one MessagePack map per printed line, of the form
+    /// `{"number": 42, "content": "...", "selected": true}`, where `content` is a string if the
+    /// line is valid UTF-8 and binary otherwise. `jsonl` writes one JSON object per printed line,
+    /// of the form `{"version": 2, "number": 42, "content": "...", "line_ending": "\n", "selected":
+    /// true}`, where invalid UTF-8 in `content` is replaced, and lines that aren't valid UTF-8 also
+    /// get their exact bytes in a `content_base64` field. Colors and decorations only apply to
+    /// `text`
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        default_value_t = OutputFormat::Text,
+        help_heading = "Output"
+    )]
+    pub(crate) output_format: OutputFormat,
+
+    /// Version of the schema of the `jsonl` output, to keep parsers working as the schema evolves.
+    /// New fields may be added to a version, but fields are only removed or changed in a new
+    /// version. Version 1 has the fields `version`, `number`, `content`, and `selected`, and
+    /// `content_base64` for lines that aren't valid UTF-8. Version 2 leaves the line ending out of
+    /// `content` and `content_base64`, and has it in a `line_ending` field
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u64).range(1..=LATEST_JSON_VERSION),
+        default_value_t = LATEST_JSON_VERSION,
+        help_heading = "Output"
+    )]
+    pub(crate) output_version: u64,
+
+    /// Print each line as TEMPLATE filled in, followed by the line ending of the line, e.g.:
+    /// `--format-str '{file}\t{num}\t{line}'`. The placeholders are `{num}` (the line number),
+    /// `{line}` (the line without its line ending), `{file}`, `{offset}` (the byte offset of the
+    /// line in the file), and `{selected}` (`true` or `false` for context lines). `{{` and `}}`
+    /// are literal braces, and `\t`, `\n`, `\r`, `\0`, and `\\` are escapes. Headers and other
+    /// decorations aren't printed
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        value_parser = Template::parse,
+        conflicts_with_all = ["output_format", "grid"],
+        help_heading = "Output"
+    )]
+    pub(crate) format_str: Option<Template>,
+
+    /// Mark the line ending of each printed line: `␊` for LF, `␍␊` for CRLF, `␀` for NUL with
+    /// `--null-data`, and `∅` for a last line without one. Useful to debug files with mixed line
+    /// endings
+    #[arg(long, help_heading = "Output")]
+    pub(crate) show_eol: bool,
+
+    /// Print CRLF line endings as LF, e.g.: to read files written on Windows without a carriage
+    /// return at the end of each printed line
+    #[arg(long, help_heading = "Output")]
+    pub(crate) strip_cr: bool,
+
+    /// Expand tabs in the printed lines to spaces, up to tab stops N columns apart, so that lines
+    /// are aligned the same way whatever the tab width of the terminal (e.g.: `--tabs=4`)
+    #[arg(long, value_name = "N", help_heading = "Output")]
+    pub(crate) tabs: Option<NonZeroUsize>,
+
+    /// Elide the middle of printed lines longer than N characters, keeping their first and last
+    /// N/2 characters around a marker like `…[12,345 bytes]…`. This keeps both the start (e.g.: a
+    /// timestamp) and the end (e.g.: a status) of long log lines in view
+    #[arg(long, value_name = "N", help_heading = "Output")]
+    pub(crate) elide_long: Option<NonZeroUsize>,
+
+    /// Truncate printed lines longer than N bytes, keeping their first N bytes (or fewer, so that
+    /// no character is cut) followed by a marker like `…[12,345 more bytes]`. This keeps the
+    /// single huge lines of minified files from flooding the terminal
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with = "elide_long",
+        help_heading = "Output"
+    )]
+    pub(crate) max_line_length: Option<usize>,
+
+    /// Decode the selected lines before printing them, e.g.: to read base64 payloads embedded in
+    /// logs. `base64` accepts both the standard and the URL-safe alphabets, and `url` decodes
+    /// `%XX` escapes. Whitespace around the encoded content is ignored, and lines that can't be
+    /// decoded are printed as is. Context lines are printed as is. On a terminal, control
+    /// characters and invalid UTF-8 in decoded lines are hex-escaped like with
+    /// `--escape-control-only`, unless `--force` is given
+    #[arg(long, value_enum, value_name = "ENCODING", help_heading = "Output")]
+    pub(crate) decode: Option<Encoding>,
+
+    /// Hex-escape control characters (e.g.: `\x1b` for an escape character) and invalid UTF-8 in
+    /// the printed lines, leaving the rest of the lines untouched. Unlike the raw output, this is
+    /// safe to print on a terminal, while keeping the lines readable. Tabs and line endings are
+    /// kept as is
+    #[arg(long, help_heading = "Output")]
+    pub(crate) escape_control_only: bool,
+
+    /// Make invisible characters visible, like `cat -A`: tabs are printed as `→` (followed by
+    /// spaces up to the next tab stop of `--tabs`, or 4), trailing spaces as `·`, control
+    /// characters as their symbol (e.g.: `␍` for a carriage return or `␛` for an escape), and
+    /// line endings like `--show-eol`. Invalid UTF-8 is hex-escaped
+    #[arg(
+        short = 'A',
+        long,
+        conflicts_with = "escape_control_only",
+        help_heading = "Output"
+    )]
+    pub(crate) show_all: bool,
+
+    /// Align the fields of the selected lines in columns, like `column -t`, splitting them on
+    /// DELIM (e.g.: `,` for CSV, or `\t` for TSV). Context lines are printed as is
+    #[arg(long, value_name = "DELIM", value_parser = parse_delimiter, help_heading = "Output")]
+    pub(crate) align: Option<String>,
+
+    /// Print only the given fields of the selected lines that are JSON objects (e.g.:
+    /// `ts,level,msg`). Nested fields can be given as `a.b`. Other lines are printed as is
+    #[arg(long, value_name = "FIELDS", value_delimiter = ',', help_heading = "Output")]
+    pub(crate) json_fields: Vec<String>,
+
+    /// Print the fields given to `--json-fields` as aligned columns instead of JSON objects
+    #[arg(long, requires = "json_fields", conflicts_with = "align", help_heading = "Output")]
+    pub(crate) json_table: bool,
+
+    /// Sort the selected lines by their content before printing them, keeping their line
+    /// numbers. `numeric` sorts by the number at the start of each line, like `sort -n`, and
+    /// `reverse` sorts lexically in reverse. Lines of all selectors are sorted together, so
+    /// selector headers aren't printed. Passing `--sort-content` without a value is equivalent to
+    /// `--sort-content=lexical`
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ORDER",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "lexical",
+        conflicts_with_all = ["before", "after", "context", "window"],
+        help_heading = "Output"
+    )]
+    pub(crate) sort_content: Option<SortOrder>,
+
+    /// Print a ruler of column numbers above the lines of each selector, like
+    /// `----+----1----+----2`, to find character positions in fixed-width records or long lines.
+    /// The ruler is as wide as the widest line below it, and aligned with their content
+    #[arg(long, conflicts_with = "sort_content", help_heading = "Output")]
+    pub(crate) ruler: bool,
+
+    /// Add N to the displayed line numbers, e.g.: when the input is a chunk split out of a larger
+    /// file, to number its lines like in the original file. Selectors still count from the first
+    /// line of the input
+    #[arg(long, value_name = "N", default_value_t = 0, help_heading = "Output")]
+    pub(crate) offset_base: usize,
+
+    /// Write the output into FILE instead of stdout. FILE is only replaced once the whole output
+    /// is written, so it can also be the input file. Lines are only colored if `--color=always`
+    /// is given, and decorated if `--plain=never` is given
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["split_every", "follow"],
+        help_heading = "Output"
+    )]
+    pub(crate) output: Option<PathBuf>,
+
+    /// Print nothing, and only tell whether all the selected lines exist through the exit code,
+    /// e.g.: `if line -q -n=500 file; then ...`. The exit code is 0 if they do, 1 if a selected
+    /// line is out of range, and 3 if a file doesn't exist, with or without `--quiet`
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["output", "in_place", "split_every", "follow", "watch"],
+        help_heading = "Output"
+    )]
+    pub(crate) quiet: bool,
+
+    /// Replace each file with its output instead of printing it, keeping its permissions. A file
+    /// is only replaced once its whole output is written
+    #[arg(
+        short,
+        long,
+        requires = "delete",
+        conflicts_with_all = ["output", "split_every"],
+        help_heading = "Output"
+    )]
+    pub(crate) in_place: bool,
+
+    /// Write the output into consecutive files of N lines each instead of stdout, like `split`,
+    /// to export a region of a huge file in manageable pieces. Files are named after
+    /// `--output-prefix` and their number, e.g.: `part_00`, `part_01`, etc. Lines are only
+    /// decorated if `--plain=never` is given
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["checksum", "count_bytes"],
+        help_heading = "Output"
+    )]
+    pub(crate) split_every: Option<NonZeroUsize>,
+
+    /// The prefix of the files written by `--split-every`, which may include a directory
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        default_value = "part_",
+        requires = "split_every",
+        help_heading = "Output"
+    )]
+    pub(crate) output_prefix: String,
+
+    /// Print a checksum of the selected lines instead of the lines themselves. Only the content
+    /// of the selected lines is hashed, in selection order, without line numbers or colors
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ALGORITHM",
+        conflicts_with_all = ["before", "after", "context"],
+        help_heading = "Output"
+    )]
+    pub(crate) checksum: Option<ChecksumAlgorithm>,
+
+    /// Print the number of bytes of each selector, and their total, instead of the selected lines
+    #[arg(
+        long,
+        conflicts_with_all = ["before", "after", "context", "checksum"],
+        help_heading = "Output"
+    )]
+    pub(crate) count_bytes: bool,
+
+    /// Print a one-line summary after the output, e.g.: `3 selectors, 128 lines, 9.4 KiB from
+    /// file.txt`. The summary goes to stdout when decorations are on, and to stderr otherwise so
+    /// it doesn't pollute piped output
+    #[arg(long, help_heading = "Output")]
+    pub(crate) summary: bool,
+
+    /// Print a header with the file path, size, line count, detected encoding, and line ending
+    /// style before the output
+    #[arg(long, help_heading = "Output")]
+    pub(crate) show_meta: bool,
+
     /// Show N lines before each selected line
     #[arg(long, short, value_name = "N", default_value_t = 0, help_heading = "Context")]
     pub(crate) before: usize,
@@ -71,13 +628,326 @@ pub(crate) struct Cli {
     )]
     pub(crate) context: usize,
 
+    /// Show B lines before and A lines after each selected line, e.g.: `--window 2:5` (equivalent
+    /// to --before=B --after=A)
+    #[arg(
+        long,
+        short,
+        value_parser = parse_window,
+        conflicts_with_all = ["before", "after", "context"],
+        value_name = "B:A",
+        help_heading = "Context"
+    )]
+    pub(crate) window: Option<(usize, usize)>,
+
+    /// Print `--- (start of file) ---` or `--- (end of file) ---` where the context of a selected
+    /// line is cut short by the start or the end of the file, e.g.: with `--context=5` on line 2,
+    /// so it's clear why fewer context lines are printed
+    #[arg(long, help_heading = "Context")]
+    pub(crate) mark_file_bounds: bool,
+
+    /// Print SEP between the groups of selected lines and their context, like grep. Selected lines
+    /// whose context overlaps or touches are printed in the same group, so no line is printed
+    /// twice. An empty SEP prints an empty line
+    #[arg(
+        long,
+        value_name = "SEP",
+        default_value = "--",
+        allow_hyphen_values = true,
+        help_heading = "Context"
+    )]
+    pub(crate) group_separator: String,
+
+    /// Assume that every line is exactly N bytes long, line ending included (the last line may
+    /// lack its line ending). Lines are then found by seeking straight to their offset instead of
+    /// scanning the file, which makes selecting lines from huge fixed-width files instant
+    #[arg(long, value_name = "N", help_heading = "Performance")]
+    pub(crate) assume_line_length: Option<NonZeroUsize>,
+
+    /// Map the file into memory and scan it with `memchr` instead of reading it through a buffer,
+    /// which is faster on multi-GB files. The file must not be truncated while it's read
+    #[arg(long, conflicts_with = "assume_line_length", help_heading = "Performance")]
+    pub(crate) mmap: bool,
+
+    /// Keep watching the file once the selected lines are printed, and print the lines appended to
+    /// it as they arrive, like `tail -f` (e.g.: `-n=-5: -f` prints the last five lines, then the
+    /// new ones). The file is followed from its start again if it's truncated or replaced (e.g.:
+    /// by log rotation)
+    #[arg(
+        short = 'f',
+        long,
+        conflicts_with_all = [
+            "checksum", "count_bytes", "split_every", "sort_content", "summary",
+            "assume_line_length"
+        ],
+        help_heading = "Input"
+    )]
+    pub(crate) follow: bool,
+
+    /// Print the selected lines again whenever one of the files changes, e.g.: to keep an eye on
+    /// a few lines of a config file while editing it elsewhere. The screen is cleared before
+    /// each reprint when printing to a terminal, and errors (e.g.: a selected line that's gone)
+    /// are printed until the next change
+    #[arg(
+        long,
+        conflicts_with_all = ["follow", "output", "in_place", "split_every"],
+        help_heading = "Input"
+    )]
+    pub(crate) watch: bool,
+
+    /// Keep the byte offsets of every 1024th line of the file in `FILE.line-idx`, so that later
+    /// runs on the same file skip counting its lines and seek close to the selected lines. The
+    /// index is rebuilt when the size or the modification time of the file changes
+    #[arg(
+        long,
+        conflicts_with_all = ["assume_line_length", "mmap"],
+        help_heading = "Performance"
+    )]
+    pub(crate) index: bool,
+
+    /// Number of threads used to count the lines of large files. Defaults to the number of
+    /// available CPUs
+    #[arg(long, value_name = "N", help_heading = "Performance")]
+    pub(crate) threads: Option<NonZeroUsize>,
+
+    /// Size of the buffer used to read the input (e.g.: 64K or 1M). Defaults to a size based on the
+    /// size of the file, from 8K for small files up to 1M for files of 1G or more
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, help_heading = "Performance")]
+    pub(crate) read_buffer: Option<usize>,
+
+    /// Flush the output after every printed line, so that tools reading from a pipe see each line
+    /// as soon as it's printed instead of once the write buffer fills up
+    #[arg(long, help_heading = "Performance")]
+    pub(crate) line_buffered: bool,
+
+    /// Size of the buffer used to write the output (e.g.: 64K or 1M)
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_size,
+        default_value = "64K",
+        help_heading = "Performance"
+    )]
+    pub(crate) write_buffer: usize,
+
+    /// Print the completion script of SHELL (e.g.: `line --generate-completions bash >
+    /// /usr/share/bash-completion/completions/line`)
+    #[arg(long, value_name = "SHELL", exclusive = true, hide = true)]
+    pub(crate) generate_completions: Option<clap_complete::Shell>,
+
     // TODO: support stdin
-    /// Input file (omit or use '-' for stdin)
-    #[arg(value_name = "FILE")]
-    pub(crate) file: PathBuf,
+    /// Input file(s). With more than one file, the lines of each file are printed after a
+    /// `==> FILE <==` header, and negative selectors count from the end of each file
+    #[arg(value_name = "FILE", required = true)]
+    pub(crate) files: Vec<PathBuf>,
+}
+
+impl Cli {
+    /// Returns whether the output is printed to stdout, rather than written into files.
+    pub(crate) fn prints_to_stdout(&self) -> bool {
+        self.output.is_none() && !self.in_place && self.split_every.is_none()
+    }
+
+    /// Returns the byte that ends each line, set by `-z` or `--delimiter`.
+    pub(crate) fn separator(&self) -> u8 {
+        match self.delimiter {
+            Some(delimiter) => delimiter,
+            None if self.null_data => b'\0',
+            None => b'\n',
+        }
+    }
+}
+
+/// Parses a record delimiter, which is either an ASCII character or an escape sequence.
+fn parse_record_delimiter(s: &str) -> anyhow::Result<u8> {
+    let byte = match s.as_bytes() {
+        [byte] if byte.is_ascii() => *byte,
+        b"\\0" => b'\0',
+        b"\\t" => b'\t',
+        b"\\n" => b'\n',
+        b"\\r" => b'\r',
+        b"\\\\" => b'\\',
+        [b'\\', b'x', hex @ ..] if hex.len() == 2 => std::str::from_utf8(hex)
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .with_context(|| format!("Escape `{s}` isn't of the form \\xHH (e.g.: \\x1e)"))?,
+        _ => anyhow::bail!(
+            "Delimiter `{s}` isn't a single ASCII character or an escape (e.g.: `;` or `\\0`)"
+        ),
+    };
+    Ok(byte)
+}
+
+/// Parses a chunk of the form `K/N` into the (one-based) index of the chunk and the number of
+/// chunks.
+fn parse_chunk(s: &str) -> anyhow::Result<(usize, usize)> {
+    let (k, n) = s
+        .split_once('/')
+        .with_context(|| format!("Chunk `{s}` isn't of the form K/N (e.g.: 3/10)"))?;
+    let parse = |n: &str| {
+        n.trim()
+            .parse::<usize>()
+            .with_context(|| format!("Value `{n}` is not a number"))
+    };
+    let (k, n) = (parse(k)?, parse(n)?);
+    if k == 0 || k > n {
+        anyhow::bail!("Chunk {k} doesn't exist, chunks are numbered from 1 to {n}");
+    }
+    Ok((k, n))
+}
+
+/// Parses a context window of the form `B:A` into the number of lines before and after.
+fn parse_window(s: &str) -> anyhow::Result<(usize, usize)> {
+    let (before, after) = s
+        .split_once(':')
+        .with_context(|| format!("Window `{s}` isn't of the form B:A (e.g.: 2:5)"))?;
+    let parse = |n: &str| {
+        n.trim()
+            .parse::<usize>()
+            .with_context(|| format!("Value `{n}` is not a number"))
+    };
+    Ok((parse(before)?, parse(after)?))
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// Time the line counting pass, the extraction of random lines, and a dump of all lines of
+    /// FILE, with and without `--mmap` and `--index`, reporting the throughput of each. Useful to
+    /// pick the right flags for your storage
+    Bench {
+        /// File to benchmark
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+
+    /// Print the number of lines of each FILE, and their total, like a faster `wc -l` for large
+    /// files. Unlike `wc -l`, a last line without a line ending is counted
+    Count {
+        /// Files to count the lines of
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+    },
+
+    /// Serve the files under ROOT over HTTP, so that dashboards and remote tools can request
+    /// lines with the same selectors as `-n`, e.g.:
+    /// `GET /file?path=app.log&n=100:200&context=2`. Lines are returned as JSON, or as plain text
+    /// with `format=text`, and at most 100000 lines are returned per request. Paths outside of
+    /// ROOT are refused
+    Serve {
+        /// Address to listen on
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+
+        /// Directory whose files are served
+        #[arg(value_name = "ROOT", default_value = ".")]
+        root: PathBuf,
+    },
+
+    /// Print a shell integration script that binds Ctrl-G to a widget, which asks for a selector
+    /// and turns the file name before the cursor into a `line -n=SELECTOR FILE` command. Add
+    /// `eval "$(line shell-init bash)"` to ~/.bashrc, `eval "$(line shell-init zsh)"` to ~/.zshrc,
+    /// or `line shell-init fish | source` to ~/.config/fish/config.fish
+    ShellInit {
+        /// Shell to integrate with
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Print the number of lines of FILE and statistics about their lengths, such as the longest
+    /// line. Lengths are in bytes, without line endings
+    Stats {
+        /// File to measure
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Only measure the lines selected by SELECTOR (e.g.: `100:200`), which takes the same
+        /// syntax as a single selector of the main `-n` option
+        #[arg(
+            short = 'n',
+            long = "line",
+            value_name = "SELECTOR",
+            value_parser = RawLineSelector::from_str
+        )]
+        line_selector: Option<RawLineSelector>,
+
+        /// Also print a histogram of the line lengths, in buckets that double in size, to spot a
+        /// few enormous lines among many short ones
+        #[arg(long)]
+        histogram: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum ThemeChoice {
+    Auto,
+    Dark,
+    Light,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum LineEndingCheck {
+    Warn,
+    Error,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum Emphasis {
+    Color,
+    Background,
+    Bold,
+    Underline,
+    Reverse,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum OutputFormat {
+    Text,
+    #[value(name = "msgpack")]
+    MessagePack,
+    #[value(name = "jsonl")]
+    JsonLines,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum Encoding {
+    Base64,
+    Hex,
+    Url,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Charset {
+    Auto,
+    #[value(name = "utf-8", alias = "utf8")]
+    Utf8,
+    #[value(name = "utf-16le", alias = "utf16le")]
+    Utf16Le,
+    #[value(name = "utf-16be", alias = "utf16be")]
+    Utf16Be,
+    #[value(name = "latin1", alias = "iso-8859-1")]
+    Latin1,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum SortOrder {
+    Lexical,
+    Numeric,
+    Reverse,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum ChecksumAlgorithm {
+    Sha256,
 }
 
-#[derive(ValueEnum, Clone, Debug)]
+#[derive(ValueEnum, Clone, Copy, Debug)]
 pub(crate) enum When {
     Auto,
     Always,
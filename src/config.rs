@@ -0,0 +1,436 @@
+use crate::cli::Cli;
+use crate::paths;
+use anyhow::Context;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Id};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Returns the path of the config file: `LINE_CONFIG_PATH` if it's set, or
+/// `$XDG_CONFIG_HOME/line/config.toml` (`~/.config/line/config.toml` by default) otherwise.
+/// Returns `None` if `LINE_CONFIG_PATH` is set but empty, which turns the config file off.
+fn config_path() -> Option<(PathBuf, bool)> {
+    if let Some(path) = std::env::var_os("LINE_CONFIG_PATH") {
+        return (!path.is_empty()).then(|| (PathBuf::from(path), true));
+    }
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some((config_dir.join("line").join("config.toml"), false))
+}
+
+/// Parses `args`, with the options set in the config file (see [`config_path`]), then the ones in
+/// `LINE_OPTS`, as defaults.
+///
+/// The config file is a TOML file whose keys are the long names of options, with either hyphens
+/// or underscores (e.g.: `plain = true`, `context = 2`, or `color = "never"`). `LINE_OPTS` holds
+/// arguments split like a shell would (e.g.: `--plain --color=never`). Options given in `args`
+/// override both, as do options given again later in `args`: a default is dropped if `args` set
+/// the same option (so the values of `--match` replace the default ones instead of adding to
+/// them) or an option it conflicts with (e.g.: `--before` drops a default `--context`), and
+/// `LINE_OPTS` overrides the config file the same way. Flags without a value (e.g.:
+/// `--line-buffered`) can't be turned off once they are set as defaults. Subcommands ignore the
+/// defaults.
+pub(crate) fn parse_with_defaults(args: Vec<OsString>) -> anyhow::Result<Cli> {
+    let matches = parse(args.iter().cloned());
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if cli.command.is_some() || cli.generate_completions.is_some() {
+        return Ok(cli);
+    }
+    let mut command = Cli::command();
+    // so that the number of values each option takes is known
+    command.build();
+
+    let mut defaults = split_options(&command, args_from_config_file()?);
+    if let Some(opts) = std::env::var_os("LINE_OPTS") {
+        let opts = opts
+            .into_string()
+            .map_err(|_| anyhow::anyhow!("`LINE_OPTS` isn't valid UTF-8"))?;
+        let opts = split_options(&command, split_words(&opts).context("Invalid `LINE_OPTS`")?);
+        let ids: Vec<&Id> = opts
+            .iter()
+            .filter_map(|option| option.id.as_ref())
+            .collect();
+        defaults.retain(|option| !is_overridden(&command, option, &ids));
+        defaults.extend(opts);
+    }
+    let ids: Vec<&Id> = command
+        .get_arguments()
+        .map(clap::Arg::get_id)
+        .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+        .collect();
+    defaults.retain(|option| !is_overridden(&command, option, &ids));
+    if defaults.is_empty() {
+        return Ok(cli);
+    }
+
+    // the defaults come first, so that the options given on the command line override them
+    let mut args = args.into_iter();
+    let defaults = defaults
+        .into_iter()
+        .flat_map(|option| option.words)
+        .map(OsString::from);
+    let args = args.next().into_iter().chain(defaults).chain(args);
+    let mut matches = parse(args);
+    Ok(Cli::from_arg_matches_mut(&mut matches).unwrap_or_else(|err| err.exit()))
+}
+
+/// Parses `args`, where the last of the values given to an option wins (e.g.: `-c=2 -c=0`), or
+/// exits with a usage error.
+fn parse(args: impl IntoIterator<Item = OsString>) -> ArgMatches {
+    Cli::command()
+        .args_override_self(true)
+        .try_get_matches_from(args)
+        .unwrap_or_else(|err| err.exit())
+}
+
+/// An argument given as a default, with the words it's made of (e.g.: `-c` and `2`).
+#[derive(Debug, PartialEq)]
+struct DefaultArg {
+    /// The option given, or `None` if the argument isn't a known option (e.g.: a file), in which
+    /// case it's always kept and left for clap to reject if it's invalid
+    id: Option<Id>,
+    words: Vec<String>,
+}
+
+/// Splits `words` into the arguments they give, the way `command` parses them: `--long=value`,
+/// `--long value`, `-s value`, `-svalue`, and bundled flags such as `-pq`, which are split apart.
+fn split_options(command: &clap::Command, words: Vec<String>) -> Vec<DefaultArg> {
+    // options whose value is required may take it from the next word
+    let takes_next_word = |arg: &clap::Arg| {
+        arg.get_num_args()
+            .is_some_and(|num_args| num_args.takes_values() && num_args.min_values() > 0)
+    };
+    let mut words = words.into_iter();
+    let mut args = Vec::new();
+    while let Some(word) = words.next() {
+        if word == "--" {
+            args.push(DefaultArg {
+                id: None,
+                words: [word].into_iter().chain(words.by_ref()).collect(),
+            });
+        } else if let Some(long) = word.strip_prefix("--") {
+            let (name, value) = long.split_once('=').unzip();
+            let name = name.unwrap_or(long);
+            let arg = command.get_arguments().find(|arg| {
+                arg.get_long() == Some(name)
+                    || arg
+                        .get_all_aliases()
+                        .is_some_and(|aliases| aliases.contains(&name))
+            });
+            let mut option_words = vec![word.clone()];
+            if let Some(arg) = arg
+                && value.is_none()
+                && takes_next_word(arg)
+            {
+                option_words.extend(words.next());
+            }
+            args.push(DefaultArg {
+                id: arg.map(|arg| arg.get_id().clone()),
+                words: option_words,
+            });
+        } else if let Some(shorts) = word.strip_prefix('-')
+            && !shorts.is_empty()
+        {
+            for (i, short) in shorts.char_indices() {
+                let Some(arg) = command.get_arguments().find(|arg| {
+                    arg.get_short() == Some(short)
+                        || arg
+                            .get_all_short_aliases()
+                            .is_some_and(|aliases| aliases.contains(&short))
+                }) else {
+                    args.push(DefaultArg {
+                        id: None,
+                        words: vec![format!("-{}", &shorts[i..])],
+                    });
+                    break;
+                };
+                let id = Some(arg.get_id().clone());
+                // the rest of the word is the value, if any, and more flags otherwise
+                let value = &shorts[i + short.len_utf8()..];
+                let takes_values = arg
+                    .get_num_args()
+                    .is_some_and(|num_args| num_args.takes_values());
+                if !takes_values || (arg.is_require_equals_set() && !value.starts_with('=')) {
+                    args.push(DefaultArg {
+                        id,
+                        words: vec![format!("-{short}")],
+                    });
+                    continue;
+                }
+                let mut option_words = vec![format!("-{short}{value}")];
+                if value.is_empty() && takes_next_word(arg) {
+                    option_words.extend(words.next());
+                }
+                args.push(DefaultArg {
+                    id,
+                    words: option_words,
+                });
+                break;
+            }
+        } else {
+            args.push(DefaultArg {
+                id: None,
+                words: vec![word],
+            });
+        }
+    }
+    args
+}
+
+/// Returns true if `arg` is given by one of the options `ids`, or conflicts with one of them.
+fn is_overridden(command: &clap::Command, arg: &DefaultArg, ids: &[&Id]) -> bool {
+    let Some(id) = &arg.id else {
+        return false;
+    };
+    let find = |id: &Id| command.get_arguments().find(|arg| arg.get_id() == id);
+    let conflicts = |a: &Id, b: &Id| {
+        find(a).is_some_and(|a| {
+            command
+                .get_arg_conflicts_with(a)
+                .iter()
+                .any(|conflict| conflict.get_id() == b)
+        })
+    };
+    ids.iter()
+        .any(|&other| other == id || conflicts(id, other) || conflicts(other, id))
+}
+
+/// Returns the options set in the config file as command line arguments, or none if there's no
+/// config file.
+fn args_from_config_file() -> anyhow::Result<Vec<String>> {
+    let Some((path, is_explicit)) = config_path() else {
+        return Ok(Vec::new());
+    };
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        // the default config file is optional
+        Err(err) if !is_explicit && err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(err) => {
+            return Err(err).with_context(|| {
+                format!("Failed to read config file `{}`", paths::display(&path))
+            });
+        }
+    };
+    args_from_config(&content)
+        .with_context(|| format!("Invalid config file `{}`", paths::display(&path)))
+}
+
+/// Splits `s` into words like a POSIX shell, without expansions: words are separated by
+/// whitespace, and quotes and backslashes keep whitespace within a word (e.g.: `--match 'a b'`).
+fn split_words(s: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\\' => {
+                // a trailing backslash is kept as is
+                let c = chars.next().unwrap_or('\\');
+                word.get_or_insert_default().push(c);
+            }
+            '\'' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => anyhow::bail!("Unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        // within double quotes, backslashes only escape these
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => word.extend(['\\', c]),
+                            None => anyhow::bail!("Unterminated double quote"),
+                        },
+                        Some(c) => word.push(c),
+                        None => anyhow::bail!("Unterminated double quote"),
+                    }
+                }
+            }
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Turns the options set in a config file into command line arguments (e.g.: `context = 2` into
+/// `--context=2`).
+fn args_from_config(content: &str) -> anyhow::Result<Vec<String>> {
+    let table: toml::Table = content.parse()?;
+    let mut command = Cli::command();
+    // so that the number of values each option takes is known
+    command.build();
+    let mut args = Vec::new();
+    for (key, value) in table {
+        // the `[style]` table holds styles like a theme file, each of which becomes a `--style`
+        if key == "style"
+            && let toml::Value::Table(styles) = &value
+        {
+            for (name, style) in styles {
+                let Some(style) = style.as_str() else {
+                    anyhow::bail!("The value of `style.{name}` must be a string");
+                };
+                args.push(format!("--style={name}={style}"));
+            }
+            continue;
+        }
+        let long = key.replace('_', "-");
+        let Some(arg) = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long.as_str()) && !arg.is_hide_set())
+        else {
+            anyhow::bail!("Unknown option `{key}`");
+        };
+        let num_args = arg.get_num_args().unwrap_or_default();
+        // e.g.: `--plain`, which is equivalent to `--plain=always`
+        let value_is_optional = num_args.min_values() == 0;
+        let values = match value {
+            toml::Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            let value = match value {
+                toml::Value::Boolean(true) if value_is_optional => {
+                    args.push(format!("--{long}"));
+                    continue;
+                }
+                // flags can only be turned on
+                toml::Value::Boolean(false) if !num_args.takes_values() => continue,
+                toml::Value::String(value) => value,
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                toml::Value::Boolean(value) => value.to_string(),
+                _ => anyhow::bail!("The value of `{key}` must be a string, a number, or a boolean"),
+            };
+            args.push(format!("--{long}={value}"));
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(content: &str) -> anyhow::Result<Vec<String>> {
+        args_from_config(content)
+    }
+
+    fn split(words: &str) -> Vec<(Option<String>, Vec<String>)> {
+        let mut command = Cli::command();
+        command.build();
+        split_options(&command, split_words(words).unwrap())
+            .into_iter()
+            .map(|arg| (arg.id.map(|id| id.as_str().to_owned()), arg.words))
+            .collect()
+    }
+
+    #[test]
+    fn options_become_args() {
+        assert_eq!(
+            args("canonical = false\ncontext = 2\ncolor = \"never\"\ntheme_file = \"a.toml\"")
+                .unwrap(),
+            ["--color=never", "--context=2", "--theme-file=a.toml"]
+        );
+        assert_eq!(args("line-buffered = true").unwrap(), ["--line-buffered"]);
+        assert_eq!(args("plain = true").unwrap(), ["--plain"]);
+        assert_eq!(args("plain = \"never\"").unwrap(), ["--plain=never"]);
+        assert!(args("line-buffered = false").unwrap().is_empty());
+        assert_eq!(
+            args("[style]\nselected = \"yellow bold\"").unwrap(),
+            ["--style=selected=yellow bold"]
+        );
+        assert_eq!(
+            args("match = [\"a\", \"b\"]").unwrap(),
+            ["--match=a", "--match=b"]
+        );
+    }
+
+    #[test]
+    fn options_are_split_apart() {
+        assert_eq!(
+            split("-c 1 --before=2 --after 3 -pq -w1:1 -n 5 -p=never file -- -x"),
+            [
+                (Some("context".into()), vec!["-c".into(), "1".into()]),
+                (Some("before".into()), vec!["--before=2".into()]),
+                (Some("after".into()), vec!["--after".into(), "3".into()]),
+                (Some("plain".into()), vec!["-p".into()]),
+                (Some("quiet".into()), vec!["-q".into()]),
+                (Some("window".into()), vec!["-w1:1".into()]),
+                (
+                    Some("raw_line_selectors".into()),
+                    vec!["-n".into(), "5".into()]
+                ),
+                (Some("plain".into()), vec!["-p=never".into()]),
+                (None, vec!["file".into()]),
+                (None, vec!["--".into(), "-x".into()]),
+            ]
+        );
+        // optional values are only given with `=`
+        assert_eq!(
+            split("--plain never"),
+            [
+                (Some("plain".into()), vec!["--plain".into()]),
+                (None, vec!["never".into()])
+            ]
+        );
+    }
+
+    #[test]
+    fn overridden_options() {
+        let mut command = Cli::command();
+        command.build();
+        let id = |name: &'static str| Id::from(name);
+        let arg = |name: &'static str| DefaultArg {
+            id: Some(id(name)),
+            words: Vec::new(),
+        };
+        let (context, before, grid) = (id("context"), id("before"), id("grid"));
+        assert!(is_overridden(&command, &arg("context"), &[&context]));
+        assert!(is_overridden(&command, &arg("context"), &[&before]));
+        assert!(is_overridden(&command, &arg("before"), &[&context]));
+        assert!(is_overridden(&command, &arg("plain"), &[&grid]));
+        assert!(!is_overridden(&command, &arg("after"), &[&before]));
+        let file = DefaultArg {
+            id: None,
+            words: Vec::new(),
+        };
+        assert!(!is_overridden(&command, &file, &[&context]));
+    }
+
+    #[test]
+    fn shell_words() {
+        assert_eq!(
+            split_words("  --plain\t--color=never ").unwrap(),
+            ["--plain", "--color=never"]
+        );
+        assert_eq!(
+            split_words(r#"--match 'a b' --match="c \"d\" \e" x\ y '' "#).unwrap(),
+            ["--match", "a b", "--match=c \"d\" \\e", "x y", ""]
+        );
+        assert!(split_words("--match 'a").is_err());
+        assert!(split_words("--match \"a").is_err());
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(args("nope = 1").is_err());
+        assert!(args("generate_completions = \"bash\"").is_err());
+        assert!(args("context = { a = 1 }").is_err());
+        assert!(args("context = ").is_err());
+        assert!(args("[style]\nselected = 1").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use crate::line_counter::count_lines;
+use crate::{default_read_buffer_size, open_file, paths};
+use anyhow::Context;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Prints the number of lines of each file in `paths`, followed by their total if there are more
+/// than one, `wc -l` style. Large files are counted on `threads` threads.
+///
+/// Unlike `wc -l`, a last line without a line ending is counted.
+pub(crate) fn run(paths: &[PathBuf], threads: usize) -> anyhow::Result<()> {
+    let line_counts = paths
+        .iter()
+        .map(|path| count_file_lines(path, threads))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    print_line_counts(&mut std::io::stdout().lock(), paths, &line_counts)
+        .context("Failed to output line counts")
+}
+
+fn count_file_lines(path: &Path, threads: usize) -> anyhow::Result<usize> {
+    // `open_file` refuses empty files, which have no lines to count anyway
+    if let Ok(metadata) = std::fs::metadata(paths::for_opening(path))
+        && metadata.is_file()
+        && metadata.len() == 0
+    {
+        return Ok(0);
+    }
+
+    let file = open_file(path)?;
+    let read_buffer = default_read_buffer_size(&file)?;
+    count_lines(
+        &mut BufReader::with_capacity(read_buffer, file),
+        threads,
+        b'\n',
+    )
+    .with_context(|| format!("Couldn't count the lines of `{}`", paths::display(path)))
+}
+
+fn print_line_counts(
+    writer: &mut impl Write,
+    paths: &[PathBuf],
+    line_counts: &[usize],
+) -> std::io::Result<()> {
+    let total: usize = line_counts.iter().sum();
+    let width = total.to_string().len();
+    for (path, line_count) in paths.iter().zip(line_counts) {
+        writeln!(writer, "{line_count:>width$} {}", paths::display(path))?;
+    }
+    if paths.len() > 1 {
+        writeln!(writer, "{total:>width$} total")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use crate::cli::Encoding;
+use crate::escape::split_terminator;
+
+/// Decodes the content of `line`, keeping its line ending (a trailing `terminator`, and a `\r`
+/// before a trailing `\n`) as is. Returns `None` if the content isn't valid in `encoding`.
+///
+/// Whitespace around the content is ignored.
+pub(crate) fn decode(line: &[u8], encoding: Encoding, terminator: u8) -> Option<Vec<u8>> {
+    let (content, line_ending) = split_terminator(line, terminator);
+    let content = content.trim_ascii();
+    let mut decoded = match encoding {
+        Encoding::Base64 => decode_base64(content),
+        Encoding::Hex => decode_hex(content),
+        Encoding::Url => decode_url(content),
+    }?;
+    decoded.extend_from_slice(line_ending);
+    Some(decoded)
+}
+
+/// Decodes both the standard and the URL-safe alphabets, with or without padding.
+fn decode_base64(content: &[u8]) -> Option<Vec<u8>> {
+    fn sextet(byte: u8) -> Option<u32> {
+        let sextet = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        Some(sextet.into())
+    }
+
+    let unpadded = content
+        .strip_suffix(b"==")
+        .or_else(|| content.strip_suffix(b"="));
+    if unpadded.is_some() && !content.len().is_multiple_of(4) {
+        return None;
+    }
+    let content = unpadded.unwrap_or(content);
+    // a single sextet left over doesn't make up a byte
+    if content.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(content.len() / 4 * 3 + 2);
+    for chunk in content.chunks(4) {
+        let mut bits = 0;
+        for &byte in chunk {
+            bits = bits << 6 | sextet(byte)?;
+        }
+        bits <<= 6 * (4 - chunk.len());
+        let bytes = bits.to_be_bytes();
+        decoded.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Some(decoded)
+}
+
+fn decode_hex(content: &[u8]) -> Option<Vec<u8>> {
+    if !content.len().is_multiple_of(2) {
+        return None;
+    }
+    content
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Decodes `%XX` escapes. Unlike in query strings, `+` is kept as is.
+fn decode_url(content: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(content.len());
+    let mut bytes = content.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [*bytes.next()?, *bytes.next()?];
+            decoded.push(decode_hex(&hex)?[0]);
+        } else {
+            decoded.push(byte);
+        }
+    }
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(line: &[u8], encoding: Encoding) -> Option<Vec<u8>> {
+        super::decode(line, encoding, b'\n')
+    }
+
+    #[test]
+    fn base64() {
+        for (line, decoded) in [
+            (&b"aGVsbG8=\n"[..], &b"hello\n"[..]),
+            (b"aGVsbG8\n", b"hello\n"),
+            (b"aGVsbG8h\r\n", b"hello!\r\n"),
+            (b"  aGk=  ", b"hi"),
+            (b"+/8=", b"\xfb\xff"),
+            (b"-_8", b"\xfb\xff"),
+            (b"", b""),
+        ] {
+            assert_eq!(
+                decode(line, Encoding::Base64).as_deref(),
+                Some(decoded),
+                "{line:?}"
+            );
+        }
+        for line in [&b"aGVsbG8==\n"[..], b"a", b"aGk=aGk=", b"not base64!"] {
+            assert_eq!(decode(line, Encoding::Base64), None, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(
+            decode(b"68692E\n", Encoding::Hex).as_deref(),
+            Some(&b"hi.\n"[..])
+        );
+        assert_eq!(
+            decode(b"00ff", Encoding::Hex).as_deref(),
+            Some(&b"\x00\xff"[..])
+        );
+        assert_eq!(decode(b"686", Encoding::Hex), None);
+        assert_eq!(decode(b"6g", Encoding::Hex), None);
+    }
+
+    #[test]
+    fn url() {
+        assert_eq!(
+            decode(b"a%20b+c%2Fd%c3%a9\n", Encoding::Url).as_deref(),
+            Some("a b+c/dé\n".as_bytes())
+        );
+        assert_eq!(decode(b"100%", Encoding::Url), None);
+        assert_eq!(decode(b"%zz", Encoding::Url), None);
+    }
+
+    #[test]
+    fn nul_terminated() {
+        assert_eq!(
+            super::decode(b"aGk=\0", Encoding::Base64, b'\0').as_deref(),
+            Some(&b"hi\0"[..])
+        );
+    }
+}
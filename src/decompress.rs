@@ -0,0 +1,285 @@
+use crate::cli::Charset;
+use crate::{paths, transcode};
+use anyhow::Context;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// The compression formats that are recognized by their magic bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// The number of bytes looked at to recognize a format, i.e.: the longest magic bytes.
+    const MAGIC_LEN: usize = 10;
+
+    fn detect(first_bytes: &[u8]) -> Option<Self> {
+        match first_bytes {
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            // the block size, from 1 to 9 hundred kB, and the magic bytes of the first block
+            [
+                b'B',
+                b'Z',
+                b'h',
+                b'1'..=b'9',
+                0x31,
+                0x41,
+                0x59,
+                0x26,
+                0x53,
+                0x59,
+                ..,
+            ] => Some(Self::Bzip2),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+            Self::Bzip2 => "bzip2",
+        }
+    }
+}
+
+/// The first lines of a file, when the ones past them are never read.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Prefix {
+    pub(crate) n_lines: usize,
+    pub(crate) separator: u8,
+}
+
+/// Returns the decompressed content of `file` if it's compressed, or `None` otherwise, in which
+/// case `file` is rewound.
+///
+/// Compressed streams can't be read backward, nor read twice without decompressing them again,
+/// so the content is decompressed once into an anonymous temporary file, which is read like any
+/// other file and removed once closed. This takes as much disk space in the temporary directory
+/// as the decompressed content, so given a `prefix`, only its lines are decompressed (unless the
+/// content looks like UTF-16, whose lines can only be told apart once transcoded).
+pub(crate) fn decompress(
+    file: &mut File,
+    path: &Path,
+    prefix: Option<Prefix>,
+) -> anyhow::Result<Option<File>> {
+    let mut first_bytes = [0; Compression::MAGIC_LEN];
+    let n = (&*file)
+        .take(Compression::MAGIC_LEN as u64)
+        .read(&mut first_bytes)
+        .and_then(|n| file.seek(SeekFrom::Start(0)).map(|_| n))
+        .context("Failed to read from file")?;
+    let Some(compression) = Compression::detect(&first_bytes[..n]) else {
+        return Ok(None);
+    };
+    let reader = BufReader::new(&*file);
+    let mut decompressed = tempfile::tempfile().context("Couldn't create a temporary file")?;
+    let mut writer = BufWriter::new(&mut decompressed);
+    match compression {
+        Compression::Gzip => copy(
+            flate2::read::MultiGzDecoder::new(reader),
+            &mut writer,
+            prefix,
+        ),
+        Compression::Zstd => zstd::stream::read::Decoder::with_buffer(reader)
+            .and_then(|decoder| copy(decoder, &mut writer, prefix)),
+        Compression::Bzip2 => copy(
+            bzip2::read::MultiBzDecoder::new(reader),
+            &mut writer,
+            prefix,
+        ),
+    }
+    .and_then(|()| writer.flush())
+    .with_context(|| {
+        format!(
+            "Failed to decompress {} file `{}`",
+            compression.name(),
+            paths::display(path)
+        )
+    })?;
+    drop(writer);
+    decompressed
+        .rewind()
+        .context("Failed to rewind the decompressed file")?;
+    Ok(Some(decompressed))
+}
+
+/// Copies the content decompressed by `decoder` to `writer`, up to the end of `prefix` if given.
+///
+/// The bytes looked at to recognize the encoding and content type are always copied.
+fn copy(mut decoder: impl Read, writer: &mut impl Write, prefix: Option<Prefix>) -> io::Result<()> {
+    let mut sample = Vec::with_capacity(transcode::SAMPLE_LEN);
+    (&mut decoder)
+        .take(transcode::SAMPLE_LEN as u64)
+        .read_to_end(&mut sample)?;
+    let is_utf16 = matches!(
+        transcode::detect(&sample),
+        Charset::Utf16Le | Charset::Utf16Be
+    );
+    writer.write_all(&sample)?;
+    match prefix {
+        Some(prefix) if !is_utf16 => {
+            let n_sampled_lines = memchr::memchr_iter(prefix.separator, &sample).count();
+            copy_lines(
+                &mut BufReader::new(decoder),
+                writer,
+                prefix.n_lines.saturating_sub(n_sampled_lines),
+                prefix.separator,
+            )
+        }
+        _ => io::copy(&mut decoder, writer).map(|_| ()),
+    }
+}
+
+/// Copies the first `n_lines` lines of `reader` to `writer`.
+fn copy_lines(
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+    n_lines: usize,
+    separator: u8,
+) -> io::Result<()> {
+    let mut n_lines_left = n_lines;
+    while n_lines_left > 0 {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        let mut separators = memchr::memchr_iter(separator, buf);
+        let len = match separators.nth(n_lines_left - 1) {
+            Some(i) => {
+                n_lines_left = 0;
+                i + 1
+            }
+            None => {
+                n_lines_left -= memchr::memchr_iter(separator, buf).count();
+                buf.len()
+            }
+        };
+        writer.write_all(&buf[..len])?;
+        reader.consume(len);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    fn file_with_content(content: &[u8]) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(content).unwrap();
+        file
+    }
+
+    #[test]
+    fn detects_compression() {
+        assert_eq!(
+            Compression::detect(b"\x1f\x8b\x08\x00"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::detect(b"\x28\xb5\x2f\xfd"),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(
+            Compression::detect(b"BZh9\x31\x41\x59\x26\x53\x59"),
+            Some(Compression::Bzip2)
+        );
+        assert_eq!(Compression::detect(b"BZh9"), None);
+        assert_eq!(Compression::detect(b"BZh0\x31\x41\x59\x26\x53\x59"), None);
+        assert_eq!(Compression::detect(b"BZhello world"), None);
+        assert_eq!(Compression::detect(b"\x1f"), None);
+        assert_eq!(Compression::detect(b"text"), None);
+    }
+
+    fn gzip(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decompressed_content(compressed: &[u8], prefix: Option<Prefix>) -> Vec<u8> {
+        let file = file_with_content(compressed);
+        let mut decompressed = decompress(&mut file.reopen().unwrap(), file.path(), prefix)
+            .unwrap()
+            .unwrap();
+        let mut content = Vec::new();
+        decompressed.read_to_end(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn decompresses_gzip() {
+        assert_eq!(
+            decompressed_content(&gzip(b"one\ntwo\n"), None),
+            b"one\ntwo\n"
+        );
+    }
+
+    #[test]
+    fn decompresses_zstd() {
+        let compressed = zstd::encode_all(&b"one\ntwo\n"[..], 0).unwrap();
+        assert_eq!(decompressed_content(&compressed, None), b"one\ntwo\n");
+    }
+
+    #[test]
+    fn decompresses_bzip2() {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(b"one\ntwo\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(decompressed_content(&compressed, None), b"one\ntwo\n");
+    }
+
+    #[test]
+    fn decompresses_prefix() {
+        let content: Vec<u8> = (1..=10_000)
+            .flat_map(|i| format!("{i}\n").into_bytes())
+            .collect();
+        let prefix = |n_lines| {
+            Some(Prefix {
+                n_lines,
+                separator: b'\n',
+            })
+        };
+
+        // the sampled bytes are always decompressed
+        let decompressed = decompressed_content(&gzip(&content), prefix(1));
+        assert_eq!(decompressed, content[..transcode::SAMPLE_LEN]);
+
+        let decompressed = decompressed_content(&gzip(&content), prefix(5_000));
+        assert!(decompressed.ends_with(b"\n4999\n5000\n"));
+        assert!(content.starts_with(&decompressed));
+
+        let decompressed = decompressed_content(&gzip(&content), prefix(20_000));
+        assert_eq!(decompressed, content);
+
+        // the lines of UTF-16 content can't be counted before it's transcoded
+        let content: Vec<u8> = "\u{feff}one\n\u{10a}\ntwo\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(decompressed_content(&gzip(&content), prefix(1)), content);
+    }
+
+    #[test]
+    fn leaves_plain_files() {
+        let file = file_with_content(b"one\n");
+        let mut reopened = file.reopen().unwrap();
+        assert!(
+            decompress(&mut reopened, file.path(), None)
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(reopened.stream_position().unwrap(), 0);
+
+        let file = file_with_content(b"\x1f\x8bnot gzip");
+        assert!(decompress(&mut file.reopen().unwrap(), file.path(), None).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::escape::split_terminator;
+use std::borrow::Cow;
+
+/// Elides the middle of `line` if it's longer than `max_chars` characters, keeping its first and
+/// last `max_chars / 2` characters around a marker like `…[12,345 bytes]…`. The line ending (a
+/// trailing `terminator`, and a `\r` before a trailing `\n`) is kept as is.
+///
+/// Each byte of invalid UTF-8 counts as one character.
+pub(crate) fn elide_middle(line: &[u8], max_chars: usize, terminator: u8) -> Cow<'_, [u8]> {
+    let (content, line_ending) = split_terminator(line, terminator);
+    let n_chars = char_starts(content).count();
+    if n_chars <= max_chars {
+        return Cow::Borrowed(line);
+    }
+
+    let kept_chars = max_chars / 2;
+    let head_end = char_starts(content)
+        .nth(kept_chars)
+        .unwrap_or(content.len());
+    let tail_start = char_starts(content)
+        .nth(n_chars - kept_chars)
+        .unwrap_or(content.len());
+
+    let marker = format!("…[{} bytes]…", group_thousands(tail_start - head_end));
+    let mut elided = Vec::with_capacity(line.len() - (tail_start - head_end) + marker.len());
+    elided.extend_from_slice(&content[..head_end]);
+    elided.extend_from_slice(marker.as_bytes());
+    elided.extend_from_slice(&content[tail_start..]);
+    elided.extend_from_slice(line_ending);
+    Cow::Owned(elided)
+}
+
+/// Truncates `line` if it's longer than `max_bytes` bytes, keeping its first `max_bytes` bytes
+/// or fewer, so that no character is cut in half, followed by a marker like `…[12,345 more
+/// bytes]`. The line ending is kept as is.
+pub(crate) fn truncate_end(line: &[u8], max_bytes: usize, terminator: u8) -> Cow<'_, [u8]> {
+    let (content, line_ending) = split_terminator(line, terminator);
+    if content.len() <= max_bytes {
+        return Cow::Borrowed(line);
+    }
+
+    let end = char_starts(content)
+        .take_while(|&start| start <= max_bytes)
+        .last()
+        .unwrap_or(0);
+    let marker = format!("…[{} more bytes]", group_thousands(content.len() - end));
+    let mut truncated = Vec::with_capacity(end + marker.len() + line_ending.len());
+    truncated.extend_from_slice(&content[..end]);
+    truncated.extend_from_slice(marker.as_bytes());
+    truncated.extend_from_slice(line_ending);
+    Cow::Owned(truncated)
+}
+
+/// Returns the offsets of the characters of `content`, counting each byte of invalid UTF-8 as a
+/// character.
+fn char_starts(content: &[u8]) -> impl Iterator<Item = usize> {
+    let mut offset = 0;
+    content.utf8_chunks().flat_map(move |chunk| {
+        let chunk_start = offset;
+        offset += chunk.valid().len() + chunk.invalid().len();
+        let invalid_start = chunk_start + chunk.valid().len();
+        let valid = chunk
+            .valid()
+            .char_indices()
+            .map(move |(i, _)| chunk_start + i);
+        let invalid = (0..chunk.invalid().len()).map(move |i| invalid_start + i);
+        valid.chain(invalid)
+    })
+}
+
+/// Formats `n` with commas between groups of thousands, e.g.: `12,345`.
+fn group_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i != 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_lines_are_borrowed() {
+        for line in [&b"0123456789\n"[..], b"short\r\n", b""] {
+            assert!(
+                matches!(elide_middle(line, 10, b'\n'), Cow::Borrowed(_)),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_both_ends() {
+        assert_eq!(
+            elide_middle(
+                b"2024-01-01 ... lots of noise ... status=500\r\n",
+                20,
+                b'\n'
+            ),
+            "2024-01-01…[23 bytes]…status=500\r\n".as_bytes()
+        );
+        assert_eq!(
+            elide_middle(b"abcdefg", 5, b'\n'),
+            "ab…[3 bytes]…fg".as_bytes()
+        );
+    }
+
+    #[test]
+    fn counts_characters() {
+        // the elided count is in bytes, while the kept ends are in characters
+        assert_eq!(
+            elide_middle("éééééééé\n".as_bytes(), 4, b'\n'),
+            "éé…[8 bytes]…éé\n".as_bytes()
+        );
+        assert_eq!(
+            elide_middle(b"\xff\xfeabcd\xfd\xfc\0", 4, b'\0'),
+            &b"\xff\xfe\xe2\x80\xa6[4 bytes]\xe2\x80\xa6\xfd\xfc\0"[..]
+        );
+    }
+
+    #[test]
+    fn truncates_at_char_boundaries() {
+        assert!(matches!(
+            truncate_end(b"0123456789\n", 10, b'\n'),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(
+            truncate_end(b"{\"key\": \"value\"}\r\n", 6, b'\n'),
+            "{\"key\"…[10 more bytes]\r\n".as_bytes()
+        );
+        // `é` takes up two bytes, so cutting after 3 bytes would split the second one
+        assert_eq!(
+            truncate_end("ééé\n".as_bytes(), 3, b'\n'),
+            "é…[4 more bytes]\n".as_bytes()
+        );
+        assert_eq!(
+            truncate_end(&[b'x'; 12345], 0, b'\n'),
+            "…[12,345 more bytes]".as_bytes()
+        );
+    }
+
+    #[test]
+    fn groups_thousands() {
+        for (n, grouped) in [
+            (0, "0"),
+            (999, "999"),
+            (1000, "1,000"),
+            (12345, "12,345"),
+            (1234567, "1,234,567"),
+        ] {
+            assert_eq!(group_thousands(n), grouped);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use crate::escape::split_terminator;
+
+/// Marks a line ending with `\n`.
+const LF: &str = "␊";
+/// Marks a line ending with `\r\n`.
+const CRLF: &str = "␍␊";
+/// Marks a record ending with `\0`, with `--null-data`.
+const NUL: &str = "␀";
+/// Marks the last line of a file that doesn't end with a line ending.
+const NONE: &str = "∅";
+
+/// Returns `line` with a marker of its line ending (e.g.: `␍␊` for `\r\n`) inserted before it.
+pub(crate) fn show_eol(line: &[u8], terminator: u8) -> Vec<u8> {
+    let (content, line_ending) = split_terminator(line, terminator);
+    let marker = match line_ending {
+        b"" => NONE,
+        b"\r\n" => CRLF,
+        b"\0" => NUL,
+        _ => LF,
+    };
+    let mut marked = Vec::with_capacity(line.len() + marker.len());
+    marked.extend_from_slice(content);
+    marked.extend_from_slice(marker.as_bytes());
+    marked.extend_from_slice(line_ending);
+    marked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_line_endings() {
+        for (line, marked) in [
+            (&b"unix\n"[..], "unix␊\n"),
+            (b"windows\r\n", "windows␍␊\r\n"),
+            (b"old mac\r", "old mac\r∅"),
+            (b"last", "last∅"),
+            (b"\n", "␊\n"),
+        ] {
+            assert_eq!(show_eol(line, b'\n'), marked.as_bytes(), "{line:?}");
+        }
+        assert_eq!(show_eol(b"a\r\n\0", b'\0'), "a\r\n␀\0".as_bytes());
+    }
+}
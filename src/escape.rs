@@ -0,0 +1,100 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Hex-escapes the control characters and invalid UTF-8 of `line` (e.g.: `\x1b` for an escape
+/// character), leaving the rest of `line` untouched. Tabs and the line ending (a trailing
+/// `terminator`, and a `\r` before a trailing `\n`) are kept as is.
+pub(crate) fn escape_control(line: &[u8], terminator: u8) -> Cow<'_, [u8]> {
+    let (content, line_ending) = split_terminator(line, terminator);
+    if !needs_escaping(content) {
+        return Cow::Borrowed(line);
+    }
+
+    let mut escaped = String::with_capacity(line.len() + 16);
+    for chunk in content.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c.is_control() && c != '\t' {
+                let mut bytes = [0; 4];
+                for byte in c.encode_utf8(&mut bytes).bytes() {
+                    push_hex(&mut escaped, byte);
+                }
+            } else {
+                escaped.push(c);
+            }
+        }
+        for &byte in chunk.invalid() {
+            push_hex(&mut escaped, byte);
+        }
+    }
+
+    let mut escaped = escaped.into_bytes();
+    escaped.extend_from_slice(line_ending);
+    Cow::Owned(escaped)
+}
+
+/// Splits `line` into its content and its line ending.
+pub(crate) fn split_terminator(line: &[u8], terminator: u8) -> (&[u8], &[u8]) {
+    let content_len = match line {
+        [.., b'\r', b'\n'] if terminator == b'\n' => line.len() - 2,
+        [.., last] if *last == terminator => line.len() - 1,
+        _ => line.len(),
+    };
+    line.split_at(content_len)
+}
+
+/// Returns true if `content` has a control character other than a tab, or invalid UTF-8.
+fn needs_escaping(content: &[u8]) -> bool {
+    content.utf8_chunks().any(|chunk| {
+        !chunk.invalid().is_empty() || chunk.valid().chars().any(|c| c.is_control() && c != '\t')
+    })
+}
+
+pub(crate) fn push_hex(escaped: &mut String, byte: u8) {
+    write!(escaped, "\\x{byte:02x}").expect("writing to a String can't fail");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escape(line: &[u8]) -> Cow<'_, [u8]> {
+        escape_control(line, b'\n')
+    }
+
+    #[test]
+    fn readable_lines_are_borrowed() {
+        for line in [
+            &b"plain\n"[..],
+            b"tab\tseparated\r\n",
+            "café ✓".as_bytes(),
+            b"",
+        ] {
+            assert!(matches!(escape(line), Cow::Borrowed(_)), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn control_characters() {
+        assert_eq!(
+            escape(b"\x1b[31mred\x1b[0m\n"),
+            &b"\\x1b[31mred\\x1b[0m\n"[..]
+        );
+        assert_eq!(escape(b"a\rb\x00c\x7f\n"), &b"a\\x0db\\x00c\\x7f\n"[..]);
+        // C1 control characters are escaped byte by byte
+        assert_eq!(escape("a\u{85}b".as_bytes()), &b"a\\xc2\\x85b"[..]);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        assert_eq!(escape(b"caf\xe9 ok\n"), &b"caf\\xe9 ok\n"[..]);
+        assert_eq!(escape(b"\xc3\xa9\xff!"), "é\\xff!".as_bytes());
+    }
+
+    #[test]
+    fn line_endings_are_kept() {
+        assert_eq!(escape(b"\x07\r\n"), &b"\\x07\r\n"[..]);
+        assert_eq!(escape(b"\x07\n\n"), &b"\\x07\\x0a\n"[..]);
+        assert_eq!(escape_control(b"a\nb\x00", b'\0'), &b"a\\x0ab\x00"[..]);
+        assert_eq!(escape_control(b"a\r\n", b'\0'), &b"a\\x0d\\x0a"[..]);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::line_counter::count_lines_up_to;
+use crate::random::Rng;
+use crate::{
+    LineReader, LineSource, RawLineSelector, get_context_lines_endpoints, parse_line_selectors,
+    read_lines,
+};
+use std::io::{BufRead, Seek};
+
+/// Options of [`extract_lines`]. The defaults extract lines ending with `\n`, without context
+/// lines.
+#[derive(Clone, Debug)]
+pub struct ExtractOptions {
+    before: usize,
+    after: usize,
+    separator: u8,
+    seed: Option<u64>,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            before: 0,
+            after: 0,
+            separator: b'\n',
+            seed: None,
+        }
+    }
+}
+
+impl ExtractOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Extracts `before` lines before and `after` lines after each selected line, like `-B` and
+    /// `-A`.
+    pub fn with_context(mut self, before: usize, after: usize) -> Self {
+        self.before = before;
+        self.after = after;
+        self
+    }
+
+    /// Sets the byte that ends lines, which is `\n` by default (e.g.: `\0` like `-z`).
+    pub fn with_separator(mut self, separator: u8) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// Seeds the random picks of random selectors (e.g.: `rand(1:100, 5)`), so that they select
+    /// the same lines every time, like `--seed`.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// A line extracted by [`extract_lines`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtractedLine {
+    /// The (one-based) number of the line
+    pub line_num: usize,
+    /// The content of the line, with its line ending if it has one
+    pub content: Vec<u8>,
+    /// Whether the line is only extracted as context of a selected line
+    pub is_context: bool,
+}
+
+/// Extracts the lines selected by `selectors` from `reader`, like `line -n`. Each selected line is
+/// preceded and followed by its context lines if [`ExtractOptions::with_context`] is set, so a
+/// line may be extracted more than once.
+///
+/// Lines are returned in the order of the selectors, and in the order each selector selects them
+/// (e.g.: `5:3:-1` extracts lines 5, 4, then 3). The input is read twice: once to count its lines,
+/// which resolves negative and unbounded selectors, then once to read the selected lines, which
+/// are the only ones kept in memory.
+///
+/// # Errors:
+///
+/// This function returns an error if the input is empty, if a selector is out of range, or if
+/// reading the input fails.
+pub fn extract_lines<R: BufRead + Seek>(
+    mut reader: R,
+    selectors: &[RawLineSelector],
+    options: &ExtractOptions,
+) -> anyhow::Result<Vec<ExtractedLine>> {
+    let n_lines = count_lines_up_to(&mut reader, usize::MAX, options.separator)?;
+    anyhow::ensure!(n_lines > 0, "The input is empty");
+    let mut rng = options.seed.map_or_else(Rng::from_entropy, Rng::new);
+    let line_selectors = parse_line_selectors(selectors, n_lines, &mut rng)?;
+
+    let line_reader = LineReader::new(reader).with_separator(options.separator);
+    let (before, after) = (options.before, options.after);
+    let mut lines = read_lines(line_reader, &line_selectors, before, after, n_lines)?;
+    let mut extracted_lines = Vec::new();
+    for line_selector in &line_selectors {
+        for selected_line_num in line_selector.iter_in_selection_order() {
+            let (first_line_num, last_line_num) =
+                get_context_lines_endpoints(selected_line_num, before, after, n_lines);
+            for line_num in first_line_num..=last_line_num {
+                extracted_lines.push(ExtractedLine {
+                    line_num: line_num + 1,
+                    content: lines.get(line_num)?.to_vec(),
+                    is_context: line_num != selected_line_num,
+                });
+            }
+        }
+    }
+    Ok(extracted_lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn extract(selectors: &str, options: &ExtractOptions) -> anyhow::Result<Vec<(usize, String)>> {
+        let input = Cursor::new("one\ntwo\nthree\nfour\nfive");
+        let selectors = RawLineSelector::list_from_str(selectors)?;
+        Ok(extract_lines(input, &selectors.0, options)?
+            .into_iter()
+            .map(|line| (line.line_num, String::from_utf8(line.content).unwrap()))
+            .collect())
+    }
+
+    #[test]
+    fn selected_lines_in_selection_order() {
+        let lines = extract("-1,3:2:-1", &ExtractOptions::new()).unwrap();
+        assert_eq!(
+            lines,
+            [
+                (5, "five".to_owned()),
+                (3, "three\n".to_owned()),
+                (2, "two\n".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn context_lines() {
+        let options = ExtractOptions::new().with_context(1, 2);
+        let line_nums: Vec<_> = extract("1,4", &options)
+            .unwrap()
+            .into_iter()
+            .map(|(line_num, _)| line_num)
+            .collect();
+        assert_eq!(line_nums, [1, 2, 3, 3, 4, 5]);
+    }
+
+    #[test]
+    fn seeded_random_lines() {
+        let options = ExtractOptions::new().with_seed(7);
+        assert_eq!(
+            extract("rand(:, 3)", &options).unwrap(),
+            extract("rand(:, 3)", &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(extract("6", &ExtractOptions::new()).is_err());
+        let empty = extract_lines(
+            Cursor::new(""),
+            &[RawLineSelector::Single(1)],
+            &ExtractOptions::new(),
+        );
+        assert!(empty.is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::LineSource;
+use anyhow::Context;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Returns the number of lines of a file of `len` bytes whose lines are all `line_length` bytes
+/// long (line ending included). The last line may lack its line ending.
+///
+/// # Errors:
+///
+/// This method returns an error if `len` doesn't fit lines of `line_length` bytes.
+pub(crate) fn count_lines(len: u64, line_length: usize) -> anyhow::Result<usize> {
+    let line_length = line_length as u64;
+    let n_lines = len.div_ceil(line_length);
+    let remainder = len % line_length;
+    // a missing line ending at the end of the file makes the last line a byte shorter
+    if remainder != 0 && remainder != line_length - 1 {
+        anyhow::bail!(
+            "The file size ({len} bytes) doesn't match lines of {line_length} bytes (check the \
+            value of --assume-line-length, which includes the line ending)"
+        );
+    }
+    usize::try_from(n_lines).context("The file has too many lines")
+}
+
+/// Lines read by seeking straight to their offset, for files whose lines all have the same
+/// length. Lines can be read in any order without reading the rest of the file.
+pub(crate) struct FixedWidthLines<R> {
+    reader: R,
+    line_length: usize,
+    buf: Vec<u8>,
+}
+
+impl<R: Read + Seek> FixedWidthLines<R> {
+    pub(crate) fn new(reader: R, line_length: usize) -> Self {
+        Self {
+            reader,
+            line_length,
+            buf: Vec::with_capacity(line_length),
+        }
+    }
+}
+
+impl<R: Read + Seek> LineSource for FixedWidthLines<R> {
+    fn get(&mut self, line_num: usize) -> anyhow::Result<&[u8]> {
+        let offset = line_num as u64 * self.line_length as u64;
+        self.buf.clear();
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| {
+                (&mut self.reader)
+                    .take(self.line_length as u64)
+                    .read_to_end(&mut self.buf)
+            })
+            .with_context(|| format!("Failed to read line number {}", line_num + 1))?;
+
+        // lines of the wrong length would silently shift every following line, so the line
+        // ending is checked as a cheap sanity check
+        let is_last_line = self.buf.len() < self.line_length;
+        if !is_last_line && self.buf.last() != Some(&b'\n') {
+            anyhow::bail!(
+                "Line {} isn't {} bytes long (check the value of --assume-line-length)",
+                line_num + 1,
+                self.line_length
+            );
+        }
+        Ok(&self.buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn count() {
+        assert_eq!(count_lines(12, 4).unwrap(), 3);
+        assert_eq!(count_lines(11, 4).unwrap(), 3);
+        assert_eq!(count_lines(1, 1).unwrap(), 1);
+        assert!(count_lines(10, 4).is_err());
+    }
+
+    #[test]
+    fn get_lines_in_any_order() {
+        let mut lines = FixedWidthLines::new(Cursor::new("aaa\nbbb\nccc"), 4);
+        assert_eq!(lines.get(2).unwrap(), b"ccc");
+        assert_eq!(lines.get(0).unwrap(), b"aaa\n");
+        assert_eq!(lines.get(1).unwrap(), b"bbb\n");
+    }
+
+    #[test]
+    fn wrong_line_length() {
+        let mut lines = FixedWidthLines::new(Cursor::new("aaa\nbbbb\nccc\n"), 4);
+        assert_eq!(lines.get(0).unwrap(), b"aaa\n");
+        assert!(lines.get(1).is_err());
+    }
+}
@@ -0,0 +1,250 @@
+use crate::cli::Cli;
+use crate::output::{self, Line, OutputWriter};
+use crate::{BufferedLines, LineRewriter, pager, paths, split};
+use anyhow::Context;
+use std::collections::HashMap;
+use std::fs::{self, File, Metadata};
+use std::io::{BufRead, BufReader, BufWriter, IsTerminal, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// How often a followed file is checked for appended lines.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Size of the blocks read backward to find the start of a partial last line.
+const BACKWARD_BLOCK_LEN: u64 = 8 * 1024;
+
+/// The end of a file when its lines were counted, where following it starts.
+pub(crate) struct FileEnd {
+    pub(crate) len: u64,
+    pub(crate) n_lines: usize,
+}
+
+/// Prints the lines appended to the file at `path` after `file_end`, as they arrive, for
+/// `--follow`. This only returns on errors, or once the output is closed (e.g.: `line -f | head`).
+///
+/// Lines are only printed once their line ending arrives. If the file is truncated it's followed
+/// from its start again, and if it's replaced (e.g.: by log rotation) the rest of the old file is
+/// printed before following the new one from its start.
+pub(crate) fn follow(args: &Cli, path: &Path, file_end: FileEnd) -> anyhow::Result<()> {
+    let stdout = std::io::stdout().lock();
+    let is_terminal = stdout.is_terminal() && !args.canonical;
+    let stdout = BufWriter::with_capacity(args.write_buffer, stdout);
+    // `--grid` isn't used when following, so the largest line number printed doesn't matter
+    let output = output::get_output_writer(
+        split::Destination::Stdout(stdout),
+        args,
+        path,
+        is_terminal,
+        0,
+        // `{offset}` can't be used with `--follow`
+        HashMap::new(),
+    )?;
+    // every line is flushed, since it may be a while until the next one arrives
+    let mut output = output::LineBuffered(output::OffsetLineNumbers(output, args.offset_base));
+    let rewriter = LineRewriter::new(
+        args,
+        &[],
+        0,
+        &mut BufferedLines(HashMap::new()),
+        is_terminal,
+    )?;
+
+    let mut followed = FollowedFile::open(path, args.separator())?;
+    followed.start_at(file_end)?;
+    let result = loop {
+        if let Err(err) = followed.print_appended(&rewriter, &mut output) {
+            break Err(err);
+        }
+        thread::sleep(POLL_INTERVAL);
+        // the file may be missing for a moment while it's rotated
+        let Ok(metadata) = fs::metadata(paths::for_opening(path)) else {
+            continue;
+        };
+        if identity(&metadata) != followed.identity {
+            if let Err(err) = followed.print_appended(&rewriter, &mut output) {
+                break Err(err);
+            }
+            eprintln!(
+                "Warning: `{}` was replaced, following the new file",
+                paths::display(path)
+            );
+            followed = FollowedFile::open(path, args.separator())?;
+        } else if metadata.len() < followed.offset {
+            eprintln!(
+                "Warning: `{}` was truncated, following it from its start",
+                paths::display(path)
+            );
+            followed.start_at(FileEnd { len: 0, n_lines: 0 })?;
+        }
+    };
+    match result {
+        Err(err) if pager::is_broken_pipe(&err) => Ok(()),
+        result => result,
+    }
+}
+
+/// Returns what identifies a file on its filesystem, which changes when a file is replaced by
+/// another with the same path.
+#[cfg(unix)]
+pub(crate) fn identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Replaced files can't be told apart from their metadata on this platform.
+#[cfg(not(unix))]
+pub(crate) fn identity(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+struct FollowedFile {
+    reader: BufReader<File>,
+    identity: Option<(u64, u64)>,
+    separator: u8,
+    /// The offset of the first byte that wasn't read yet
+    offset: u64,
+    /// The (zero-based) number of the line being read
+    line_num: usize,
+    /// The start of the line being read, until its line ending arrives
+    partial_line: Vec<u8>,
+}
+
+impl FollowedFile {
+    fn open(path: &Path, separator: u8) -> anyhow::Result<Self> {
+        // unlike the file given at first, a new file replacing it may well be empty
+        let file = File::open(paths::for_opening(path))
+            .with_context(|| format!("Couldn't open file `{}`", paths::display(path)))?;
+        let metadata = file.metadata().with_context(|| {
+            format!("Couldn't read file metadata of `{}`", paths::display(path))
+        })?;
+        Ok(Self {
+            reader: BufReader::new(file),
+            identity: identity(&metadata),
+            separator,
+            offset: 0,
+            line_num: 0,
+            partial_line: Vec::new(),
+        })
+    }
+
+    /// Follows the file from `file_end`. A last line that lacked its line ending then is read
+    /// again, so that it's printed whole once its line ending arrives.
+    fn start_at(&mut self, file_end: FileEnd) -> anyhow::Result<()> {
+        self.offset = self.start_of_line_at(file_end.len)?;
+        self.line_num = file_end.n_lines - usize::from(self.offset < file_end.len);
+        self.partial_line.clear();
+        self.reader
+            .seek(SeekFrom::Start(self.offset))
+            .context("Failed to seek in file")?;
+        Ok(())
+    }
+
+    /// Returns the offset of the start of the line that ends at `end` (excluded), or `end` if the
+    /// byte before it is a line ending.
+    fn start_of_line_at(&mut self, end: u64) -> anyhow::Result<u64> {
+        let mut block_end = end;
+        let mut block = Vec::new();
+        while block_end > 0 {
+            let block_start = block_end.saturating_sub(BACKWARD_BLOCK_LEN);
+            block.clear();
+            self.reader
+                .seek(SeekFrom::Start(block_start))
+                .and_then(|_| {
+                    (&mut self.reader)
+                        .take(block_end - block_start)
+                        .read_to_end(&mut block)
+                })
+                .context("Failed to read from file")?;
+            let is_last_block = block_end == end;
+            let searched = if is_last_block {
+                // the line ending of the previous line is searched for, not the one at `end`
+                match block.split_last() {
+                    Some((&last, _)) if last == self.separator => return Ok(end),
+                    Some((_, rest)) => rest,
+                    None => return Ok(end),
+                }
+            } else {
+                &block
+            };
+            if let Some(i) = memchr::memrchr(self.separator, searched) {
+                return Ok(block_start + i as u64 + 1);
+            }
+            block_end = block_start;
+        }
+        Ok(0)
+    }
+
+    /// Prints the lines that were appended since the last call, once their line ending arrived.
+    fn print_appended(
+        &mut self,
+        rewriter: &LineRewriter,
+        output: &mut impl OutputWriter,
+    ) -> anyhow::Result<()> {
+        loop {
+            let n = self
+                .reader
+                .read_until(self.separator, &mut self.partial_line)
+                .context("Failed to read from file")?;
+            if n == 0 {
+                return Ok(());
+            }
+            self.offset += n as u64;
+            if self.partial_line.last() != Some(&self.separator) {
+                continue;
+            }
+            let line = rewriter.rewrite(&self.partial_line);
+            let line = Line::Selected {
+                line_num: self.line_num,
+                line: &line,
+            };
+            output
+                .print_line(line)
+                .with_context(|| format!("Failed to output line {}", self.line_num + 1))?;
+            self.line_num += 1;
+            self.partial_line.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn followed_file(content: &[u8]) -> FollowedFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(content).unwrap();
+        FollowedFile::open(file.path(), b'\n').unwrap()
+    }
+
+    #[test]
+    fn start_of_partial_last_line() {
+        let mut followed = followed_file(b"one\ntwo\nthr");
+        assert_eq!(followed.start_of_line_at(11).unwrap(), 8);
+        assert_eq!(followed.start_of_line_at(8).unwrap(), 8);
+        assert_eq!(followed.start_of_line_at(3).unwrap(), 0);
+        assert_eq!(followed.start_of_line_at(0).unwrap(), 0);
+
+        let long_line = vec![b'x'; 3 * BACKWARD_BLOCK_LEN as usize];
+        let mut followed = followed_file(&[&b"one\n"[..], &long_line].concat());
+        let len = 4 + long_line.len() as u64;
+        assert_eq!(followed.start_of_line_at(len).unwrap(), 4);
+    }
+
+    #[test]
+    fn restarts_partial_last_line() {
+        let mut followed = followed_file(b"one\ntwo\nthr");
+        followed
+            .start_at(FileEnd {
+                len: 11,
+                n_lines: 3,
+            })
+            .unwrap();
+        assert_eq!((followed.offset, followed.line_num), (8, 2));
+
+        followed.start_at(FileEnd { len: 8, n_lines: 2 }).unwrap();
+        assert_eq!((followed.offset, followed.line_num), (8, 2));
+    }
+}
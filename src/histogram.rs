@@ -0,0 +1,133 @@
+use crate::size::format_size;
+use std::io::Write;
+
+/// The width of the longest bar, in characters.
+const BAR_WIDTH: usize = 40;
+
+/// A histogram of line lengths, in buckets whose bounds are powers of two: `0 B`, `1 B – 2 B`,
+/// `2 B – 4 B`, `4 B – 8 B`, etc.
+///
+/// Doubling the bucket sizes keeps a few enormous lines from squashing all the others into the
+/// first bucket, which is what makes them stand out.
+#[derive(Default)]
+pub(crate) struct Histogram {
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    pub(crate) fn add(&mut self, len: usize) {
+        let bucket = bucket_of(len);
+        if self.counts.len() <= bucket {
+            self.counts.resize(bucket + 1, 0);
+        }
+        self.counts[bucket] += 1;
+    }
+
+    /// Prints a row per bucket, from the first to the last non-empty one, with a bar
+    /// proportional to its count.
+    pub(crate) fn print(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let Some(first_bucket) = self.counts.iter().position(|&count| count != 0) else {
+            return Ok(());
+        };
+        let max_count = self.counts.iter().copied().max().unwrap_or_default();
+        let rows: Vec<_> = self.counts[first_bucket..]
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| (bucket_label(first_bucket + i), count))
+            .collect();
+
+        let label_width = rows.iter().map(|(label, _)| label.chars().count()).max();
+        let label_width = label_width.unwrap_or_default().max("Length".len());
+        let count_width = max_count.to_string().len().max("Lines".len());
+        writeln!(
+            writer,
+            "{:<label_width$}  {:>count_width$}",
+            "Length", "Lines"
+        )?;
+        for (label, count) in rows {
+            // non-empty buckets get a bar, however small they are
+            let bar_len = (count * BAR_WIDTH as u64).div_ceil(max_count) as usize;
+            writeln!(
+                writer,
+                "{label:<label_width$}  {count:>count_width$}  {}",
+                "█".repeat(bar_len)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn bucket_of(len: usize) -> usize {
+    match len {
+        0 => 0,
+        _ => len.ilog2() as usize + 1,
+    }
+}
+
+fn bucket_label(bucket: usize) -> String {
+    match bucket {
+        0 => String::from("0 B"),
+        _ => format!(
+            "{} – {}",
+            format_size(1 << (bucket - 1)),
+            format_size(1 << bucket)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_double_in_size() {
+        for (len, bucket) in [
+            (0, 0),
+            (1, 1),
+            (2, 2),
+            (3, 2),
+            (4, 3),
+            (1023, 10),
+            (1024, 11),
+        ] {
+            assert_eq!(bucket_of(len), bucket, "{len}");
+        }
+        assert_eq!(bucket_label(0), "0 B");
+        assert_eq!(bucket_label(3), "4 B – 8 B");
+        assert_eq!(bucket_label(11), "1.0 KiB – 2.0 KiB");
+    }
+
+    #[test]
+    fn prints_non_empty_range_of_buckets() {
+        let mut histogram = Histogram::default();
+        for len in [2, 3, 3, 5, 100] {
+            histogram.add(len);
+        }
+
+        let mut output = Vec::new();
+        histogram.print(&mut output).unwrap();
+        let bar = |len| "█".repeat(len);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "Length        Lines\n\
+                 2 B – 4 B         3  {}\n\
+                 4 B – 8 B         1  {}\n\
+                 8 B – 16 B        0  \n\
+                 16 B – 32 B       0  \n\
+                 32 B – 64 B       0  \n\
+                 64 B – 128 B      1  {}\n",
+                bar(40),
+                bar(14),
+                bar(14)
+            )
+        );
+    }
+
+    #[test]
+    fn empty_histogram() {
+        let mut output = Vec::new();
+        Histogram::default().print(&mut output).unwrap();
+        assert!(output.is_empty());
+    }
+}
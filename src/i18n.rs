@@ -0,0 +1,164 @@
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+/// The translations of user-facing messages, in the Fluent format, by language. English is the
+/// fallback for other languages, and for the messages missing from a translation.
+const TRANSLATIONS: &[(&str, &str)] = &[
+    ("en", include_str!("i18n/en.ftl")),
+    ("de", include_str!("i18n/de.ftl")),
+    ("es", include_str!("i18n/es.ftl")),
+    ("fr", include_str!("i18n/fr.ftl")),
+];
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Returns the message `id` in the language of the user (see [`language_from_env`]), with its
+/// variables set to `args`.
+pub(crate) fn message(id: &str, args: &[(&str, FluentValue<'_>)]) -> String {
+    CATALOG
+        .get_or_init(|| Catalog::new(language_from_env().as_deref()))
+        .message(id, args)
+}
+
+/// Prints messages in English regardless of the environment, for `--canonical`. This must be
+/// called before any message is printed to take effect.
+pub(crate) fn use_english() {
+    let _ = CATALOG.set(Catalog::new(None));
+}
+
+/// Returns the language of the first of `LC_ALL`, `LC_MESSAGES`, and `LANG` that is set, like
+/// gettext does, e.g.: `de` for `de_DE.UTF-8`.
+fn language_from_env() -> Option<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(std::env::var_os)
+        .find(|locale| !locale.is_empty())?;
+    let language = locale.to_str()?.split(['_', '.', '@']).next()?;
+    // the C and POSIX locales are English
+    (!matches!(language, "C" | "POSIX")).then(|| language.to_ascii_lowercase())
+}
+
+struct Catalog {
+    /// The translation in the language of the user, if there's one
+    translation: Option<FluentBundle<FluentResource>>,
+    english: FluentBundle<FluentResource>,
+}
+
+impl Catalog {
+    fn new(language: Option<&str>) -> Self {
+        let translation = language
+            .filter(|&language| language != "en")
+            .and_then(|language| TRANSLATIONS.iter().find(|(lang, _)| *lang == language))
+            .map(|&(language, ftl)| bundle(language, ftl));
+        let (language, ftl) = TRANSLATIONS[0];
+        Self {
+            translation,
+            english: bundle(language, ftl),
+        }
+    }
+
+    fn message(&self, id: &str, args: &[(&str, FluentValue<'_>)]) -> String {
+        let args: FluentArgs = args.iter().cloned().collect();
+        let Some((bundle, message)) = [self.translation.as_ref(), Some(&self.english)]
+            .into_iter()
+            .flatten()
+            .find_map(|bundle| Some((bundle, bundle.get_message(id)?)))
+        else {
+            debug_assert!(
+                false,
+                "message `{id}` is missing from the English translation"
+            );
+            return id.to_owned();
+        };
+        let Some(pattern) = message.value() else {
+            return id.to_owned();
+        };
+        let mut errors = Vec::new();
+        let message = bundle.format_pattern(pattern, Some(&args), &mut errors);
+        debug_assert!(
+            errors.is_empty(),
+            "couldn't format message `{id}`: {errors:?}"
+        );
+        message.into_owned()
+    }
+}
+
+/// Builds a bundle of the messages of `ftl`, which are in `language`.
+fn bundle(language: &str, ftl: &'static str) -> FluentBundle<FluentResource> {
+    let language: LanguageIdentifier = language.parse().expect("languages are valid");
+    let mut bundle = FluentBundle::new_concurrent(vec![language]);
+    // isolation marks around variables would end up in the output of non-bidi terminals
+    bundle.set_use_isolating(false);
+    let resource = FluentResource::try_new(ftl.to_owned()).expect("translations are valid");
+    bundle
+        .add_resource(resource)
+        .expect("translations have no duplicate messages");
+    bundle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(catalog: &Catalog, selectors: usize, lines: usize) -> String {
+        catalog.message(
+            "summary",
+            &[
+                ("selectors", selectors.into()),
+                ("lines", lines.into()),
+                ("size", "9.4 KiB".into()),
+                ("path", "file.txt".into()),
+            ],
+        )
+    }
+
+    #[test]
+    fn english() {
+        for language in [None, Some("en"), Some("xx")] {
+            let catalog = Catalog::new(language);
+            assert_eq!(catalog.message("lines-header", &[]), "Lines");
+            assert_eq!(
+                summary(&catalog, 1, 128),
+                "1 selector, 128 lines, 9.4 KiB from file.txt"
+            );
+        }
+    }
+
+    #[test]
+    fn translations() {
+        let catalog = Catalog::new(Some("de"));
+        assert_eq!(catalog.message("line-header", &[]), "Zeile");
+        assert_eq!(
+            summary(&catalog, 3, 1),
+            "3 Selektoren, 1 Zeile, 9.4 KiB aus file.txt"
+        );
+        assert_eq!(
+            Catalog::new(Some("fr")).message("empty-file", &[("path", "a.txt".into())]),
+            "`a.txt` est un fichier vide"
+        );
+    }
+
+    #[test]
+    fn translations_have_every_message() {
+        let ids = [
+            "error",
+            "line-header",
+            "lines-header",
+            "start-of-file",
+            "end-of-file",
+            "summary",
+            "empty-file",
+            "binary-file",
+            "line-out-of-range",
+        ];
+        for &(language, _) in TRANSLATIONS {
+            let catalog = Catalog::new(Some(language));
+            let translation = catalog.translation.as_ref().unwrap_or(&catalog.english);
+            for id in ids {
+                assert!(translation.has_message(id), "{language} misses `{id}`");
+            }
+        }
+    }
+}
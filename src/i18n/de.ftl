@@ -0,0 +1,20 @@
+error = Fehler
+
+line-header = Zeile
+lines-header = Zeilen
+file-header = Datei
+
+start-of-file = --- (Dateianfang) ---
+end-of-file = --- (Dateiende) ---
+
+summary = { $selectors ->
+        [one] { $selectors } Selektor
+       *[other] { $selectors } Selektoren
+    }, { $lines ->
+        [one] { $lines } Zeile
+       *[other] { $lines } Zeilen
+    }, { $size } aus { $path }
+
+empty-file = `{ $path }` ist eine leere Datei
+binary-file = Datei '{ $path }' scheint eine Binärdatei zu sein (mit --allow-binary-files wird sie trotzdem gelesen)
+line-out-of-range = Zeile { $line } liegt außerhalb des Bereichs (die Eingabe hat nur { $count } Zeile(n))
@@ -0,0 +1,25 @@
+# The prefix of errors, e.g.: Error: `file.txt` is an empty file
+error = Error
+
+# The headers printed above the lines of a selector, e.g.: Lines: 3:7, and above the file with
+# --grid, e.g.: File: notes.txt
+line-header = Line
+lines-header = Lines
+file-header = File
+
+# The markers printed where context is cut short, e.g.: --- (start of file) ---
+start-of-file = --- (start of file) ---
+end-of-file = --- (end of file) ---
+
+# The summary printed by --summary, e.g.: 3 selectors, 128 lines, 9.4 KiB from file.txt
+summary = { $selectors ->
+        [one] { $selectors } selector
+       *[other] { $selectors } selectors
+    }, { $lines ->
+        [one] { $lines } line
+       *[other] { $lines } lines
+    }, { $size } from { $path }
+
+empty-file = `{ $path }` is an empty file
+binary-file = file '{ $path }' appears to be a binary file (use --allow-binary-files to override)
+line-out-of-range = Line { $line } is out of range (input has { $count } line(s) only)
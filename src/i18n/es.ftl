@@ -0,0 +1,20 @@
+error = Error
+
+line-header = Línea
+lines-header = Líneas
+file-header = Archivo
+
+start-of-file = --- (inicio del archivo) ---
+end-of-file = --- (fin del archivo) ---
+
+summary = { $selectors ->
+        [one] { $selectors } selector
+       *[other] { $selectors } selectores
+    }, { $lines ->
+        [one] { $lines } línea
+       *[other] { $lines } líneas
+    }, { $size } de { $path }
+
+empty-file = `{ $path }` es un archivo vacío
+binary-file = el archivo '{ $path }' parece ser binario (use --allow-binary-files para leerlo de todos modos)
+line-out-of-range = La línea { $line } está fuera de rango (la entrada solo tiene { $count } línea(s))
@@ -0,0 +1,20 @@
+error = Erreur
+
+line-header = Ligne
+lines-header = Lignes
+file-header = Fichier
+
+start-of-file = --- (début du fichier) ---
+end-of-file = --- (fin du fichier) ---
+
+summary = { $selectors ->
+        [one] { $selectors } sélecteur
+       *[other] { $selectors } sélecteurs
+    }, { $lines ->
+        [one] { $lines } ligne
+       *[other] { $lines } lignes
+    }, { $size } de { $path }
+
+empty-file = `{ $path }` est un fichier vide
+binary-file = le fichier '{ $path }' semble être un fichier binaire (utilisez --allow-binary-files pour le lire quand même)
+line-out-of-range = La ligne { $line } est hors limites (l'entrée n'a que { $count } ligne(s))
@@ -0,0 +1,128 @@
+use serde_json::{Map, Value};
+
+/// Projects JSON object lines onto a subset of their fields, for `--json-fields`.
+pub(crate) struct Projection<'a> {
+    fields: &'a [String],
+    table: bool,
+}
+
+impl<'a> Projection<'a> {
+    /// Creates a projection onto `fields`. With `table`, the values of the fields are separated by
+    /// tabs instead of being written as a JSON object.
+    pub(crate) fn new(fields: &'a [String], table: bool) -> Self {
+        Self { fields, table }
+    }
+
+    /// Returns `line` with only the projected fields, or `None` if `line` isn't a JSON object.
+    /// The line ending is kept as is.
+    ///
+    /// A field like `a.b` refers to the key `a.b` if it exists, and to the key `b` nested in `a`
+    /// otherwise. In object mode, missing fields are omitted. In table mode, they are left empty.
+    pub(crate) fn project(&self, line: &[u8]) -> Option<Vec<u8>> {
+        let content = line.trim_ascii_end();
+        let object: Map<String, Value> = serde_json::from_slice(content).ok()?;
+
+        let mut projected = Vec::with_capacity(line.len());
+        if self.table {
+            for (i, field) in self.fields.iter().enumerate() {
+                if i != 0 {
+                    projected.push(b'\t');
+                }
+                match get_field(&object, field) {
+                    // strings are printed unquoted unless that would break the table
+                    Some(Value::String(s)) if !s.contains(['\t', '\n', '\r']) => {
+                        projected.extend_from_slice(s.as_bytes());
+                    }
+                    Some(value) => projected.extend_from_slice(value.to_string().as_bytes()),
+                    None => {}
+                }
+            }
+        } else {
+            projected.push(b'{');
+            let present_fields = self
+                .fields
+                .iter()
+                .filter_map(|field| Some((field, get_field(&object, field)?)));
+            for (i, (field, value)) in present_fields.enumerate() {
+                if i != 0 {
+                    projected.push(b',');
+                }
+                projected.extend_from_slice(Value::from(field.as_str()).to_string().as_bytes());
+                projected.push(b':');
+                projected.extend_from_slice(value.to_string().as_bytes());
+            }
+            projected.push(b'}');
+        }
+        projected.extend_from_slice(&line[content.len()..]);
+
+        Some(projected)
+    }
+}
+
+fn get_field<'v>(object: &'v Map<String, Value>, field: &str) -> Option<&'v Value> {
+    if let Some(value) = object.get(field) {
+        return Some(value);
+    }
+    let (key, rest) = field.split_once('.')?;
+    match object.get(key)? {
+        Value::Object(object) => get_field(object, rest),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|field| field.to_string()).collect()
+    }
+
+    #[test]
+    fn project_object() {
+        let fields = fields(&["msg", "level", "missing"]);
+        let projection = Projection::new(&fields, false);
+        assert_eq!(
+            projection.project(br#"{"level":"info","ts":1,"msg":"hi"}"#.as_slice()),
+            Some(br#"{"msg":"hi","level":"info"}"#.to_vec())
+        );
+        assert_eq!(
+            projection.project(b"{\"msg\": [1, 2]}\r\n"),
+            Some(b"{\"msg\":[1,2]}\r\n".to_vec())
+        );
+        assert_eq!(projection.project(b"{}\n"), Some(b"{}\n".to_vec()));
+    }
+
+    #[test]
+    fn project_table() {
+        let fields = fields(&["ts", "missing", "msg", "level"]);
+        let projection = Projection::new(&fields, true);
+        assert_eq!(
+            projection.project(b"{\"level\":null,\"ts\":1,\"msg\":\"hi there\"}\n"),
+            Some(b"1\t\thi there\tnull\n".to_vec())
+        );
+        assert_eq!(
+            projection.project(br#"{"msg":"a\tb"}"#.as_slice()),
+            Some(br#"		"a\tb"	"#.to_vec())
+        );
+    }
+
+    #[test]
+    fn nested_fields() {
+        let fields = fields(&["http.status", "a.b"]);
+        let projection = Projection::new(&fields, false);
+        assert_eq!(
+            projection.project(br#"{"http":{"status":200},"a.b":1,"a":{"b":2}}"#.as_slice()),
+            Some(br#"{"http.status":200,"a.b":1}"#.to_vec())
+        );
+    }
+
+    #[test]
+    fn not_an_object() {
+        let fields = fields(&["a"]);
+        let projection = Projection::new(&fields, false);
+        assert_eq!(projection.project(b"plain text\n"), None);
+        assert_eq!(projection.project(b"[1, 2]\n"), None);
+        assert_eq!(projection.project(b"{\"a\": 1\n"), None);
+    }
+}
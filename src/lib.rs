@@ -0,0 +1,1501 @@
+//! Extracts lines from files by their numbers, the library behind the `line` command.
+//!
+//! Lines are selected with the same selectors as `line -n` (e.g.: `5`, `-1`, `3:7`, or `1:20:2`),
+//! parsed with [`str::parse`] or [`RawLineSelector::list_from_str`], then read with
+//! [`extract_lines`]:
+//!
+//! ```rust
+//! use line_rs::{ExtractOptions, RawLineSelector, extract_lines};
+//! use std::io::Cursor;
+//!
+//! let input = Cursor::new("one\ntwo\nthree\nfour\n");
+//! let selectors = RawLineSelector::list_from_str("-1,2").unwrap();
+//! let options = ExtractOptions::new().with_context(0, 1);
+//! let lines = extract_lines(input, &selectors.0, &options).unwrap();
+//!
+//! let contents: Vec<_> = lines.iter().map(|line| line.content.as_slice()).collect();
+//! assert_eq!(contents, [&b"four\n"[..], b"two\n", b"three\n"]);
+//! assert!(lines[2].is_context);
+//!
+//! let selector: RawLineSelector = "1:2".parse().unwrap();
+//! let lines = extract_lines(Cursor::new("a\nb\nc"), &[selector], &ExtractOptions::new()).unwrap();
+//! assert_eq!(lines.len(), 2);
+//! ```
+//!
+//! [`LineReader`] reads lines by number from any [`BufRead`] without buffering the whole input,
+//! and [`ParsedLineSelector`] resolves selectors against the number of lines of an input.
+
+use crate::cli::{Cli, Command, Encoding, LineEndingCheck, OutputFormat, SortOrder};
+use crate::line_counter::{
+    count_lines, count_lines_and_endings, count_lines_and_matches, count_lines_and_sample,
+    count_lines_up_to, line_at_offset, line_offsets,
+};
+use crate::line_selector::{LineOutOfRange, LineSelector};
+use crate::output::{Line, OutputWriter};
+use anyhow::{Context, Result};
+use clap::CommandFactory;
+use content_inspector::ContentType;
+use regex::bytes::{Regex, RegexSet};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{File, TryLockError};
+use std::io::{BufRead, BufReader, BufWriter, IsTerminal, Read, Seek, Write};
+use std::num::NonZeroUsize;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+mod align;
+mod bench;
+mod byte_range;
+mod checksum;
+mod cli;
+mod config;
+mod count;
+mod decode;
+mod decompress;
+mod elide;
+mod eol;
+mod escape;
+mod extract;
+mod fixed_width;
+mod follow;
+mod histogram;
+mod i18n;
+mod json_fields;
+mod line_counter;
+mod line_index;
+mod line_reader;
+mod line_selector;
+mod mapped;
+mod output;
+mod output_file;
+mod pager;
+mod paths;
+mod random;
+mod ruler;
+mod sed;
+mod serve;
+mod shell_init;
+mod show_all;
+mod size;
+mod sort;
+mod split;
+mod stats;
+mod symbol;
+mod tabs;
+mod transcode;
+mod watch;
+mod width;
+
+pub use extract::{ExtractOptions, ExtractedLine, extract_lines};
+pub use line_reader::LineReader;
+pub use line_selector::{ParsedLineSelector, RawLineSelector, RawLineSelectors};
+
+/// The exit code when a selected line is past the end of the input (e.g.: `-n=500` on a shorter
+/// file), which is also the one of other errors.
+const EXIT_OUT_OF_RANGE: u8 = 1;
+/// The exit code when an input file doesn't exist, which isn't 2 since clap exits with it on
+/// invalid arguments.
+const EXIT_FILE_MISSING: u8 = 3;
+
+/// Runs the `line` command with the arguments of the process, and returns its exit code. This is
+/// the whole `line` binary.
+pub fn run_cli() -> ExitCode {
+    match run() {
+        Ok(exit_code) => exit_code,
+        // the user quit the pager before reading all the output
+        Err(err) if pager::is_closed_early(&err) => ExitCode::SUCCESS,
+        Err(err) => {
+            // the same format as returning the error from `main`, with a localized prefix
+            eprintln!("{}: {err:?}", i18n::message("error", &[]));
+            exit_code(&err)
+        }
+    }
+}
+
+/// Returns the exit code of `err`, which tells missing files apart from selected lines that are
+/// out of range, for scripts.
+fn exit_code(err: &anyhow::Error) -> ExitCode {
+    let is_file_missing = err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|err| err.kind() == std::io::ErrorKind::NotFound)
+    });
+    if is_file_missing {
+        ExitCode::from(EXIT_FILE_MISSING)
+    } else {
+        ExitCode::from(EXIT_OUT_OF_RANGE)
+    }
+}
+
+fn run() -> Result<ExitCode> {
+    // cmd.exe and PowerShell pass wildcards through, so they are expanded here on Windows (this
+    // is a no-op on other platforms, where the shell expands them)
+    let args: Vec<OsString> = wild::args_os().collect();
+    // `sed -n` arguments clash with line's own (e.g.: `-n`), so they are translated before parsing
+    let mut args = match sed::compat_args(&args) {
+        Some(sed_args) => {
+            config::parse_with_defaults(sed::translate_compat_args(&args[0], sed_args)?)?
+        }
+        None => config::parse_with_defaults(args)?,
+    };
+    if args.canonical {
+        i18n::use_english();
+    }
+
+    if let Some(shell) = args.generate_completions {
+        let mut script = Vec::new();
+        clap_complete::generate(shell, &mut Cli::command(), "line", &mut script);
+        return std::io::stdout()
+            .write_all(&script)
+            .map(|()| ExitCode::SUCCESS)
+            .context("Failed to output completion script");
+    }
+
+    if let Some(command) = args.command {
+        let result = match command {
+            Command::Bench { file } => bench::run(&file, available_threads()),
+            Command::Count { files } => count::run(&files, available_threads()),
+            Command::Serve { listen, root } => serve::run(listen, &root),
+            Command::ShellInit { shell } => shell_init::run(shell),
+            Command::Stats {
+                file,
+                line_selector,
+                histogram,
+            } => stats::run(&file, line_selector, histogram),
+        };
+        return result.map(|()| ExitCode::SUCCESS);
+    }
+
+    // if `--context` is set (i.e. not 0), then `--context=N` is equivalent
+    // to `--before=N --after=N`
+    if args.context != 0 {
+        args.before = args.context;
+        args.after = args.context;
+    }
+    if let Some((before, after)) = args.window {
+        args.before = before;
+        args.after = after;
+    }
+    if let Some(line_file) = &args.line_file {
+        let line_selectors = if line_file.as_os_str() == "-" {
+            RawLineSelector::list_from_lines(std::io::stdin().lock())
+        } else {
+            File::open(paths::for_opening(line_file))
+                .context("Couldn't open file")
+                .and_then(|file| RawLineSelector::list_from_lines(BufReader::new(file)))
+        }
+        .with_context(|| format!("Invalid --line-file `{}`", paths::display(line_file)))?;
+        args.raw_line_selectors.push(line_selectors);
+    }
+    // the file is printed as is, without the selected lines
+    if args.delete {
+        args.invert = true;
+        args.plain = cli::When::Always;
+        args.color = cli::When::Never;
+    }
+
+    let files = std::mem::take(&mut args.files);
+    if files.len() > 1 && args.split_every.is_some() {
+        anyhow::bail!("--split-every can't be used with more than one file");
+    }
+    if files.len() > 1 && args.follow {
+        anyhow::bail!("--follow can't be used with more than one file");
+    }
+    if args.follow
+        && let Some(template) = &args.format_str
+        && template.uses_offset()
+    {
+        anyhow::bail!("The `{{offset}}` placeholder of --format-str can't be used with --follow");
+    }
+    // headers would break structured output, checksums are already followed by their file, and
+    // the grid shows the file above its lines
+    let print_file_headers = files.len() > 1
+        && !args.delete
+        && !args.grid
+        && matches!(args.output_format, OutputFormat::Text)
+        && args.checksum.is_none();
+    if args.in_place {
+        for path in &files {
+            let mut edited_file = output_file::OutputFile::create(path, args.write_buffer)?;
+            select_from_file(&args, path, &mut edited_file)?;
+            edited_file.persist()?;
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.quiet {
+        // only the exit code tells whether the selected lines exist
+        return match print_files(&args, &files, false, &mut std::io::sink()) {
+            Err(err) if err.chain().any(|cause| cause.is::<LineOutOfRange>()) => {
+                Ok(ExitCode::from(EXIT_OUT_OF_RANGE))
+            }
+            result => result.map(|()| ExitCode::SUCCESS),
+        };
+    }
+    let is_terminal = args.prints_to_stdout() && std::io::stdout().is_terminal() && !args.canonical;
+    if args.watch {
+        return watch::watch(&args, &files, print_file_headers, is_terminal)
+            .map(|()| ExitCode::SUCCESS);
+    }
+
+    let mut output_file = match &args.output {
+        Some(path) => Some(output_file::OutputFile::create(path, args.write_buffer)?),
+        None => None,
+    };
+    // a pager would wait for the end of the output, which never comes when following a file
+    let paging = if args.follow {
+        cli::When::Never
+    } else {
+        args.paging
+    };
+    let _pager = pager::start(paging, is_terminal);
+    match &mut output_file {
+        Some(output_file) => print_files(&args, &files, print_file_headers, output_file)?,
+        None => print_files(&args, &files, print_file_headers, &mut std::io::stdout())?,
+    }
+    if let Some(output_file) = output_file {
+        output_file.persist()?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prints the lines selected by `args` from each of `files` to `out`, after a header naming the
+/// file if `print_file_headers` is true.
+fn print_files(
+    args: &Cli,
+    files: &[PathBuf],
+    print_file_headers: bool,
+    out: &mut impl Write,
+) -> Result<()> {
+    for (i, path) in files.iter().enumerate() {
+        if print_file_headers {
+            let separator = if i == 0 { "" } else { "\n" };
+            writeln!(out, "{separator}==> {} <==", paths::display(path))
+                .context("Failed to output file header")?;
+        }
+        let file_end = select_from_file(args, path, out)?;
+        if args.follow {
+            follow::follow(args, path, file_end)?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints the lines selected by `args` from the file at `path` to `out`, with negative selectors
+/// counting from the end of this file.
+fn select_from_file(args: &Cli, path: &Path, out: &mut impl Write) -> Result<follow::FileEnd> {
+    let mut file = open_file(path)?;
+    if let Some(max_file_size) = args.max_file_size
+        && !args.force
+    {
+        check_file_size(&file, path, max_file_size as u64)?;
+    }
+    if args.lock {
+        lock_shared(&file, path)?;
+    }
+    // NUL-separated records would be taken for UTF-16
+    let charset = match args.encoding {
+        cli::Charset::Auto if args.separator() == b'\0' => cli::Charset::Utf8,
+        charset => charset,
+    };
+    let decompressed = if args.no_decompress {
+        None
+    } else {
+        decompress::decompress(&mut file, path, decompressed_prefix(args, charset))?
+    };
+    let is_decompressed = decompressed.is_some();
+    if let Some(decompressed) = decompressed {
+        if args.follow {
+            anyhow::bail!("--follow can't be used with compressed files");
+        }
+        ensure_not_empty(&decompressed, path)?;
+        file = decompressed;
+    }
+    let transcoded = transcode::transcode(&mut file, path, charset)?;
+    let is_transcoded = transcoded.is_some();
+    if let Some(transcoded) = transcoded {
+        if args.follow {
+            anyhow::bail!("--follow can only be used with UTF-8 files");
+        }
+        ensure_not_empty(&transcoded, path)?;
+        file = transcoded;
+    }
+    if let Some(byte_range) = args.bytes
+        && args.exact
+    {
+        let len = file
+            .metadata()
+            .context("Failed to read file metadata")?
+            .len();
+        byte_range::copy_exact(&mut file, byte_range.resolve(len)?, out)?;
+        // `--exact` can't be used with `--follow`, so the end of the file isn't followed
+        return Ok(follow::FileEnd { len, n_lines: 0 });
+    }
+    let read_buffer = match args.read_buffer {
+        Some(read_buffer) => read_buffer,
+        None => default_read_buffer_size(&file)?,
+    };
+    let mut file = BufReader::with_capacity(read_buffer, file);
+
+    let content_type = inspect_content(&mut file)?;
+    // NUL-separated records look binary, but are text
+    if !args.allow_binary_files && args.separator() != b'\0' && content_type.is_binary() {
+        anyhow::bail!(i18n::message(
+            "binary-file",
+            &[("path", paths::display(path).to_string().into())]
+        ));
+    }
+
+    let separator = args.separator();
+    let patterns = if args.patterns.is_empty() {
+        None
+    } else {
+        Some(
+            RegexSet::new(args.patterns.iter().map(Regex::as_str))
+                .context("Invalid --match patterns")?,
+        )
+    };
+    let mapped_lines = if args.mmap {
+        Some(mapped::MappedLines::new(file.get_ref(), separator)?)
+    } else {
+        None
+    };
+    let mut line_index = None;
+    // the lines matching `--match` are found during the counting pass if it reads every line
+    let mut matched_line_nums = None;
+    let mut rng = args
+        .seed
+        .map_or_else(random::Rng::from_entropy, random::Rng::new);
+    // the lines picked by `--sample` are picked during the counting pass if it reads every line
+    let mut sampled_line_nums = None;
+    let n_lines = if args.show_meta || args.check_line_endings.is_some() {
+        // line endings are tallied during the counting pass, which is then single-threaded
+        let (n_lines, line_endings) = count_lines_and_endings(&mut file)?;
+        if let Some(check) = args.check_line_endings
+            && line_endings.is_mixed()
+        {
+            let message = format!(
+                "`{}` has {}",
+                paths::display(path),
+                line_endings.describe_mix()
+            );
+            match check {
+                LineEndingCheck::Warn => eprintln!("Warning: {message}"),
+                LineEndingCheck::Error => anyhow::bail!(message),
+            }
+        }
+        if args.show_meta {
+            let meta = stats::FileMeta {
+                path,
+                len: file
+                    .get_ref()
+                    .metadata()
+                    .context("Failed to read file metadata")?
+                    .len(),
+                n_lines,
+                content_type,
+                line_endings,
+            };
+            write!(out, "{meta}").context("Failed to output file metadata")?;
+        }
+        n_lines
+    } else if let Some(line_length) = args.assume_line_length {
+        let len = file
+            .get_ref()
+            .metadata()
+            .context("Failed to read file metadata")?
+            .len();
+        fixed_width::count_lines(len, line_length.get())?
+    } else if let Some(patterns) = &patterns {
+        let (n_lines, line_nums) = count_lines_and_matches(&mut file, separator, patterns)?;
+        matched_line_nums = Some(line_nums);
+        n_lines
+    } else if args.index && !is_decompressed && !is_transcoded {
+        let index = line_index::load_or_build(path, &mut file, separator)?;
+        let n_lines = index.n_lines;
+        line_index = Some(index);
+        n_lines
+    } else if let Some(sample_size) = args.sample {
+        let (n_lines, line_nums) =
+            count_lines_and_sample(&mut file, separator, sample_size.get(), &mut rng)?;
+        sampled_line_nums = Some(line_nums);
+        n_lines
+    } else if let Some(prefix_len) = selected_prefix_len(args) {
+        // the lines past the last selected one are never read
+        match &mapped_lines {
+            Some(mapped_lines) => mapped_lines.count_lines_up_to(prefix_len),
+            None => count_lines_up_to(&mut file, prefix_len, separator)?,
+        }
+    } else {
+        let threads = args.threads.map_or_else(available_threads, NonZeroUsize::get);
+        match &mapped_lines {
+            Some(mapped_lines) => mapped_lines.count_lines(threads),
+            None => count_lines(&mut file, threads, separator)?,
+        }
+    };
+    let file_end = follow::FileEnd {
+        len: file
+            .get_ref()
+            .metadata()
+            .context("Failed to read file metadata")?
+            .len(),
+        n_lines,
+    };
+    let mut raw_line_selectors: Vec<RawLineSelector> = args
+        .raw_line_selectors
+        .iter()
+        .flat_map(|line_selectors| line_selectors.0.iter().copied())
+        .collect();
+    for name in &args.symbol {
+        let line_selectors = symbol::resolve(path, name, symbol::SymbolKind::Any)?;
+        raw_line_selectors.extend(line_selectors);
+    }
+    for name in &args.function {
+        let line_selectors = symbol::resolve(path, name, symbol::SymbolKind::Function)?;
+        raw_line_selectors.extend(line_selectors);
+    }
+    if !args.expression.is_empty() {
+        let line_selectors = sed::resolve(&args.expression, &mut file, n_lines, separator)?;
+        raw_line_selectors.extend(line_selectors);
+    }
+    if let Some(patterns) = &patterns {
+        let line_nums = match matched_line_nums {
+            Some(line_nums) => line_nums,
+            None => count_lines_and_matches(&mut file, separator, patterns)?.1,
+        };
+        raw_line_selectors.extend(RawLineSelector::runs(line_nums));
+    }
+    if let Some((k, n)) = args.chunk {
+        raw_line_selectors.extend(RawLineSelector::chunk(k, n, n_lines));
+    }
+    // the bytes at the edges of `--head-bytes` and `--tail-bytes` are in the last and the first
+    // line they select, respectively
+    if args.head_bytes.is_some() || args.tail_bytes.is_some() {
+        let len = file
+            .get_ref()
+            .metadata()
+            .context("Failed to read file metadata")?
+            .len();
+        if let Some(n_bytes) = args.head_bytes
+            && n_bytes > 0
+        {
+            let last_byte = (n_bytes as u64).min(len) - 1;
+            let last_line = line_at_offset(&mut file, last_byte, separator)?;
+            raw_line_selectors.push(RawLineSelector::Range(
+                Some(1),
+                Some(last_line as isize + 1),
+            ));
+        }
+        if let Some(n_bytes) = args.tail_bytes
+            && n_bytes > 0
+        {
+            let first_byte = len.saturating_sub(n_bytes as u64);
+            let first_line = line_at_offset(&mut file, first_byte, separator)?;
+            raw_line_selectors.push(RawLineSelector::Range(Some(first_line as isize + 1), None));
+        }
+    }
+    if let Some(byte_range) = args.bytes {
+        let len = file
+            .get_ref()
+            .metadata()
+            .context("Failed to read file metadata")?
+            .len();
+        let bytes = byte_range.resolve(len)?;
+        let first_line = line_at_offset(&mut file, *bytes.start(), separator)?;
+        let last_line = line_at_offset(&mut file, *bytes.end(), separator)?;
+        raw_line_selectors.push(if first_line == last_line {
+            RawLineSelector::Single(first_line as isize + 1)
+        } else {
+            RawLineSelector::Range(Some(first_line as isize + 1), Some(last_line as isize + 1))
+        });
+    }
+    if let Some(step) = args.every {
+        // `--every` on its own steps through the whole file
+        if raw_line_selectors.is_empty()
+            && patterns.is_none()
+            && args.sample.is_none()
+            && args.chunk.is_none()
+        {
+            raw_line_selectors.push(RawLineSelector::Range(None, None));
+        }
+        for raw_line_selector in &mut raw_line_selectors {
+            *raw_line_selector = raw_line_selector.with_every(step, args.starting_at);
+        }
+    }
+    if let Some(sample_size) = args.sample {
+        let mut line_nums = match sampled_line_nums {
+            Some(line_nums) => line_nums,
+            // the lines were counted without being read (e.g.: with `--index`)
+            None => random::reservoir_sample(0..n_lines, sample_size.get(), &mut rng),
+        };
+        if args.sample_ordered {
+            line_nums.sort_unstable();
+        } else {
+            random::shuffle(&mut line_nums, &mut rng);
+        }
+        raw_line_selectors.extend(
+            line_nums
+                .into_iter()
+                .map(|line_num| RawLineSelector::Single(line_num as isize + 1)),
+        );
+    }
+    let mut line_selectors = parse_line_selectors(&raw_line_selectors, n_lines, &mut rng)?;
+    if args.invert {
+        let raw_line_selectors = line_selector::invert(&line_selectors, n_lines);
+        line_selectors = parse_line_selectors(&raw_line_selectors, n_lines, &mut rng)?;
+    }
+    if args.unique {
+        let raw_line_selectors = line_selector::unique(&line_selectors, n_lines);
+        line_selectors = parse_line_selectors(&raw_line_selectors, n_lines, &mut rng)?;
+    }
+
+    // the offsets of `{offset}` are found in a pass of their own, before any line is read
+    let line_offsets = match &args.format_str {
+        Some(template) if template.uses_offset() => {
+            let mut ranges: Vec<_> = line_selectors
+                .iter()
+                .flat_map(|line_selector| {
+                    printed_line_ranges(line_selector, args.before, args.after, n_lines)
+                })
+                .collect();
+            ranges.sort_unstable_by_key(|range| *range.start());
+            line_offsets(&mut file, &ranges, separator)?
+        }
+        _ => HashMap::new(),
+    };
+
+    if let Some(line_length) = args.assume_line_length {
+        // lines are read straight from the file since every read seeks anyway
+        let mut lines = fixed_width::FixedWidthLines::new(file.into_inner(), line_length.get());
+        return output_selection(
+            &line_selectors,
+            args,
+            path,
+            n_lines,
+            line_offsets,
+            &mut lines,
+            out,
+        )
+        .map(|()| file_end);
+    }
+
+    if let Some(mut lines) = mapped_lines {
+        // mapped lines can be read in any order without being buffered
+        return output_selection(
+            &line_selectors,
+            args,
+            path,
+            n_lines,
+            line_offsets,
+            &mut lines,
+            out,
+        )
+        .map(|()| file_end);
+    }
+
+    if let Some(index) = line_index {
+        let mut lines = line_index::IndexedLines::new(file, index);
+        return output_selection(
+            &line_selectors,
+            args,
+            path,
+            n_lines,
+            line_offsets,
+            &mut lines,
+            out,
+        )
+        .map(|()| file_end);
+    }
+
+    let line_reader = LineReader::new(file)
+        .with_retries(!args.no_retry)
+        .with_separator(separator);
+    // aligning and rulers need a first pass over the printed lines to measure them, and sorting
+    // needs all of them at once
+    if args.align.is_none()
+        && !args.json_table
+        && !args.ruler
+        && args.sort_content.is_none()
+        && is_printed_in_file_order(&line_selectors)
+    {
+        // every line is printed at most once and in the same order as in the file, so lines can
+        // be streamed straight to the output through a single reused buffer
+        let mut lines = StreamedLines {
+            line_reader,
+            buf: Vec::new(),
+        };
+        output_selection(
+            &line_selectors,
+            args,
+            path,
+            n_lines,
+            line_offsets,
+            &mut lines,
+            out,
+        )
+    } else {
+        let mut lines = read_lines(
+            line_reader,
+            &line_selectors,
+            args.before,
+            args.after,
+            n_lines,
+        )?;
+        output_selection(
+            &line_selectors,
+            args,
+            path,
+            n_lines,
+            line_offsets,
+            &mut lines,
+            out,
+        )
+    }
+    .map(|()| file_end)
+}
+
+/// Returns how many lines at the start of the file hold every selected line and its context, if
+/// lines are only selected by `-n` and all its selectors have positive bounds.
+///
+/// Only these lines need to be counted: if the file is shorter, they're all of its lines, so
+/// selectors out of range are still reported, and otherwise none of the selectors or the context
+/// lines reach past them.
+fn selected_prefix_len(args: &Cli) -> Option<usize> {
+    if !args.symbol.is_empty()
+        || !args.function.is_empty()
+        || !args.expression.is_empty()
+        || !args.patterns.is_empty()
+        || args.chunk.is_some()
+        || args.head_bytes.is_some()
+        || args.bytes.is_some()
+        || args.tail_bytes.is_some()
+        || args.every.is_some()
+        || args.sample.is_some()
+        || args.invert
+        || args.follow
+    {
+        return None;
+    }
+    let last_line_num = args
+        .raw_line_selectors
+        .iter()
+        .flat_map(|line_selectors| line_selectors.0.iter())
+        .try_fold(0, |last_line_num, line_selector| {
+            Some(last_line_num.max(line_selector.last_line_num()?))
+        })?;
+    (last_line_num > 0).then(|| last_line_num.saturating_add(args.after))
+}
+
+/// Returns the lines of a compressed file that need to be decompressed, if only those counted up to
+/// `selected_prefix_len` are ever read, and they can be counted before being transcoded.
+fn decompressed_prefix(args: &Cli, charset: cli::Charset) -> Option<decompress::Prefix> {
+    if args.show_meta
+        || args.check_line_endings.is_some()
+        || args.assume_line_length.is_some()
+        || matches!(charset, cli::Charset::Utf16Le | cli::Charset::Utf16Be)
+    {
+        return None;
+    }
+    Some(decompress::Prefix {
+        n_lines: selected_prefix_len(args)?,
+        separator: args.separator(),
+    })
+}
+
+/// Prints the selected lines to `out` (stdout or the `--output` file), or their checksum or byte
+/// counts if `--checksum` or `--count-bytes` is given.
+fn output_selection(
+    line_selectors: &[LineSelector],
+    args: &Cli,
+    path: &Path,
+    n_lines: usize,
+    line_offsets: HashMap<usize, u64>,
+    lines: &mut impl LineSource,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    // stdout may have been redirected to a pager, which displays the output on the terminal
+    let is_terminal = args.prints_to_stdout()
+        && (std::io::stdout().is_terminal() || pager::is_on_terminal())
+        && !args.canonical;
+    let mut stdout = BufWriter::with_capacity(args.write_buffer, out);
+
+    if let Some(algorithm) = args.checksum {
+        let checksum = checksum::compute(algorithm, line_selectors, lines)?;
+        writeln!(stdout, "{checksum}  {}", paths::display(path))
+            .and_then(|()| stdout.flush())
+            .context("Failed to output checksum")?;
+        return Ok(());
+    }
+
+    if args.count_bytes {
+        let byte_counts = stats::count_bytes(line_selectors, lines)?;
+        stats::print_byte_counts(&mut stdout, line_selectors, &byte_counts)
+            .and_then(|()| stdout.flush())
+            .context("Failed to output byte counts")?;
+        return Ok(());
+    }
+
+    let rewriter = LineRewriter::new(args, line_selectors, n_lines, lines, is_terminal)?;
+
+    let (destination, is_terminal) = match args.split_every {
+        Some(lines_per_file) => {
+            let separator = args.separator();
+            let files = split::SplitFiles::new(
+                args.output_prefix.clone(),
+                lines_per_file.get(),
+                separator,
+                args.write_buffer,
+            );
+            (split::Destination::Files(files), false)
+        }
+        None => (split::Destination::Stdout(stdout), is_terminal),
+    };
+    // the line numbers of context lines after the last selected line are the largest printed
+    let max_line_num = line_selectors
+        .iter()
+        .filter_map(LineSelector::max_line_num)
+        .max()
+        .map_or(0, |line_num| {
+            line_num
+                .saturating_add(args.after)
+                .min(n_lines.saturating_sub(1))
+        })
+        .saturating_add(args.offset_base);
+    let output = output::get_output_writer(
+        destination,
+        args,
+        path,
+        is_terminal,
+        max_line_num,
+        line_offsets,
+    )?;
+    let output = output::OffsetLineNumbers(output, args.offset_base);
+    if args.line_buffered {
+        let output = output::LineBuffered(output);
+        print_selection(
+            line_selectors,
+            args,
+            path,
+            n_lines,
+            &rewriter,
+            lines,
+            output,
+        )
+    } else {
+        print_selection(
+            line_selectors,
+            args,
+            path,
+            n_lines,
+            &rewriter,
+            lines,
+            output,
+        )
+    }
+}
+
+/// Prints the selected lines to `output`, followed by a summary if `--summary` is given.
+fn print_selection(
+    line_selectors: &[LineSelector],
+    args: &Cli,
+    path: &Path,
+    n_lines: usize,
+    rewriter: &LineRewriter,
+    lines: &mut impl LineSource,
+    mut output: impl OutputWriter,
+) -> anyhow::Result<()> {
+    if !args.summary {
+        print_line_selectors(line_selectors, args, n_lines, rewriter, lines, &mut output)?;
+        output
+            .finish()
+            .context("Failed to output the end of the grid")?;
+        return output.flush().context("Failed to flush output");
+    }
+
+    let mut lines = stats::TalliedLines::new(lines);
+    print_line_selectors(
+        line_selectors,
+        args,
+        n_lines,
+        rewriter,
+        &mut lines,
+        &mut output,
+    )?;
+    output
+        .finish()
+        .context("Failed to output the end of the grid")?;
+    let summary = stats::format_summary(line_selectors.len(), &lines, path);
+    if output.is_decorated() {
+        writeln!(output, "\n{summary}").context("Failed to output summary")?;
+    } else {
+        output.flush().context("Failed to flush output")?;
+        eprintln!("{summary}");
+    }
+    output.flush().context("Failed to flush output")
+}
+
+/// The number of columns between the tab stops of `--show-all` if `--tabs` isn't given.
+const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// Rewrites the content of printed lines for `--strip-cr`, `--escape-control-only`, `--show-all`,
+/// `--tabs`, `--elide-long`, `--max-line-length`, and `--show-eol`, and of selected lines for
+/// `--decode`, `--json-fields`, and `--align`.
+struct LineRewriter<'a> {
+    /// The byte ending lines, kept as is when decoding or escaping
+    terminator: u8,
+    escape_control: bool,
+    show_all: bool,
+    /// The maximum number of characters of printed lines
+    elide_long: Option<NonZeroUsize>,
+    /// The maximum number of bytes of printed lines
+    max_line_length: Option<usize>,
+    /// The number of columns between tab stops, for `--tabs`
+    tab_width: Option<NonZeroUsize>,
+    show_eol: bool,
+    strip_cr: bool,
+    decode: Option<Encoding>,
+    /// Whether control characters and invalid UTF-8 in decoded lines are escaped, since decoded
+    /// content may be binary, which would garble the terminal
+    escape_decoded: bool,
+    projection: Option<json_fields::Projection<'a>>,
+    columns: Option<align::Columns>,
+    /// The width of the ruler of each selector, for `--ruler`
+    ruler_widths: Vec<usize>,
+    strict_utf8: bool,
+}
+
+impl<'a> LineRewriter<'a> {
+    /// Measures the columns of the selected lines if they are aligned, and the printed lines of
+    /// each selector if they get a ruler, which reads them all.
+    fn new(
+        args: &'a Cli,
+        line_selectors: &[LineSelector],
+        n_lines: usize,
+        lines: &mut impl LineSource,
+        is_terminal: bool,
+    ) -> anyhow::Result<Self> {
+        let projection = (!args.json_fields.is_empty())
+            .then(|| json_fields::Projection::new(&args.json_fields, args.json_table));
+        let mut rewriter = Self {
+            terminator: args.separator(),
+            escape_control: args.escape_control_only,
+            show_all: args.show_all,
+            elide_long: args.elide_long,
+            max_line_length: args.max_line_length,
+            tab_width: args.tabs,
+            show_eol: args.show_eol || args.show_all,
+            strip_cr: args.strip_cr,
+            decode: args.decode,
+            escape_decoded: is_terminal && !args.force,
+            projection,
+            columns: None,
+            ruler_widths: Vec::new(),
+            strict_utf8: args.strict_utf8,
+        };
+
+        // a table separates the projected fields with tabs
+        let delimiter = if args.json_table {
+            Some("\t")
+        } else {
+            args.align.as_deref()
+        };
+        if let Some(delimiter) = delimiter {
+            let mut columns = align::Columns::new(delimiter);
+            for line_selector in line_selectors {
+                for line_num in line_selector.iter() {
+                    columns.measure(&rewriter.project(lines.get(line_num)?));
+                }
+            }
+            rewriter.columns = Some(columns);
+        }
+
+        if args.ruler {
+            for line_selector in line_selectors {
+                let mut ruler_width = 0;
+                for selected_line_num in line_selector.iter() {
+                    let (first_line_num, last_line_num) = get_context_lines_endpoints(
+                        selected_line_num,
+                        args.before,
+                        args.after,
+                        n_lines,
+                    );
+                    for line_num in first_line_num..=last_line_num {
+                        let line = lines.get(line_num)?;
+                        let line = if line_num == selected_line_num {
+                            rewriter.rewrite(line)
+                        } else {
+                            rewriter.rewrite_context(line)
+                        };
+                        let (content, _) = escape::split_terminator(&line, rewriter.terminator);
+                        ruler_width = ruler_width.max(width::display_width(content));
+                    }
+                }
+                rewriter.ruler_widths.push(ruler_width);
+            }
+        }
+
+        Ok(rewriter)
+    }
+
+    /// Fails if `--strict-utf8` is given and the selected line `line_num` isn't valid UTF-8.
+    fn check_utf8(&self, line_num: usize, line: &[u8]) -> anyhow::Result<()> {
+        if self.strict_utf8
+            && let Err(err) = std::str::from_utf8(line)
+        {
+            anyhow::bail!(
+                "Line {} isn't valid UTF-8 (invalid byte at offset {})",
+                line_num + 1,
+                err.valid_up_to()
+            );
+        }
+        Ok(())
+    }
+
+    /// Rewrites a context line.
+    fn rewrite_context<'l>(&self, line: &'l [u8]) -> Cow<'l, [u8]> {
+        self.mark_eol(self.elide(self.expand_tabs(self.escape(self.strip_cr(line)))))
+    }
+
+    /// Rewrites a selected line.
+    fn rewrite<'l>(&self, line: &'l [u8]) -> Cow<'l, [u8]> {
+        let line = self.project(line);
+        let line = match &self.columns {
+            Some(columns) => Cow::Owned(columns.align(&line)),
+            None => line,
+        };
+        self.mark_eol(self.elide(self.expand_tabs(line)))
+    }
+
+    /// Rewrites a selected line, except for aligning it.
+    fn project<'l>(&self, line: &'l [u8]) -> Cow<'l, [u8]> {
+        let line = self.strip_cr(line);
+        // lines that can't be decoded are printed as is
+        let line = match self
+            .decode
+            .and_then(|encoding| decode::decode(&line, encoding, self.terminator))
+        {
+            Some(decoded_line) if self.escape_decoded => {
+                Cow::Owned(escape::escape_control(&decoded_line, self.terminator).into_owned())
+            }
+            Some(decoded_line) => Cow::Owned(decoded_line),
+            None => line,
+        };
+        let line = self.escape(line);
+        if let Some(projection) = &self.projection
+            && let Some(projected_line) = projection.project(&line)
+        {
+            return Cow::Owned(projected_line);
+        }
+        line
+    }
+
+    /// Turns a `\r\n` line ending into `\n`, for `--strip-cr`.
+    fn strip_cr<'l>(&self, line: &'l [u8]) -> Cow<'l, [u8]> {
+        if self.strip_cr
+            && self.terminator == b'\n'
+            && let Some(content) = line.strip_suffix(b"\r\n")
+        {
+            let mut stripped = Vec::with_capacity(line.len() - 1);
+            stripped.extend_from_slice(content);
+            stripped.push(b'\n');
+            return Cow::Owned(stripped);
+        }
+        Cow::Borrowed(line)
+    }
+
+    fn escape<'l>(&self, line: Cow<'l, [u8]>) -> Cow<'l, [u8]> {
+        if self.show_all {
+            // tabs are expanded along with their marker
+            let tab_width = self.tab_width.map_or(DEFAULT_TAB_WIDTH, NonZeroUsize::get);
+            return match line {
+                Cow::Borrowed(line) => show_all::show_all(line, self.terminator, tab_width),
+                Cow::Owned(line) => {
+                    Cow::Owned(show_all::show_all(&line, self.terminator, tab_width).into_owned())
+                }
+            };
+        }
+        if !self.escape_control {
+            return line;
+        }
+        match line {
+            Cow::Borrowed(line) => escape::escape_control(line, self.terminator),
+            Cow::Owned(line) => {
+                Cow::Owned(escape::escape_control(&line, self.terminator).into_owned())
+            }
+        }
+    }
+
+    fn expand_tabs<'l>(&self, line: Cow<'l, [u8]>) -> Cow<'l, [u8]> {
+        let Some(tab_width) = self.tab_width else {
+            return line;
+        };
+        match line {
+            Cow::Borrowed(line) => tabs::expand_tabs(line, tab_width.get()),
+            Cow::Owned(line) => Cow::Owned(tabs::expand_tabs(&line, tab_width.get()).into_owned()),
+        }
+    }
+
+    fn mark_eol<'l>(&self, line: Cow<'l, [u8]>) -> Cow<'l, [u8]> {
+        if self.show_eol {
+            Cow::Owned(eol::show_eol(&line, self.terminator))
+        } else {
+            line
+        }
+    }
+
+    fn elide<'l>(&self, line: Cow<'l, [u8]>) -> Cow<'l, [u8]> {
+        if let Some(max_bytes) = self.max_line_length {
+            return match line {
+                Cow::Borrowed(line) => elide::truncate_end(line, max_bytes, self.terminator),
+                Cow::Owned(line) => {
+                    Cow::Owned(elide::truncate_end(&line, max_bytes, self.terminator).into_owned())
+                }
+            };
+        }
+        let Some(max_chars) = self.elide_long else {
+            return line;
+        };
+        match line {
+            Cow::Borrowed(line) => elide::elide_middle(line, max_chars.get(), self.terminator),
+            Cow::Owned(line) => Cow::Owned(
+                elide::elide_middle(&line, max_chars.get(), self.terminator).into_owned(),
+            ),
+        }
+    }
+}
+
+/// Gives access to the content of the lines being printed.
+pub(crate) trait LineSource {
+    /// Returns the content of `line_num` (zero-based).
+    fn get(&mut self, line_num: usize) -> anyhow::Result<&[u8]>;
+}
+
+/// Lines read ahead of printing, used when lines are printed out of order or more than once.
+pub(crate) struct BufferedLines(HashMap<usize, Vec<u8>>);
+
+impl LineSource for BufferedLines {
+    fn get(&mut self, line_num: usize) -> anyhow::Result<&[u8]> {
+        Ok(&self.0[&line_num])
+    }
+}
+
+/// Lines read on demand, used when lines are printed in file order and at most once.
+struct StreamedLines<R> {
+    line_reader: LineReader<R>,
+    buf: Vec<u8>,
+}
+
+impl<R: BufRead> LineSource for StreamedLines<R> {
+    fn get(&mut self, line_num: usize) -> anyhow::Result<&[u8]> {
+        self.buf.clear();
+        self.line_reader
+            .read_specific_line(&mut self.buf, line_num)
+            .with_context(|| format!("Failed to read line number {}", line_num + 1))?;
+        Ok(&self.buf)
+    }
+}
+
+/// Reads all selected lines and their context lines into memory.
+pub(crate) fn read_lines<R: BufRead>(
+    mut line_reader: LineReader<R>,
+    line_selectors: &[LineSelector],
+    before: usize,
+    after: usize,
+    n_lines: usize,
+) -> anyhow::Result<BufferedLines> {
+    // store the line numbers of all lines to be read (selected lines and context lines)
+    let mut lines: HashMap<usize, Vec<u8>> = HashMap::new();
+    for line_selector in line_selectors {
+        for printed_lines in printed_line_ranges(line_selector, before, after, n_lines) {
+            for line_num in printed_lines {
+                lines.entry(line_num).or_default();
+            }
+        }
+    }
+    let mut line_nums_to_read: Box<[usize]> = lines.keys().copied().collect();
+    line_nums_to_read.sort_unstable();
+
+    for line_num in line_nums_to_read {
+        let line_buf = lines
+            .get_mut(&line_num)
+            .expect("we already inserted all line numbers into the hash map");
+        line_reader
+            .read_specific_line(line_buf, line_num)
+            .with_context(|| format!("Failed to read line number {}", line_num + 1))?;
+    }
+
+    Ok(BufferedLines(lines))
+}
+
+/// Returns true if the selected lines and their context lines would be printed in strictly
+/// ascending order, i.e. no line is printed twice and no line is printed before a previous one.
+///
+/// The context of selected lines is merged where it overlaps, even across selectors, as long as
+/// they are selected in ascending order, so that is all it takes.
+fn is_printed_in_file_order(line_selectors: &[LineSelector]) -> bool {
+    let mut last_selected_line = None;
+    for line_selector in line_selectors {
+        if let ParsedLineSelector::Range(_, _, step) = line_selector.parsed
+            && step < 0
+        {
+            return false;
+        }
+        if let Some(first_selected_line) = line_selector.min_line_num()
+            && last_selected_line.is_some_and(|line_num| first_selected_line <= line_num)
+        {
+            return false;
+        }
+        if let Some(line_num) = line_selector.max_line_num() {
+            last_selected_line = Some(line_num);
+        }
+    }
+    true
+}
+
+/// Prints the selected lines of all selectors sorted by their content, for `--sort-content`.
+fn print_sorted_lines(
+    line_selectors: &[LineSelector],
+    order: SortOrder,
+    rewriter: &LineRewriter,
+    lines: &mut impl LineSource,
+    output: &mut impl OutputWriter,
+) -> anyhow::Result<()> {
+    let mut selected_lines = Vec::new();
+    for line_selector in line_selectors {
+        for line_num in line_selector.iter_in_selection_order() {
+            let line = lines.get(line_num)?;
+            rewriter.check_utf8(line_num, line)?;
+            let line = rewriter.rewrite(line).into_owned();
+            selected_lines.push((line_num, line));
+        }
+    }
+    sort::sort_lines(&mut selected_lines, order);
+
+    for (line_num, line) in &selected_lines {
+        output
+            .print_line(Line::Selected {
+                line_num: *line_num,
+                line,
+            })
+            .with_context(|| format!("Failed to output line {}", line_num + 1))?;
+    }
+    Ok(())
+}
+
+fn print_line_selectors(
+    line_selectors: &[LineSelector],
+    args: &Cli,
+    n_lines: usize,
+    rewriter: &LineRewriter,
+    lines: &mut impl LineSource,
+    output: &mut impl OutputWriter,
+) -> anyhow::Result<()> {
+    if let Some(order) = args.sort_content {
+        return print_sorted_lines(line_selectors, order, rewriter, lines, output);
+    }
+
+    let mut is_first = true;
+    // the next line to print if the group of the previous selected line continues, i.e.: if its
+    // context overlaps or touches the context of the current selected line, which may be one of
+    // the next selector
+    let mut next_line_num = None;
+    for (i, line_selector) in line_selectors.iter().enumerate() {
+        output
+            .print_line_selector_header(line_selector, is_first)
+            .context("Failed to output header")?;
+        is_first = false;
+
+        let mut selected_line_nums = line_selector.iter_in_selection_order().peekable();
+        if let Some(&ruler_width) = rewriter.ruler_widths.get(i)
+            && let Some(&first_selected_line_num) = selected_line_nums.peek()
+        {
+            let first_line_num = next_line_num.unwrap_or_else(|| {
+                get_context_lines_endpoints(first_selected_line_num, args.before, 0, n_lines).0
+            });
+            output
+                .print_ruler(first_line_num, &ruler::format(ruler_width))
+                .context("Failed to output ruler")?;
+        }
+        while let Some(selected_line_num) = selected_line_nums.next() {
+            let (first_context_line, last_context_line) =
+                get_context_lines_endpoints(selected_line_num, args.before, args.after, n_lines);
+            let first_line_num = match next_line_num {
+                Some(next_line_num) => next_line_num,
+                None => {
+                    if args.mark_file_bounds && args.before > 0 && selected_line_num < args.before {
+                        output
+                            .print_file_boundary(&i18n::message("start-of-file", &[]))
+                            .context("Failed to output start of file")?;
+                    }
+                    first_context_line
+                }
+            };
+            print_context_lines(first_line_num..selected_line_num, rewriter, lines, output)?;
+            print_selected_line(selected_line_num, rewriter, lines, output)?;
+
+            let is_last_of_selector = selected_line_nums.peek().is_none();
+            let next_selected_line_num = match selected_line_nums.peek() {
+                Some(&line_num) => Some(line_num),
+                None => line_selectors[i + 1..]
+                    .iter()
+                    .find_map(|line_selector| line_selector.iter_in_selection_order().next()),
+            };
+            // lines selected in descending order are printed in separate groups
+            let continues_group = next_selected_line_num.is_some_and(|next_selected_line_num| {
+                next_selected_line_num > selected_line_num
+                    && next_selected_line_num.saturating_sub(args.before) <= last_context_line + 1
+            });
+            if continues_group {
+                next_line_num = Some(selected_line_num + 1);
+                continue;
+            }
+            next_line_num = None;
+            print_context_lines(
+                selected_line_num + 1..=last_context_line,
+                rewriter,
+                lines,
+                output,
+            )?;
+            if args.mark_file_bounds && args.after > 0 && selected_line_num + args.after >= n_lines
+            {
+                output
+                    .print_file_boundary(&i18n::message("end-of-file", &[]))
+                    .context("Failed to output end of file")?;
+            }
+            // the headers of decorated output already separate the groups of different selectors
+            if next_selected_line_num.is_some()
+                && (args.after != 0 || args.before != 0)
+                && !(is_last_of_selector && output.is_decorated())
+            {
+                output
+                    .print_group_separator(&args.group_separator)
+                    .context("Failed to output separator")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_context_lines(
+    context_line_nums: impl Iterator<Item = usize>,
+    rewriter: &LineRewriter,
+    lines: &mut impl LineSource,
+    output: &mut impl OutputWriter,
+) -> anyhow::Result<()> {
+    for line_num in context_line_nums {
+        let line = rewriter.rewrite_context(lines.get(line_num)?);
+        let line = Line::Context {
+            line_num,
+            line: &line,
+        };
+        output
+            .print_line(line)
+            .with_context(|| format!("Failed to output line {}", line_num + 1))?;
+    }
+    Ok(())
+}
+
+fn print_selected_line(
+    selected_line_num: usize,
+    rewriter: &LineRewriter,
+    lines: &mut impl LineSource,
+    output: &mut impl OutputWriter,
+) -> anyhow::Result<()> {
+    let line = lines.get(selected_line_num)?;
+    rewriter.check_utf8(selected_line_num, line)?;
+    let line = rewriter.rewrite(line);
+    let line = Line::Selected {
+        line_num: selected_line_num,
+        line: &line,
+    };
+    output
+        .print_line(line)
+        .with_context(|| format!("Failed to output line {}", selected_line_num + 1))?;
+    Ok(())
+}
+
+/// Parses a slice of `RawLineSelector`s into a slice of `LineSelector`
+pub(crate) fn parse_line_selectors(
+    raw_line_selectors: &[RawLineSelector],
+    n_lines: usize,
+    rng: &mut random::Rng,
+) -> anyhow::Result<Box<[LineSelector]>> {
+    raw_line_selectors
+        .iter()
+        .map(|&raw_line_selector| {
+            let parsed_line_selector =
+                ParsedLineSelector::from_raw_with_rng(raw_line_selector, n_lines, rng)
+                    .with_context(|| format!("Invalid line selector: {raw_line_selector}"))?;
+
+            Ok(LineSelector {
+                parsed: parsed_line_selector,
+                raw: raw_line_selector,
+            })
+        })
+        .collect()
+}
+
+/// Returns the number of threads to use when `--threads` isn't given.
+pub(crate) fn available_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Opens a file and bails if the file is a directory or empty
+pub(crate) fn open_file(path: &Path) -> anyhow::Result<File> {
+    let file = File::open(paths::for_opening(path))
+        .with_context(|| format!("Couldn't open file `{}`", paths::display(path)))?;
+
+    let metadata = file
+        .metadata()
+        .with_context(|| format!("Couldn't read file metadata of `{}`", paths::display(path)))?;
+
+    if !metadata.is_file() {
+        anyhow::bail!("`{}` is not a file", paths::display(path));
+    } else if metadata.len() == 0 {
+        anyhow::bail!(i18n::message(
+            "empty-file",
+            &[("path", paths::display(path).to_string().into())]
+        ));
+    }
+
+    Ok(file)
+}
+
+/// Fails if `file` is larger than `max_file_size` bytes (for `--max-file-size`).
+fn check_file_size(file: &File, path: &Path, max_file_size: u64) -> anyhow::Result<()> {
+    let len = file
+        .metadata()
+        .context("Failed to read file metadata")?
+        .len();
+    if len > max_file_size {
+        anyhow::bail!(
+            "`{}` is {}, which is more than the maximum file size of {} (use --force to read it \
+            anyway)",
+            paths::display(path),
+            size::format_size(len),
+            size::format_size(max_file_size)
+        );
+    }
+    Ok(())
+}
+
+/// Takes a shared advisory lock on `file`, which is released when `file` is closed. Waits for
+/// writers holding an exclusive lock to release it, telling the user about it first.
+fn lock_shared(file: &File, path: &Path) -> anyhow::Result<()> {
+    let lock_error = || format!("Couldn't lock file `{}`", paths::display(path));
+    match file.try_lock_shared() {
+        Ok(()) => Ok(()),
+        Err(TryLockError::WouldBlock) => {
+            eprintln!(
+                "Waiting for another process to unlock `{}`...",
+                paths::display(path)
+            );
+            file.lock_shared().with_context(lock_error)
+        }
+        Err(TryLockError::Error(err)) => Err(err).with_context(lock_error),
+    }
+}
+
+/// Picks a read buffer size that grows with the size of `file`. The default 8 KiB buffer of
+/// `BufReader` is a bottleneck when counting the lines of multi-GiB files.
+pub(crate) fn default_read_buffer_size(file: &File) -> anyhow::Result<usize> {
+    const KIB: u64 = 1 << 10;
+    const MIB: u64 = 1 << 20;
+    const GIB: u64 = 1 << 30;
+
+    let len = file
+        .metadata()
+        .context("Failed to read file metadata")?
+        .len();
+    let size = match len {
+        ..MIB => 8 * KIB,
+        MIB..GIB => 64 * KIB,
+        GIB.. => MIB,
+    };
+    Ok(size as usize)
+}
+
+/// Returns an error if the decompressed or transcoded content of `path` is empty, like for files
+/// that are empty to begin with.
+fn ensure_not_empty(content: &File, path: &Path) -> anyhow::Result<()> {
+    let len = content
+        .metadata()
+        .context("Failed to read file metadata")?
+        .len();
+    if len == 0 {
+        anyhow::bail!(i18n::message(
+            "empty-file",
+            &[("path", paths::display(path).to_string().into())]
+        ));
+    }
+    Ok(())
+}
+
+/// Guesses the type of content of `file` (e.g.: binary or UTF-8) by inspecting the first few
+/// bytes, then rewinds to the beginning of the file
+pub(crate) fn inspect_content(file: &mut BufReader<File>) -> anyhow::Result<ContentType> {
+    let mut first_few_bytes = [0; 64];
+    let n = file
+        .read(&mut first_few_bytes)
+        .context("Failed to read from file")?;
+    let content_type = content_inspector::inspect(&first_few_bytes[..n]);
+
+    // we read a small amount of bytes, so rewinding shouldn't be expensive due to caching
+    file.rewind().context("Failed to rewind file")?;
+
+    Ok(content_type)
+}
+
+/// Returns the lines printed for `line_selector` (i.e.: its selected lines and their context) as
+/// ranges in ascending order, merging the context of selected lines where it overlaps or touches,
+/// like printing does.
+///
+/// The context before and after each line of a range covers the lines skipped by its step when
+/// `before + after + 1 >= step`, in which case the whole range is merged at once.
+fn printed_line_ranges(
+    line_selector: &LineSelector,
+    before: usize,
+    after: usize,
+    n_lines: usize,
+) -> Vec<RangeInclusive<usize>> {
+    if let ParsedLineSelector::Range(start, end, step) = line_selector.parsed
+        && before.saturating_add(after).saturating_add(1) >= step.unsigned_abs()
+    {
+        // ranges are tightened, and ranges with negative steps select lines from `end` to `start`
+        let (first_line_num, last_line_num) = if step > 0 { (start, end) } else { (end, start) };
+        let (first_context_line, _) =
+            get_context_lines_endpoints(first_line_num, before, 0, n_lines);
+        let (_, last_context_line) = get_context_lines_endpoints(last_line_num, 0, after, n_lines);
+        return vec![first_context_line..=last_context_line];
+    }
+
+    let mut ranges: Vec<RangeInclusive<usize>> = Vec::new();
+    for selected_line_num in line_selector.iter() {
+        let (first_context_line, last_context_line) =
+            get_context_lines_endpoints(selected_line_num, before, after, n_lines);
+        match ranges.last_mut() {
+            Some(range) if first_context_line <= range.end().saturating_add(1) => {
+                *range = *range.start()..=last_context_line.max(*range.end());
+            }
+            _ => ranges.push(first_context_line..=last_context_line),
+        }
+    }
+    ranges
+}
+
+/// Returns the first and last context lines of `selected_line_num`, capped between 0 and
+/// n_lines - 1.
+pub(crate) fn get_context_lines_endpoints(
+    selected_line_num: usize,
+    before: usize,
+    after: usize,
+    n_lines: usize,
+) -> (usize, usize) {
+    debug_assert!(n_lines > 0); // ensures `n_lines - 1` doesn't panic/underflow
+    let first_context_line = selected_line_num.saturating_sub(before);
+    let last_context_line = selected_line_num.saturating_add(after).min(n_lines - 1);
+    (first_context_line, last_context_line)
+}
@@ -0,0 +1,567 @@
+use crate::escape::split_terminator;
+use crate::random::{Rng, reservoir_sample};
+use anyhow::Context;
+use regex::bytes::RegexSet;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek};
+use std::ops::RangeInclusive;
+use std::thread;
+
+/// Files smaller than this are counted on a single thread, since spawning threads for them costs
+/// more than it saves.
+pub(crate) const PARALLEL_THRESHOLD: u64 = 32 * 1024 * 1024;
+
+/// Size of the buffer each counting thread reads into.
+const CHUNK_BUF_SIZE: usize = 64 * 1024;
+
+/// Counts the number of lines, which end with `separator` (usually `\n`), in the file then
+/// rewinds to the beginning of the file.
+///
+/// Large files are split into `threads` byte ranges which are counted concurrently.
+pub(crate) fn count_lines(
+    file: &mut BufReader<File>,
+    threads: usize,
+    separator: u8,
+) -> anyhow::Result<usize> {
+    let len = file
+        .get_ref()
+        .metadata()
+        .context("Failed to read file metadata")?
+        .len();
+
+    if threads > 1 && len >= PARALLEL_THRESHOLD {
+        // positional reads don't move the file cursor, but rewind anyway to drop whatever the
+        // `BufReader` has buffered so far
+        file.rewind().context("Failed to rewind file")?;
+        return count_lines_in_parallel(file.get_ref(), len, threads, separator);
+    }
+
+    let mut n_lines = 0;
+    while file
+        .skip_until(separator)
+        .context("Failed to read from file")?
+        > 0
+    {
+        n_lines += 1;
+    }
+    file.rewind().context("Failed to rewind file")?;
+    Ok(n_lines)
+}
+
+/// How many line numbers of each kind of terminator are kept, to point at the lines that differ
+/// when line endings are mixed.
+const MAX_KEPT_LINE_NUMS: usize = 10;
+
+/// Line terminators tallied while counting lines.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct LineEndings {
+    pub(crate) lf: usize,
+    pub(crate) crlf: usize,
+    /// The (zero-based) numbers of the first lines ending with LF
+    lf_line_nums: Vec<usize>,
+    /// The (zero-based) numbers of the first lines ending with CRLF
+    crlf_line_nums: Vec<usize>,
+}
+
+impl LineEndings {
+    pub(crate) fn is_mixed(&self) -> bool {
+        self.lf > 0 && self.crlf > 0
+    }
+
+    /// Describes mixed line endings, pointing at the lines ending with the less common
+    /// terminator, e.g.: `mixed line endings (10 CRLF, 2 LF): lines 3, 7 end with LF`.
+    pub(crate) fn describe_mix(&self) -> String {
+        let lf = (self.lf, "LF", &self.lf_line_nums);
+        let crlf = (self.crlf, "CRLF", &self.crlf_line_nums);
+        let ((majority, majority_name, _), (minority, minority_name, line_nums)) =
+            if self.lf <= self.crlf {
+                (crlf, lf)
+            } else {
+                (lf, crlf)
+            };
+
+        let mut lines = line_nums
+            .iter()
+            .map(|line_num| (line_num + 1).to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if minority > line_nums.len() {
+            lines.push_str(&format!(" and {} more", minority - line_nums.len()));
+        }
+        let (lines, end) = if minority == 1 {
+            (format!("line {lines}"), "ends")
+        } else {
+            (format!("lines {lines}"), "end")
+        };
+        format!(
+            "mixed line endings ({majority} {majority_name}, {minority} {minority_name}): {lines} \
+            {end} with {minority_name}"
+        )
+    }
+
+    fn add(&mut self, crlf: bool) {
+        let line_num = self.lf + self.crlf;
+        let (count, line_nums) = if crlf {
+            (&mut self.crlf, &mut self.crlf_line_nums)
+        } else {
+            (&mut self.lf, &mut self.lf_line_nums)
+        };
+        *count += 1;
+        if line_nums.len() < MAX_KEPT_LINE_NUMS {
+            line_nums.push(line_num);
+        }
+    }
+}
+
+impl Display for LineEndings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.lf, self.crlf) {
+            (0, 0) => write!(f, "none"),
+            (_, 0) => write!(f, "LF"),
+            (0, _) => write!(f, "CRLF"),
+            (lf, crlf) => write!(f, "mixed ({lf} LF, {crlf} CRLF)"),
+        }
+    }
+}
+
+/// Counts the number of lines like [`count_lines`] while tallying their terminators, then rewinds
+/// to the beginning of the file.
+///
+/// Unlike [`count_lines`], this always runs on a single thread.
+pub(crate) fn count_lines_and_endings<R: BufRead + Seek>(
+    file: &mut R,
+) -> anyhow::Result<(usize, LineEndings)> {
+    let mut line_endings = LineEndings::default();
+    let mut last_byte = None;
+    loop {
+        let buf = file.fill_buf().context("Failed to read from file")?;
+        if buf.is_empty() {
+            break;
+        }
+        for (i, &byte) in buf.iter().enumerate() {
+            if byte != b'\n' {
+                continue;
+            }
+            let previous_byte = if i > 0 { Some(buf[i - 1]) } else { last_byte };
+            line_endings.add(previous_byte == Some(b'\r'));
+        }
+        last_byte = buf.last().copied();
+        let len = buf.len();
+        file.consume(len);
+    }
+    file.rewind().context("Failed to rewind file")?;
+
+    // the last line is counted even if it doesn't end with a new line
+    let mut n_lines = line_endings.lf + line_endings.crlf;
+    if last_byte.is_some_and(|byte| byte != b'\n') {
+        n_lines += 1;
+    }
+    Ok((n_lines, line_endings))
+}
+
+/// Counts the number of lines like [`count_lines`] while collecting the (one-based) numbers of
+/// the lines matching any of `patterns`, then rewinds to the beginning of the file. Lines are
+/// matched without their line ending.
+///
+/// Unlike [`count_lines`], this always runs on a single thread.
+pub(crate) fn count_lines_and_matches<R: BufRead + Seek>(
+    file: &mut R,
+    separator: u8,
+    patterns: &RegexSet,
+) -> anyhow::Result<(usize, Vec<usize>)> {
+    let mut n_lines = 0;
+    let mut matches = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if file
+            .read_until(separator, &mut buf)
+            .context("Failed to read from file")?
+            == 0
+        {
+            break;
+        }
+        n_lines += 1;
+        let (line, _) = split_terminator(&buf, separator);
+        if patterns.is_match(line) {
+            matches.push(n_lines);
+        }
+    }
+    file.rewind().context("Failed to rewind file")?;
+    Ok((n_lines, matches))
+}
+
+/// Counts the number of lines like [`count_lines`] while picking `k` of their (zero-based) numbers
+/// at random with [`reservoir_sample`], in the same pass, then rewinds to the beginning of the
+/// file. Only the picked line numbers are kept in memory.
+///
+/// Unlike [`count_lines`], this always runs on a single thread.
+pub(crate) fn count_lines_and_sample<R: BufRead + Seek>(
+    file: &mut R,
+    separator: u8,
+    k: usize,
+    rng: &mut Rng,
+) -> anyhow::Result<(usize, Vec<usize>)> {
+    let mut n_lines = 0;
+    let mut read_error = None;
+    let line_nums = std::iter::from_fn(|| match file.skip_until(separator) {
+        Ok(0) => None,
+        Ok(_) => {
+            n_lines += 1;
+            Some(n_lines - 1)
+        }
+        Err(err) => {
+            read_error = Some(err);
+            None
+        }
+    });
+    let line_nums = reservoir_sample(line_nums, k, rng);
+    if let Some(err) = read_error {
+        return Err(err).context("Failed to read from file");
+    }
+    file.rewind().context("Failed to rewind file")?;
+    Ok((n_lines, line_nums))
+}
+
+/// Counts the lines of the file like [`count_lines`], but stops at `max_lines` lines, then rewinds
+/// to the beginning of the file. Only the lines up to there are read.
+pub(crate) fn count_lines_up_to<R: BufRead + Seek>(
+    file: &mut R,
+    max_lines: usize,
+    separator: u8,
+) -> anyhow::Result<usize> {
+    let mut n_lines = 0;
+    while n_lines < max_lines
+        && file
+            .skip_until(separator)
+            .context("Failed to read from file")?
+            > 0
+    {
+        n_lines += 1;
+    }
+    file.rewind().context("Failed to rewind file")?;
+    Ok(n_lines)
+}
+
+/// Returns the (zero-based) number of the line holding the byte at `offset`, i.e.: the number of
+/// `separator`s before it, then rewinds to the beginning of the file.
+pub(crate) fn line_at_offset<R: BufRead + Seek>(
+    file: &mut R,
+    offset: u64,
+    separator: u8,
+) -> anyhow::Result<usize> {
+    let mut reader = file.by_ref().take(offset);
+    let mut n_separators = 0;
+    loop {
+        let buf = reader.fill_buf().context("Failed to read from file")?;
+        if buf.is_empty() {
+            break;
+        }
+        n_separators += buf.iter().filter(|&&byte| byte == separator).count();
+        let len = buf.len();
+        reader.consume(len);
+    }
+    file.rewind().context("Failed to rewind file")?;
+    Ok(n_separators)
+}
+
+/// Returns the byte offsets of the (zero-based) lines in `ranges`, which are sorted by their
+/// start, then rewinds to the beginning of the file.
+pub(crate) fn line_offsets<R: BufRead + Seek>(
+    file: &mut R,
+    ranges: &[RangeInclusive<usize>],
+    separator: u8,
+) -> anyhow::Result<HashMap<usize, u64>> {
+    let mut offsets = HashMap::new();
+    let mut line_num = 0;
+    let mut offset = 0;
+    for range in ranges {
+        // the lines of overlapping ranges before `line_num` are already found
+        for target in *range.start().max(&line_num)..=*range.end() {
+            while line_num < target {
+                offset += file
+                    .skip_until(separator)
+                    .context("Failed to read from file")? as u64;
+                line_num += 1;
+            }
+            offsets.insert(target, offset);
+        }
+    }
+    file.rewind().context("Failed to rewind file")?;
+    Ok(offsets)
+}
+
+/// Counts the lines of the first `len` bytes of `file` using `threads` threads.
+fn count_lines_in_parallel(
+    file: &File,
+    len: u64,
+    threads: usize,
+    separator: u8,
+) -> anyhow::Result<usize> {
+    if len == 0 {
+        return Ok(0);
+    }
+
+    let chunk_len = len.div_ceil(threads as u64);
+    let n_separators = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads as u64)
+            .map(|i| {
+                let start = (i * chunk_len).min(len);
+                let end = (start + chunk_len).min(len);
+                scope.spawn(move || count_separators_in_range(file, start, end, separator))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("counting threads don't panic"))
+            .sum::<anyhow::Result<usize>>()
+    })?;
+
+    // the last line is counted even if it doesn't end with a new line
+    let mut last_byte = [0];
+    read_exact_at(file, &mut last_byte, len - 1).context("Failed to read from file")?;
+    if last_byte[0] != separator {
+        return Ok(n_separators + 1);
+    }
+    Ok(n_separators)
+}
+
+/// Counts the occurrences of `separator` in the byte range `start..end` of `file`.
+fn count_separators_in_range(
+    file: &File,
+    start: u64,
+    end: u64,
+    separator: u8,
+) -> anyhow::Result<usize> {
+    let mut buf = vec![0; CHUNK_BUF_SIZE];
+    let mut n_separators = 0;
+    let mut offset = start;
+    while offset < end {
+        let len = (end - offset).min(CHUNK_BUF_SIZE as u64) as usize;
+        let buf = &mut buf[..len];
+        read_exact_at(file, buf, offset).context("Failed to read from file")?;
+        n_separators += buf.iter().filter(|&&byte| byte == separator).count();
+        offset += len as u64;
+    }
+    Ok(n_separators)
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn file_with_content(content: &[u8]) -> File {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(content).unwrap();
+        file
+    }
+
+    #[test]
+    fn parallel_count_matches_sequential_count() {
+        let inputs: [&[u8]; 6] = [
+            b"\n",
+            b"one",
+            b"one\n",
+            b"one\ntwo",
+            b"one\ntwo\nthree\n",
+            b"\n\n\nfour\n\nsix",
+        ];
+        for content in inputs {
+            let file = file_with_content(content);
+            let mut sequential = BufReader::new(file.try_clone().unwrap());
+            sequential.rewind().unwrap();
+            let expected = count_lines(&mut sequential, 1, b'\n').unwrap();
+
+            for threads in 1..=8 {
+                let actual =
+                    count_lines_in_parallel(&file, content.len() as u64, threads, b'\n').unwrap();
+                assert_eq!(actual, expected, "content: {content:?}, threads: {threads}");
+            }
+        }
+    }
+
+    #[test]
+    fn sampling_while_counting_matches_sampling_after() {
+        let content: String = (1..=100).map(|i| format!("{i}\n")).collect();
+        let mut file = BufReader::new(file_with_content(content.as_bytes()));
+        file.rewind().unwrap();
+        let (n_lines, line_nums) =
+            count_lines_and_sample(&mut file, b'\n', 5, &mut Rng::new(9)).unwrap();
+        assert_eq!(n_lines, 100);
+        assert_eq!(line_nums, reservoir_sample(0..100, 5, &mut Rng::new(9)));
+
+        let (n_lines, line_nums) =
+            count_lines_and_sample(&mut file, b'\n', usize::MAX, &mut Rng::new(9)).unwrap();
+        assert_eq!(n_lines, 100);
+        assert_eq!(line_nums, (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn count_lines_and_endings_matches_count_lines() {
+        for (content, lf, crlf) in [
+            ("", 0, 0),
+            ("one", 0, 0),
+            ("one\n", 1, 0),
+            ("one\r\n", 0, 1),
+            ("one\r\ntwo\nthree", 1, 1),
+            ("\r\n\r\n\n", 1, 2),
+            ("\r", 0, 0),
+        ] {
+            let mut expected = BufReader::new(file_with_content(content.as_bytes()));
+            expected.rewind().unwrap();
+            let expected = count_lines(&mut expected, 1, b'\n').unwrap();
+
+            let mut cursor = std::io::Cursor::new(content);
+            let (n_lines, line_endings) = count_lines_and_endings(&mut cursor).unwrap();
+            assert_eq!(n_lines, expected, "content: {content:?}");
+            assert_eq!(
+                (line_endings.lf, line_endings.crlf),
+                (lf, crlf),
+                "content: {content:?}"
+            );
+            assert_eq!(cursor.position(), 0);
+        }
+    }
+
+    #[test]
+    fn crlf_split_across_buffer_boundary() {
+        let content = "one\r\ntwo\r\n";
+        // a buffer of 4 bytes splits the first `\r\n`
+        let mut reader = BufReader::with_capacity(4, std::io::Cursor::new(content));
+        let (n_lines, line_endings) = count_lines_and_endings(&mut reader).unwrap();
+        assert_eq!(n_lines, 2);
+        assert_eq!((line_endings.lf, line_endings.crlf), (0, 2));
+    }
+
+    #[test]
+    fn display_line_endings() {
+        let display = |lf, crlf| {
+            LineEndings {
+                lf,
+                crlf,
+                ..LineEndings::default()
+            }
+            .to_string()
+        };
+        assert_eq!(display(0, 0), "none");
+        assert_eq!(display(3, 0), "LF");
+        assert_eq!(display(0, 3), "CRLF");
+        assert_eq!(display(1, 2), "mixed (1 LF, 2 CRLF)");
+    }
+
+    #[test]
+    fn describe_mixed_line_endings() {
+        let describe = |content: &str| {
+            let mut cursor = std::io::Cursor::new(content);
+            let (_, line_endings) = count_lines_and_endings(&mut cursor).unwrap();
+            assert!(line_endings.is_mixed());
+            line_endings.describe_mix()
+        };
+        assert_eq!(
+            describe("a\r\nb\nc\r\nd\r\n"),
+            "mixed line endings (3 CRLF, 1 LF): line 2 ends with LF"
+        );
+        assert_eq!(
+            describe(&("a\r\n".repeat(20) + &"a\n".repeat(12))),
+            "mixed line endings (20 CRLF, 12 LF): lines 21, 22, 23, 24, 25, 26, 27, 28, 29, 30 \
+            and 2 more end with LF"
+        );
+    }
+
+    #[test]
+    fn count_lines_and_matches_matches_lines_without_their_ending() {
+        let patterns = RegexSet::new(["^two$", "four"]).unwrap();
+        let mut cursor = std::io::Cursor::new("one\ntwo\r\nthree\nfour");
+        let (n_lines, matches) = count_lines_and_matches(&mut cursor, b'\n', &patterns).unwrap();
+        assert_eq!(n_lines, 4);
+        assert_eq!(matches, [2, 4]);
+        assert_eq!(cursor.position(), 0);
+    }
+
+    #[test]
+    fn line_at_offset_counts_preceding_separators() {
+        let content = "one\ntwo\nthree\n";
+        for (offset, expected) in [(0, 0), (3, 0), (4, 1), (7, 1), (8, 2), (13, 2)] {
+            let mut cursor = std::io::Cursor::new(content);
+            assert_eq!(
+                line_at_offset(&mut cursor, offset, b'\n').unwrap(),
+                expected,
+                "offset: {offset}"
+            );
+            assert_eq!(cursor.position(), 0);
+        }
+    }
+
+    #[test]
+    fn count_lines_up_to_stops_early() {
+        for (content, max_lines, expected) in [
+            ("", 2, 0),
+            ("one\ntwo\nthree", 2, 2),
+            ("one\ntwo\nthree", 3, 3),
+            ("one\ntwo\nthree\n", 5, 3),
+            ("one\n", 0, 0),
+        ] {
+            let mut cursor = std::io::Cursor::new(content);
+            assert_eq!(
+                count_lines_up_to(&mut cursor, max_lines, b'\n').unwrap(),
+                expected,
+                "content: {content:?}, max_lines: {max_lines}"
+            );
+            assert_eq!(cursor.position(), 0);
+        }
+    }
+
+    #[test]
+    fn parallel_count_of_empty_file() {
+        let file = file_with_content(b"");
+        assert_eq!(count_lines_in_parallel(&file, 0, 4, b'\n').unwrap(), 0);
+    }
+
+    #[test]
+    fn nul_separated_records() {
+        let content = b"one\ntwo\0three\0four";
+        let mut sequential = BufReader::new(file_with_content(content));
+        sequential.rewind().unwrap();
+        assert_eq!(count_lines(&mut sequential, 1, b'\0').unwrap(), 3);
+
+        let file = file_with_content(content);
+        for threads in 1..=4 {
+            let n_lines = count_lines_in_parallel(&file, content.len() as u64, threads, b'\0');
+            assert_eq!(n_lines.unwrap(), 3);
+        }
+    }
+
+    #[test]
+    fn chunks_larger_than_buffer() {
+        let content = "line\n".repeat(CHUNK_BUF_SIZE);
+        let file = file_with_content(content.as_bytes());
+        let n_lines = count_lines_in_parallel(&file, content.len() as u64, 3, b'\n').unwrap();
+        assert_eq!(n_lines, CHUNK_BUF_SIZE);
+    }
+}
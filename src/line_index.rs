@@ -0,0 +1,287 @@
+use crate::LineSource;
+use anyhow::Context;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Identifies index files, and the version of their format.
+const MAGIC: &[u8; 8] = b"LINEIDX1";
+
+/// Size of the header of index files: the magic, the size and the modification time of the
+/// file, the separator, and the number of lines.
+const HEADER_LEN: usize = MAGIC.len() + 8 + 16 + 1 + 8;
+
+/// The offset of every `STRIDE`-th line is kept in the index.
+const STRIDE: usize = 1024;
+
+/// The byte offsets of every `STRIDE`-th line of a file, for `--index`. The index is stored next
+/// to the file, so that later runs on the same file can skip counting its lines and seek close to
+/// the selected lines.
+///
+/// The index is tied to the size and the modification time of the file, and to the separator of
+/// its lines, and is rebuilt when any of them changes.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct LineIndex {
+    len: u64,
+    /// Modification time of the file, in nanoseconds since the Unix epoch
+    modified: u128,
+    separator: u8,
+    pub(crate) n_lines: usize,
+    offsets: Vec<u64>,
+}
+
+/// Returns the path of the index of the file at `path`, e.g.: `app.log.line-idx` for `app.log`.
+pub(crate) fn index_path(path: &Path) -> PathBuf {
+    let mut index_path = OsString::from(path.as_os_str());
+    index_path.push(".line-idx");
+    PathBuf::from(index_path)
+}
+
+/// Loads the index of the file at `path` if it's up to date, or builds it from `file` and stores
+/// it otherwise. Failing to store the index is only reported as a warning, since the index built
+/// for this run is still used.
+pub(crate) fn load_or_build(
+    path: &Path,
+    file: &mut BufReader<File>,
+    separator: u8,
+) -> anyhow::Result<LineIndex> {
+    let metadata = file
+        .get_ref()
+        .metadata()
+        .context("Failed to read file metadata")?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_nanos());
+
+    let index_path = index_path(path);
+    if let Some(index) = fs::read(&index_path)
+        .ok()
+        .and_then(|bytes| LineIndex::decode(&bytes))
+        && index.len == metadata.len()
+        && index.modified == modified
+        && index.separator == separator
+    {
+        return Ok(index);
+    }
+
+    let index = LineIndex::build(file, separator, modified)?;
+    // the index is written aside then renamed, so other runs never read a partial index
+    let mut tmp_path = index_path.clone().into_os_string();
+    tmp_path.push(".tmp");
+    if let Err(err) =
+        fs::write(&tmp_path, index.encode()).and_then(|()| fs::rename(&tmp_path, &index_path))
+    {
+        let _ = fs::remove_file(&tmp_path);
+        eprintln!(
+            "Warning: Failed to store the line index at `{}`: {err}",
+            index_path.display()
+        );
+    }
+    Ok(index)
+}
+
+impl LineIndex {
+    /// Reads `file` to find the offsets of its lines, then rewinds to the beginning of the file.
+    fn build(file: &mut BufReader<File>, separator: u8, modified: u128) -> anyhow::Result<Self> {
+        let mut offsets = vec![0];
+        let mut len = 0;
+        let mut n_separators = 0;
+        let mut last_byte = None;
+        loop {
+            let buf = file.fill_buf().context("Failed to read from file")?;
+            let Some(&last) = buf.last() else {
+                break;
+            };
+            for i in memchr::memchr_iter(separator, buf) {
+                n_separators += 1;
+                if n_separators % STRIDE == 0 {
+                    offsets.push(len + i as u64 + 1);
+                }
+            }
+            last_byte = Some(last);
+            len += buf.len() as u64;
+            let consumed = buf.len();
+            file.consume(consumed);
+        }
+        file.rewind().context("Failed to rewind file")?;
+
+        // the last line may lack its line ending
+        let n_lines = n_separators + usize::from(last_byte.is_some_and(|last| last != separator));
+        // a separator at the very end of the file doesn't start a new line
+        offsets.truncate(n_lines.div_ceil(STRIDE));
+        Ok(Self {
+            len,
+            modified,
+            separator,
+            n_lines,
+            offsets,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + 8 * self.offsets.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.len.to_le_bytes());
+        bytes.extend_from_slice(&self.modified.to_le_bytes());
+        bytes.push(self.separator);
+        bytes.extend_from_slice(&(self.n_lines as u64).to_le_bytes());
+        for offset in &self.offsets {
+            bytes.extend_from_slice(&offset.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decodes an index encoded by [`LineIndex::encode`]. Returns `None` if `bytes` isn't a valid
+    /// index, e.g.: if it was written by another version.
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let (header, offsets) = bytes.split_at_checked(HEADER_LEN)?;
+        let (magic, header) = header.split_at(MAGIC.len());
+        let (len, header) = header.split_at(8);
+        let (modified, header) = header.split_at(16);
+        let (&separator, n_lines) = header.split_first()?;
+        if magic != MAGIC {
+            return None;
+        }
+        let n_lines = usize::try_from(u64::from_le_bytes(n_lines.try_into().ok()?)).ok()?;
+        let (offsets, rest) = offsets.as_chunks::<8>();
+        if !rest.is_empty() || offsets.len() != n_lines.div_ceil(STRIDE) {
+            return None;
+        }
+        Some(Self {
+            len: u64::from_le_bytes(len.try_into().ok()?),
+            modified: u128::from_le_bytes(modified.try_into().ok()?),
+            separator,
+            n_lines,
+            offsets: offsets
+                .iter()
+                .map(|&offset| u64::from_le_bytes(offset))
+                .collect(),
+        })
+    }
+}
+
+/// Lines read by seeking to the closest indexed line before them, then skipping the lines in
+/// between. Lines can be read in any order.
+pub(crate) struct IndexedLines {
+    reader: BufReader<File>,
+    index: LineIndex,
+    /// The (zero-based) number of the line the reader is at
+    line_num: usize,
+    buf: Vec<u8>,
+}
+
+impl IndexedLines {
+    pub(crate) fn new(reader: BufReader<File>, index: LineIndex) -> Self {
+        Self {
+            reader,
+            index,
+            line_num: 0,
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl LineSource for IndexedLines {
+    fn get(&mut self, line_num: usize) -> anyhow::Result<&[u8]> {
+        let indexed_line_num = line_num / STRIDE * STRIDE;
+        // reading on is cheaper than seeking unless an indexed line is closer
+        if line_num < self.line_num || indexed_line_num > self.line_num {
+            self.reader
+                .seek(SeekFrom::Start(self.index.offsets[line_num / STRIDE]))
+                .with_context(|| format!("Failed to seek to line number {}", line_num + 1))?;
+            self.line_num = indexed_line_num;
+        }
+        let separator = self.index.separator;
+        while self.line_num < line_num {
+            let n = self
+                .reader
+                .skip_until(separator)
+                .with_context(|| format!("Failed to read line number {}", line_num + 1))?;
+            if n == 0 {
+                break;
+            }
+            self.line_num += 1;
+        }
+        self.buf.clear();
+        self.reader
+            .read_until(separator, &mut self.buf)
+            .with_context(|| format!("Failed to read line number {}", line_num + 1))?;
+        self.line_num += 1;
+        Ok(&self.buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn file_with_content(content: &[u8]) -> BufReader<File> {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(content).unwrap();
+        let mut file = BufReader::new(file);
+        file.rewind().unwrap();
+        file
+    }
+
+    fn numbered_lines(n_lines: usize) -> String {
+        (0..n_lines)
+            .map(|line_num| format!("{line_num}\n"))
+            .collect()
+    }
+
+    #[test]
+    fn build() {
+        for (content, n_lines, n_offsets) in [
+            (String::new(), 0, 0),
+            ("one".to_owned(), 1, 1),
+            (numbered_lines(STRIDE), STRIDE, 1),
+            (numbered_lines(STRIDE) + "last", STRIDE + 1, 2),
+            (numbered_lines(3 * STRIDE + 7), 3 * STRIDE + 7, 4),
+        ] {
+            let mut file = file_with_content(content.as_bytes());
+            let index = LineIndex::build(&mut file, b'\n', 0).unwrap();
+            assert_eq!(index.n_lines, n_lines);
+            assert_eq!(index.offsets.len(), n_offsets);
+            assert_eq!(index.len, content.len() as u64);
+            assert_eq!(file.stream_position().unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let content = numbered_lines(2 * STRIDE + 1);
+        let index =
+            LineIndex::build(&mut file_with_content(content.as_bytes()), b'\n', 42).unwrap();
+        let bytes = index.encode();
+        assert_eq!(LineIndex::decode(&bytes), Some(index));
+        assert_eq!(LineIndex::decode(&bytes[..bytes.len() - 8]), None);
+        assert_eq!(LineIndex::decode(b"LINEIDX0"), None);
+    }
+
+    #[test]
+    fn lines_in_any_order() {
+        let content = numbered_lines(3 * STRIDE);
+        let mut file = file_with_content(content.as_bytes());
+        let index = LineIndex::build(&mut file, b'\n', 0).unwrap();
+        let mut lines = IndexedLines::new(file, index);
+        for line_num in [5, 6, 2 * STRIDE + 3, STRIDE, STRIDE - 1, 0, 3 * STRIDE - 1] {
+            assert_eq!(
+                lines.get(line_num).unwrap(),
+                format!("{line_num}\n").as_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn index_path_appends_extension() {
+        assert_eq!(
+            index_path(Path::new("logs/app.log")),
+            Path::new("logs/app.log.line-idx")
+        );
+    }
+}
@@ -1,4 +1,12 @@
-use std::io::BufRead;
+use std::io::{self, BufRead, ErrorKind};
+use std::thread;
+use std::time::Duration;
+
+/// Number of times a read that failed with a transient error is retried before giving up.
+const MAX_RETRIES: u32 = 5;
+
+/// Delay before the first retry, which is doubled before each of the next ones.
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(10);
 
 /// Efficient line-by-line reader that can skip to specific line numbers.
 ///
@@ -14,6 +22,7 @@ use std::io::BufRead;
 /// # Examples
 ///
 /// ```rust,no_run
+/// use line_rs::LineReader;
 /// use std::io::BufReader;
 /// use std::fs::File;
 ///
@@ -23,21 +32,60 @@ use std::io::BufRead;
 /// let mut buffer = Vec::new();
 /// reader.read_specific_line(&mut buffer, 42).unwrap(); // Read line 43 (zero-based indexing)
 /// ```
-pub(crate) struct LineReader<R> {
+///
+/// # Retries
+///
+/// Reads that fail with a transient error, which network filesystems (e.g.: NFS or SMB) report
+/// now and then, are retried a few times with an exponential backoff. Since `BufRead` consumes
+/// whatever it read before failing, a retry picks up right where the failed read stopped.
+pub struct LineReader<R> {
     reader: R,
     current_line: usize,
+    max_retries: u32,
+    separator: u8,
 }
 
 impl<R: BufRead> LineReader<R> {
-    pub(crate) fn new(reader: R) -> Self {
+    pub fn new(reader: R) -> Self {
         Self {
             reader,
             current_line: 0,
+            max_retries: MAX_RETRIES,
+            separator: b'\n',
+        }
+    }
+
+    /// Sets the byte that ends lines, which is `\n` by default (e.g.: `\0` for `--null-data`).
+    pub fn with_separator(mut self, separator: u8) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// Turns the retries of reads that failed with a transient error on or off.
+    pub fn with_retries(mut self, retry: bool) -> Self {
+        self.max_retries = if retry { MAX_RETRIES } else { 0 };
+        self
+    }
+
+    /// Runs `read` on the underlying reader, retrying it if it fails with a transient error.
+    fn retry<T>(&mut self, mut read: impl FnMut(&mut R) -> io::Result<T>) -> io::Result<T> {
+        let mut delay = FIRST_RETRY_DELAY;
+        let mut retries = 0;
+        loop {
+            match read(&mut self.reader) {
+                Err(err) if retries < self.max_retries && is_transient(&err) => {
+                    thread::sleep(delay);
+                    delay *= 2;
+                    retries += 1;
+                }
+                result => return result,
+            }
         }
     }
 
     fn read_next_line(&mut self, buf: &mut Vec<u8>) -> anyhow::Result<()> {
-        let n = self.reader.read_until(b'\n', buf)?;
+        let separator = self.separator;
+        let n = self.retry(|reader| reader.read_until(separator, buf))?;
         if n != 0 {
             self.current_line += 1;
         }
@@ -47,7 +95,8 @@ impl<R: BufRead> LineReader<R> {
     /// Skips `n` lines.
     fn skip_lines(&mut self, n: usize) -> anyhow::Result<()> {
         let mut i = 0;
-        while i < n && self.reader.skip_until(b'\n')? > 0 {
+        let separator = self.separator;
+        while i < n && self.retry(|reader| reader.skip_until(separator))? > 0 {
             i += 1;
         }
         self.current_line += i;
@@ -66,11 +115,7 @@ impl<R: BufRead> LineReader<R> {
     /// result in an undefined behaviour. Similarly, reading the same line twice is an undefined
     /// behaviour as well. Instead, you should read lines in an incremental manner, e.g.: read line
     /// 3 then 5.
-    pub(crate) fn read_specific_line(
-        &mut self,
-        buf: &mut Vec<u8>,
-        line_num: usize,
-    ) -> anyhow::Result<()> {
+    pub fn read_specific_line(&mut self, buf: &mut Vec<u8>, line_num: usize) -> anyhow::Result<()> {
         debug_assert!(
             line_num >= self.current_line,
             "current line is {} (one-based), can't read previous line {} (one-based). Reads must happen incrementally, see \
@@ -85,6 +130,18 @@ impl<R: BufRead> LineReader<R> {
     }
 }
 
+/// Returns true if `err` may go away if the read is tried again.
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            | ErrorKind::ResourceBusy
+            | ErrorKind::StaleNetworkFileHandle
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -307,6 +364,88 @@ mod tests {
         }
     }
 
+    mod retry {
+        use super::*;
+
+        /// Fails with an error of `kind` for the first `n_failures` reads.
+        struct FlakyReader {
+            inner: Cursor<&'static str>,
+            kind: ErrorKind,
+            n_failures: usize,
+        }
+
+        impl Read for FlakyReader {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.n_failures > 0 {
+                    self.n_failures -= 1;
+                    return Err(io::Error::from(self.kind));
+                }
+                self.inner.read(buf)
+            }
+        }
+
+        fn flaky_line_reader(
+            kind: ErrorKind,
+            n_failures: usize,
+        ) -> LineReader<BufReader<FlakyReader>> {
+            let reader = FlakyReader {
+                inner: Cursor::new("one\ntwo\n"),
+                kind,
+                n_failures,
+            };
+            // a one-byte buffer makes every byte a separate read
+            LineReader::new(BufReader::with_capacity(1, reader))
+        }
+
+        #[test]
+        fn transient_errors_are_retried() {
+            let mut line_reader = flaky_line_reader(ErrorKind::WouldBlock, 2);
+            let mut buf = Vec::new();
+            line_reader.read_specific_line(&mut buf, 1).unwrap();
+            assert_eq!(buf, b"two\n");
+
+            let mut line_reader = flaky_line_reader(ErrorKind::StaleNetworkFileHandle, 1);
+            let mut buf = Vec::new();
+            line_reader.read_specific_line(&mut buf, 0).unwrap();
+            assert_eq!(buf, b"one\n");
+        }
+
+        #[test]
+        fn retries_are_bounded() {
+            let mut line_reader = flaky_line_reader(ErrorKind::TimedOut, MAX_RETRIES as usize + 1);
+            let mut buf = Vec::new();
+            assert!(line_reader.read_specific_line(&mut buf, 0).is_err());
+        }
+
+        #[test]
+        fn other_errors_are_not_retried() {
+            let mut line_reader = flaky_line_reader(ErrorKind::PermissionDenied, 1);
+            let mut buf = Vec::new();
+            assert!(line_reader.read_specific_line(&mut buf, 0).is_err());
+        }
+
+        #[test]
+        fn retries_can_be_turned_off() {
+            let mut line_reader = flaky_line_reader(ErrorKind::WouldBlock, 1).with_retries(false);
+            let mut buf = Vec::new();
+            assert!(line_reader.read_specific_line(&mut buf, 0).is_err());
+        }
+    }
+
+    #[test]
+    fn nul_separated_records() {
+        let cursor = Cursor::new("one\ntwo\0three\0four");
+        let mut line_reader = LineReader::new(cursor).with_separator(b'\0');
+
+        let mut buf = Vec::new();
+        line_reader.read_specific_line(&mut buf, 0).unwrap();
+        assert_eq!(buf, b"one\ntwo\0");
+        buf.clear();
+
+        line_reader.read_specific_line(&mut buf, 2).unwrap();
+        assert_eq!(buf, b"four");
+    }
+
     mod read_specific_line {
         use super::*;
 
@@ -1,5 +1,13 @@
+use crate::i18n;
+use crate::random::{self, Rng};
 use anyhow::Context;
 use std::fmt::{Debug, Display};
+use std::io::BufRead;
+use std::iter::{Copied, Rev, StepBy};
+use std::num::NonZeroUsize;
+use std::ops::RangeInclusive;
+use std::slice;
+use std::str::FromStr;
 
 pub(crate) struct LineSelector {
     pub(crate) parsed: ParsedLineSelector,
@@ -13,17 +21,156 @@ impl LineSelector {
     /// `4:8:2` and `8:4:-2` will both produce the values `4, 6, 8` in this order.
     pub(crate) fn iter(&self) -> impl Iterator<Item = usize> {
         match self.parsed {
-            ParsedLineSelector::Single(line_num) => (line_num..=line_num).step_by(1),
+            ParsedLineSelector::Single(line_num) => {
+                LineNums::Ascending((line_num..=line_num).step_by(1))
+            }
             ParsedLineSelector::Range(start, end, step) => {
                 let line_nums = if step > 0 { start..=end } else { end..=start };
-                line_nums.step_by(step.unsigned_abs())
+                LineNums::Ascending(line_nums.step_by(step.unsigned_abs()))
+            }
+            ParsedLineSelector::Random(ref line_nums) => {
+                LineNums::Listed(line_nums.iter().copied())
             }
         }
     }
+
+    /// Returns how many lines the selector selects, without iterating over them.
+    pub(crate) fn n_selected(&self) -> usize {
+        match self.parsed {
+            ParsedLineSelector::Single(_) => 1,
+            // ranges are tightened, so both ends are selected
+            ParsedLineSelector::Range(start, end, step) => {
+                start.abs_diff(end) / step.unsigned_abs() + 1
+            }
+            ParsedLineSelector::Random(ref line_nums) => line_nums.len(),
+        }
+    }
+
+    /// Returns the smallest line number of the selector, or `None` if it selects no line.
+    pub(crate) fn min_line_num(&self) -> Option<usize> {
+        match self.parsed {
+            ParsedLineSelector::Single(line_num) => Some(line_num),
+            ParsedLineSelector::Range(start, end, step) => Some(if step > 0 { start } else { end }),
+            ParsedLineSelector::Random(ref line_nums) => line_nums.first().copied(),
+        }
+    }
+
+    /// Returns the largest line number of the selector, or `None` if it selects no line.
+    pub(crate) fn max_line_num(&self) -> Option<usize> {
+        match self.parsed {
+            ParsedLineSelector::Single(line_num) => Some(line_num),
+            // ranges are tightened, so the end of the range is the last line it selects
+            ParsedLineSelector::Range(start, end, step) => Some(if step > 0 { end } else { start }),
+            ParsedLineSelector::Random(ref line_nums) => line_nums.last().copied(),
+        }
+    }
+
+    /// Returns an iterator over the parsed line selector in selection order.
+    ///
+    /// Unlike [`LineSelector::iter`], negative steps yield the items in descending order. That is,
+    /// `8:4:-2` produces the values `8, 6, 4` in this order.
+    pub(crate) fn iter_in_selection_order(&self) -> impl Iterator<Item = usize> {
+        match self.parsed {
+            ParsedLineSelector::Single(line_num) => {
+                LineNums::Ascending((line_num..=line_num).step_by(1))
+            }
+            ParsedLineSelector::Range(start, end, step) if step > 0 => {
+                LineNums::Ascending((start..=end).step_by(step.unsigned_abs()))
+            }
+            ParsedLineSelector::Range(start, end, step) => {
+                // the end is tightened, so stepping down from the start lands on it
+                LineNums::Descending((end..=start).rev().step_by(step.unsigned_abs()))
+            }
+            // randomly picked lines are selected in ascending order
+            ParsedLineSelector::Random(ref line_nums) => {
+                LineNums::Listed(line_nums.iter().copied())
+            }
+        }
+    }
+}
+
+/// The line numbers of a [`LineSelector`], in one of the orders its kind can yield them.
+enum LineNums<'a> {
+    Ascending(StepBy<RangeInclusive<usize>>),
+    Descending(StepBy<Rev<RangeInclusive<usize>>>),
+    Listed(Copied<slice::Iter<'a, usize>>),
+}
+
+impl Iterator for LineNums<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        match self {
+            LineNums::Ascending(line_nums) => line_nums.next(),
+            LineNums::Descending(line_nums) => line_nums.next(),
+            LineNums::Listed(line_nums) => line_nums.next(),
+        }
+    }
+}
+
+/// Returns the lines of a file with `n_lines` lines that none of `line_selectors` selects, as
+/// ranges of consecutive lines, for `--invert`.
+pub(crate) fn invert(line_selectors: &[LineSelector], n_lines: usize) -> Vec<RawLineSelector> {
+    runs_where(line_selectors, n_lines, false)
+}
+
+/// Returns the lines of a file with `n_lines` lines that any of `line_selectors` selects, once
+/// each and in ascending order, as ranges of consecutive lines, for `--unique`.
+pub(crate) fn unique(line_selectors: &[LineSelector], n_lines: usize) -> Vec<RawLineSelector> {
+    runs_where(line_selectors, n_lines, true)
+}
+
+/// Groups the lines whose selection by any of `line_selectors` is `is_selected` into ranges.
+///
+/// The selected lines are merged range by range instead of being marked line by line, so only the
+/// lines of stepped ranges and random selectors are looked at one at a time, and the lines that
+/// aren't selected are the gaps between the merged ranges.
+fn runs_where(
+    line_selectors: &[LineSelector],
+    n_lines: usize,
+    is_selected: bool,
+) -> Vec<RawLineSelector> {
+    let mut ranges = Vec::new();
+    for line_selector in line_selectors {
+        match line_selector.parsed {
+            ParsedLineSelector::Range(start, end, step) if step.unsigned_abs() == 1 => {
+                ranges.push((start.min(end), start.max(end)));
+            }
+            _ => ranges.extend(line_selector.iter().map(|line_num| (line_num, line_num))),
+        }
+    }
+    ranges.sort_unstable();
+    let mut selected: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match selected.last_mut() {
+            Some((_, last)) if start <= *last + 1 => *last = (*last).max(end),
+            _ => selected.push((start, end)),
+        }
+    }
+    let runs = if is_selected {
+        selected
+    } else {
+        let mut not_selected = Vec::with_capacity(selected.len() + 1);
+        let mut next = 0;
+        for (start, end) in selected {
+            if next < start {
+                not_selected.push((next, start - 1));
+            }
+            next = end + 1;
+        }
+        if next < n_lines {
+            not_selected.push((next, n_lines - 1));
+        }
+        not_selected
+    };
+    runs.into_iter()
+        .map(|(first, last)| RawLineSelector::run(first + 1, last + 1))
+        .collect()
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub(crate) enum ParsedLineSelector {
+#[non_exhaustive]
+pub enum ParsedLineSelector {
     /// Single line number (zero-based)
     Single(usize),
 
@@ -35,8 +182,30 @@ pub(crate) enum ParsedLineSelector {
     /// `Range(1, 5, 2)` represents the lines 1, 3, and 5.
     /// `Range(8, 2, -3)` represents the lines 8, 5, and 2.
     Range(usize, usize, isize),
+
+    /// Randomly picked line numbers (zero-based), in ascending order
+    Random(Vec<usize>),
+}
+
+/// The error of a selector reaching past the end of the input, which `line` tells apart from
+/// other errors by its exit code.
+#[derive(Debug)]
+pub(crate) struct LineOutOfRange {
+    pub(crate) line: isize,
+    pub(crate) n_lines: usize,
+}
+
+impl Display for LineOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&i18n::message(
+            "line-out-of-range",
+            &[("line", self.line.into()), ("count", self.n_lines.into())],
+        ))
+    }
 }
 
+impl std::error::Error for LineOutOfRange {}
+
 impl ParsedLineSelector {
     /// Parses `raw` as a zero-based line number, normalizing negative line numbers and
     /// unbounded ranges.
@@ -56,10 +225,28 @@ impl ParsedLineSelector {
     /// 1. `raw` contains a zero (`raw` is one-based so it can't be zero)
     /// 2. `raw` contains a number that's beyond the limits of the file (i.e.: not between -n_lines and n_lines)
     /// 3. `raw` is a range and the start is larger than the end (e.g.: `5:3` or `3:5:-1`)
-    pub(crate) fn from_raw(raw: RawLineSelector, n_lines: usize) -> anyhow::Result<Self> {
+    ///
+    /// `seed` seeds the random picks of random selectors (e.g.: `rand(100:2000, 5)`), so that
+    /// they pick the same lines every time, like `--seed`.
+    pub fn from_raw(
+        raw: RawLineSelector,
+        n_lines: usize,
+        seed: Option<u64>,
+    ) -> anyhow::Result<Self> {
+        let mut rng = seed.map_or_else(Rng::from_entropy, Rng::new);
+        Self::from_raw_with_rng(raw, n_lines, &mut rng)
+    }
+
+    /// Like [`Self::from_raw`], with the lines of random selectors picked by `rng`, which the
+    /// selectors of a command line share so that one `--seed` picks different lines for each.
+    pub(crate) fn from_raw_with_rng(
+        raw: RawLineSelector,
+        n_lines: usize,
+        rng: &mut Rng,
+    ) -> anyhow::Result<Self> {
         let to_positive_one_based = |num: isize| {
             if num.unsigned_abs() > n_lines {
-                anyhow::bail!("Line {num} is out of range (input has {n_lines} line(s) only)");
+                return Err(LineOutOfRange { line: num, n_lines });
             }
 
             let num = if num < 0 {
@@ -91,6 +278,31 @@ impl ParsedLineSelector {
                     Ok(Self::Range(start, end, 1))
                 }
             }
+            RawLineSelector::RangeWithCount(start, count) => {
+                let start_one_based = start;
+                let start = to_positive_one_based(start)?;
+                let Some(end) = start.checked_add(count - 1).filter(|&end| end < n_lines) else {
+                    let end = start_one_based.saturating_add_unsigned(count - 1);
+                    return Err(LineOutOfRange { line: end, n_lines }.into());
+                };
+                if start == end {
+                    Ok(Self::Single(start))
+                } else {
+                    Ok(Self::Range(start, end, 1))
+                }
+            }
+            RawLineSelector::Random(start, end, count) => {
+                let start = start.map(to_positive_one_based).unwrap_or(Ok(0))?;
+                let end = end.map(to_positive_one_based).unwrap_or(Ok(n_lines - 1))?;
+
+                if start > end {
+                    anyhow::bail!("The start of the range can't be more than its end");
+                }
+
+                let mut line_nums = random::reservoir_sample(start..=end, count, rng);
+                line_nums.sort_unstable();
+                Ok(Self::Random(line_nums))
+            }
             RawLineSelector::RangeWithStep(start, end, step) => {
                 let start = start.map(to_positive_one_based).unwrap_or(Ok(0))?;
                 let end = end.map(to_positive_one_based).unwrap_or(Ok(n_lines - 1))?;
@@ -136,8 +348,10 @@ impl ParsedLineSelector {
 /// `-4` is represented as Single(-4)
 /// `:5` is represented as Range(None, Some(5))
 /// `3:7:2` is represented as RangeWithStep(Some(3), Some(7), Some(2))
+/// `rand(100:, 5)` is represented as Random(Some(100), None, 5)
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub(crate) enum RawLineSelector {
+#[non_exhaustive]
+pub enum RawLineSelector {
     /// Single line number (1-based)
     Single(isize),
 
@@ -146,9 +360,106 @@ pub(crate) enum RawLineSelector {
 
     /// Range with step (1-based, inclusive)
     RangeWithStep(Option<isize>, Option<isize>, Option<isize>),
+
+    /// Number of lines to pick at random from a range with optional bounds (1-based, inclusive)
+    Random(Option<isize>, Option<isize>, usize),
+
+    /// Range of a number of lines starting at a line (1-based), e.g.: `100+20` for lines 100 to
+    /// 119
+    RangeWithCount(isize, usize),
 }
 
 impl RawLineSelector {
+    /// Returns the range of the `k`-th (one-based) of `n` chunks of consecutive lines of a file
+    /// with `n_lines` lines, or `None` if the chunk is empty. The first `n_lines % n` chunks have
+    /// one more line than the others.
+    pub(crate) fn chunk(k: usize, n: usize, n_lines: usize) -> Option<Self> {
+        debug_assert!((1..=n).contains(&k));
+        let (len, n_longer_chunks) = (n_lines / n, n_lines % n);
+        let start = (k - 1) * len + (k - 1).min(n_longer_chunks);
+        let len = len + usize::from(k <= n_longer_chunks);
+        match len {
+            0 => None,
+            1 => Some(Self::Single(start as isize + 1)),
+            _ => Some(Self::Range(
+                Some(start as isize + 1),
+                Some((start + len) as isize),
+            )),
+        }
+    }
+
+    /// Groups the (one-based) line numbers `line_nums`, which are in increasing order, into
+    /// selectors of consecutive lines, e.g.: `[1, 2, 3, 7]` becomes `1:3` and `7`.
+    pub(crate) fn runs(line_nums: impl IntoIterator<Item = usize>) -> Vec<Self> {
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        for line_num in line_nums {
+            match runs.last_mut() {
+                Some((_, last)) if *last + 1 == line_num => *last = line_num,
+                _ => runs.push((line_num, line_num)),
+            }
+        }
+        runs.into_iter()
+            .map(|(first, last)| Self::run(first, last))
+            .collect()
+    }
+
+    /// Returns the selector of the (one-based) lines `first` to `last`.
+    fn run(first: usize, last: usize) -> Self {
+        if first == last {
+            Self::Single(first as isize)
+        } else {
+            Self::Range(Some(first as isize), Some(last as isize))
+        }
+    }
+
+    /// Returns the (one-based) number of the last line `self` selects if it can be told without
+    /// knowing the number of lines in the file, i.e.: if its bounds are given and positive.
+    pub(crate) fn last_line_num(self) -> Option<usize> {
+        let positive = |line_num: isize| usize::try_from(line_num).ok().filter(|&n| n > 0);
+        match self {
+            Self::Single(line_num) => positive(line_num),
+            Self::Range(Some(start), Some(end))
+            | Self::RangeWithStep(Some(start), Some(end), _) => {
+                Some(positive(start)?.max(positive(end)?))
+            }
+            Self::RangeWithCount(start, count) => positive(start)?.checked_add(count - 1),
+            _ => None,
+        }
+    }
+
+    /// Applies `step` to a range, multiplying its own step if it has one, for `--every`. Ranges
+    /// without a start start at line `start` if it's given, for `--starting-at`.
+    ///
+    /// For example, `:100` becomes `3:100:5` with a step of 5 and a start of 3, and `10:1:-1`
+    /// becomes `10:1:-2` with a step of 2. Other selectors are returned as is.
+    pub(crate) fn with_every(self, step: NonZeroUsize, start: Option<NonZeroUsize>) -> Self {
+        // steps and starts past `isize::MAX` are past the end of any file anyway
+        let step = isize::try_from(step.get()).unwrap_or(isize::MAX);
+        let start = |range_start: Option<isize>| {
+            range_start.or(start.map(|start| isize::try_from(start.get()).unwrap_or(isize::MAX)))
+        };
+        match self {
+            Self::Range(range_start, end) => {
+                Self::RangeWithStep(start(range_start), end, Some(step))
+            }
+            Self::RangeWithStep(range_start, end, range_step) => Self::RangeWithStep(
+                start(range_start),
+                end,
+                Some(range_step.unwrap_or(1).saturating_mul(step)),
+            ),
+            Self::RangeWithCount(range_start, count) => {
+                let end = range_start.saturating_add_unsigned(count - 1);
+                // a range ending past the end of the file is kept as is to be reported later
+                if range_start > 0 || end < 0 {
+                    Self::RangeWithStep(Some(range_start), Some(end), Some(step))
+                } else {
+                    self
+                }
+            }
+            Self::Single(_) | Self::Random(..) => self,
+        }
+    }
+
     /// Parses `s` into single and range line selectors without validation (e.g. if the number is
     /// out of bound) or further processing (e.g. converting negative numbers and unbounded ranges).
     /// Thus, the numbers are stored as one-based.
@@ -161,6 +472,9 @@ impl RawLineSelector {
         if s.is_empty() {
             anyhow::bail!("Line number can't be empty");
         }
+        if let Some(args) = s.strip_prefix("rand(") {
+            return Self::random_from_str(args);
+        }
 
         let parse = |s: &str| {
             if s.is_empty() {
@@ -178,6 +492,21 @@ impl RawLineSelector {
             Ok(Some(num))
         };
 
+        // the sign of the start isn't mistaken for a count (e.g.: `+5` or `-10+5`)
+        if !s.contains(':')
+            && let Some(i) = s.rfind('+').filter(|&i| i > 0)
+        {
+            let start = parse(s[..i].trim())?.expect("`s` is trimmed, so the start isn't empty");
+            let count = s[i + 1..].trim();
+            let count: usize = count
+                .parse()
+                .with_context(|| format!("Value `{count}` is not a number"))?;
+            if count == 0 {
+                anyhow::bail!("The number of lines of a counted range can't be zero");
+            }
+            return Ok(Self::RangeWithCount(start, count));
+        }
+
         let mut parts = s.splitn(3, ':');
         match (parts.next(), parts.next(), parts.next()) {
             (Some(line_num), None, None) => {
@@ -198,6 +527,77 @@ impl RawLineSelector {
             _ => unreachable!(),
         }
     }
+
+    /// Parses the arguments of `rand(RANGE, COUNT)`, given as `args` with the closing parenthesis.
+    fn random_from_str(args: &str) -> anyhow::Result<Self> {
+        let usage =
+            "Random selectors are of the form rand(START:END, COUNT) (e.g.: rand(100:2000, 5))";
+        let (range, count) = args
+            .strip_suffix(')')
+            .and_then(|args| args.split_once(','))
+            .context(usage)?;
+        let count: usize = count
+            .trim()
+            .parse()
+            .with_context(|| format!("Value `{}` is not a number", count.trim()))?;
+        if count == 0 {
+            anyhow::bail!("The number of random lines can't be zero");
+        }
+        match Self::from_str(range)? {
+            Self::Range(start, end) => Ok(Self::Random(start, end, count)),
+            _ => anyhow::bail!(usage),
+        }
+    }
+
+    /// Splits the value of `-n` into line selectors on the commas that aren't inside the
+    /// parentheses of a random selector (e.g.: `1,rand(5:9, 2)` is split into `1` and
+    /// `rand(5:9, 2)`), then parses each of them.
+    pub fn list_from_str(s: &str) -> anyhow::Result<RawLineSelectors> {
+        let mut line_selectors = Vec::new();
+        let mut depth = 0_usize;
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    line_selectors.push(Self::from_str(&s[start..i])?);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        line_selectors.push(Self::from_str(&s[start..])?);
+        Ok(RawLineSelectors(line_selectors))
+    }
+
+    /// Parses the line selectors of `--line-file`, one per line of `reader`. Blank lines are
+    /// skipped.
+    pub(crate) fn list_from_lines(reader: impl BufRead) -> anyhow::Result<RawLineSelectors> {
+        let mut line_selectors = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.context("Failed to read line selectors")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let line_selector = Self::from_str(&line)
+                .with_context(|| format!("Invalid line selector on line {}", i + 1))?;
+            line_selectors.push(line_selector);
+        }
+        Ok(RawLineSelectors(line_selectors))
+    }
+}
+
+/// The line selectors given to one `-n`.
+#[derive(Clone, Debug)]
+pub struct RawLineSelectors(pub Vec<RawLineSelector>);
+
+impl FromStr for RawLineSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Self::from_str(s)
+    }
 }
 
 impl Display for RawLineSelector {
@@ -220,6 +620,12 @@ impl Display for RawLineSelector {
                 (Some(start), Some(end), None) => write!(f, "{start}:{end}:"),
                 (Some(start), Some(end), Some(step)) => write!(f, "{start}:{end}:{step}"),
             },
+            RawLineSelector::RangeWithCount(start, count) => write!(f, "{start}+{count}"),
+            RawLineSelector::Random(start, end, count) => {
+                write!(f, "rand(")?;
+                Display::fmt(&RawLineSelector::Range(*start, *end), f)?;
+                write!(f, ", {count})")
+            }
         }
     }
 }
@@ -247,13 +653,27 @@ impl Display for RawLineSelector {
 mod tests {
     use super::*;
 
+    /// Parses the comma-separated selectors `s` for a file with `n_lines` lines.
+    fn line_selectors(s: &str, n_lines: usize) -> Vec<LineSelector> {
+        let mut rng = Rng::new(0);
+        RawLineSelector::list_from_str(s)
+            .unwrap()
+            .0
+            .into_iter()
+            .map(|raw| LineSelector {
+                parsed: ParsedLineSelector::from_raw_with_rng(raw, n_lines, &mut rng).unwrap(),
+                raw,
+            })
+            .collect()
+    }
+
     mod create_parsed_line_selector {
         use super::*;
 
         macro_rules! create_parsed_line_selector {
             ($s: literal, $n_lines: literal) => {{
                 let raw = RawLineSelector::from_str($s).unwrap();
-                ParsedLineSelector::from_raw(raw, $n_lines)
+                ParsedLineSelector::from_raw_with_rng(raw, $n_lines, &mut Rng::new(0))
             }};
         }
 
@@ -357,6 +777,171 @@ mod tests {
         }
     }
 
+    mod random_line_selector {
+        use super::*;
+
+        #[test]
+        fn parse() {
+            assert_eq!(
+                RawLineSelector::from_str("rand(100:2000, 5)").unwrap(),
+                RawLineSelector::Random(Some(100), Some(2000), 5)
+            );
+            assert_eq!(
+                RawLineSelector::from_str(" rand(:-2,3) ").unwrap(),
+                RawLineSelector::Random(None, Some(-2), 3)
+            );
+            assert!(RawLineSelector::from_str("rand(1:5)").is_err());
+            assert!(RawLineSelector::from_str("rand(1:5, 2").is_err());
+            assert!(RawLineSelector::from_str("rand(1:5, 0)").is_err());
+            assert!(RawLineSelector::from_str("rand(1:5, a)").is_err());
+            assert!(RawLineSelector::from_str("rand(3, 2)").is_err());
+            assert!(RawLineSelector::from_str("rand(1:5:2, 2)").is_err());
+        }
+
+        #[test]
+        fn picks_distinct_lines_in_range() {
+            let raw = RawLineSelector::from_str("rand(3:-3, 4)").unwrap();
+            let parsed = ParsedLineSelector::from_raw_with_rng(raw, 20, &mut Rng::new(0)).unwrap();
+            let ParsedLineSelector::Random(line_nums) = parsed else {
+                panic!("expected random lines, got {parsed:?}");
+            };
+            assert_eq!(line_nums.len(), 4);
+            assert!(line_nums.is_sorted_by(|a, b| a < b));
+            assert!(line_nums.iter().all(|line_num| (2..=17).contains(line_num)));
+        }
+
+        #[test]
+        fn picks_every_line_of_small_ranges() {
+            let raw = RawLineSelector::from_str("rand(2:4, 10)").unwrap();
+            assert_eq!(
+                ParsedLineSelector::from_raw_with_rng(raw, 5, &mut Rng::new(0)).unwrap(),
+                ParsedLineSelector::Random(vec![1, 2, 3])
+            );
+        }
+
+        #[test]
+        fn same_seed_same_lines() {
+            let raw = RawLineSelector::from_str("rand(:, 3)").unwrap();
+            assert_eq!(
+                ParsedLineSelector::from_raw(raw, 1000, Some(42)).unwrap(),
+                ParsedLineSelector::from_raw(raw, 1000, Some(42)).unwrap()
+            );
+        }
+
+        #[test]
+        fn split_list() {
+            let line_selectors = RawLineSelector::list_from_str("1,rand(5:9, 2), 3:4").unwrap();
+            assert_eq!(
+                line_selectors.0,
+                [
+                    RawLineSelector::Single(1),
+                    RawLineSelector::Random(Some(5), Some(9), 2),
+                    RawLineSelector::Range(Some(3), Some(4)),
+                ]
+            );
+            assert!(RawLineSelector::list_from_str("1,,2").is_err());
+        }
+    }
+
+    mod counted_range {
+        use super::*;
+
+        fn resolve(s: &str, n_lines: usize) -> anyhow::Result<ParsedLineSelector> {
+            let raw = RawLineSelector::from_str(s).unwrap();
+            ParsedLineSelector::from_raw_with_rng(raw, n_lines, &mut Rng::new(0))
+        }
+
+        #[test]
+        fn parse() {
+            assert_eq!(
+                RawLineSelector::from_str("100+20").unwrap(),
+                RawLineSelector::RangeWithCount(100, 20)
+            );
+            assert_eq!(
+                RawLineSelector::from_str(" -10 + 3 ").unwrap(),
+                RawLineSelector::RangeWithCount(-10, 3)
+            );
+            assert_eq!(
+                RawLineSelector::from_str("+5").unwrap(),
+                RawLineSelector::Single(5)
+            );
+            assert!(RawLineSelector::from_str("5+0").is_err());
+            assert!(RawLineSelector::from_str("5+").is_err());
+            assert!(RawLineSelector::from_str("5+-2").is_err());
+            assert!(RawLineSelector::from_str("0+2").is_err());
+            assert!(RawLineSelector::from_str("1:5+2").is_err());
+        }
+
+        #[test]
+        fn resolves_to_range() {
+            assert_eq!(
+                resolve("2+3", 5).unwrap(),
+                ParsedLineSelector::Range(1, 3, 1)
+            );
+            assert_eq!(
+                resolve("-2+2", 5).unwrap(),
+                ParsedLineSelector::Range(3, 4, 1)
+            );
+            assert_eq!(resolve("4+1", 5).unwrap(), ParsedLineSelector::Single(3));
+            assert!(resolve("4+3", 5).is_err());
+            assert!(resolve("-1+2", 5).is_err());
+            let err = resolve("3+18446744073709551615", 5).unwrap_err();
+            assert!(err.is::<LineOutOfRange>());
+        }
+    }
+
+    mod line_file {
+        use super::*;
+
+        #[test]
+        fn one_selector_per_line() {
+            let line_selectors =
+                RawLineSelector::list_from_lines("3\n\n 5:7 \n-1+2\n".as_bytes()).unwrap();
+            assert_eq!(
+                line_selectors.0,
+                [
+                    RawLineSelector::Single(3),
+                    RawLineSelector::Range(Some(5), Some(7)),
+                    RawLineSelector::RangeWithCount(-1, 2)
+                ]
+            );
+            let err = RawLineSelector::list_from_lines("3\nx\n".as_bytes()).unwrap_err();
+            assert_eq!(err.to_string(), "Invalid line selector on line 2");
+        }
+    }
+
+    mod iter_line_selector {
+        use super::*;
+
+        fn line_selector(s: &str, n_lines: usize) -> LineSelector {
+            let raw = RawLineSelector::from_str(s).unwrap();
+            LineSelector {
+                parsed: ParsedLineSelector::from_raw_with_rng(raw, n_lines, &mut Rng::new(0))
+                    .unwrap(),
+                raw,
+            }
+        }
+
+        #[test]
+        fn ascending_and_selection_order() {
+            for (s, ascending, in_selection_order) in [
+                ("3", vec![2], vec![2]),
+                ("2:4", vec![1, 2, 3], vec![1, 2, 3]),
+                ("1:6:2", vec![0, 2, 4], vec![0, 2, 4]),
+                ("8:4:-2", vec![3, 5, 7], vec![7, 5, 3]),
+                ("8:2:-3", vec![1, 4, 7], vec![7, 4, 1]),
+            ] {
+                let line_selector = line_selector(s, 8);
+                assert_eq!(line_selector.iter().collect::<Vec<_>>(), ascending);
+                assert_eq!(line_selector.max_line_num(), ascending.last().copied());
+                assert_eq!(
+                    line_selector.iter_in_selection_order().collect::<Vec<_>>(),
+                    in_selection_order
+                );
+            }
+        }
+    }
+
     mod display_raw_line_selector {
         use super::*;
 
@@ -381,5 +966,167 @@ mod tests {
                 assert_eq!(line_selector.to_string(), s);
             }
         }
+
+        #[test]
+        fn range_with_count() {
+            for s in ["100+20", "-5+2"] {
+                let line_selector = RawLineSelector::from_str(s).unwrap();
+                assert_eq!(line_selector.to_string(), s);
+            }
+        }
+
+        #[test]
+        fn random() {
+            for s in [
+                "rand(:, 5)",
+                "rand(1:, 5)",
+                "rand(:-1, 5)",
+                "rand(100:2000, 5)",
+            ] {
+                let line_selector = RawLineSelector::from_str(s).unwrap();
+                assert_eq!(line_selector.to_string(), s);
+            }
+        }
+    }
+
+    mod last_line_num {
+        use super::*;
+
+        #[test]
+        fn bounded_selectors_only() {
+            for (s, expected) in [
+                ("3", Some(3)),
+                ("2:7", Some(7)),
+                ("9:4:-2", Some(9)),
+                ("1:5:2", Some(5)),
+                ("100+20", Some(119)),
+                ("-5+2", None),
+                ("-1", None),
+                ("3:", None),
+                (":3", None),
+                ("2:-1", None),
+                ("rand(1:5, 2)", None),
+            ] {
+                let line_selector = RawLineSelector::from_str(s).unwrap();
+                assert_eq!(line_selector.last_line_num(), expected, "selector: {s}");
+            }
+        }
+    }
+
+    mod every {
+        use super::*;
+
+        #[test]
+        fn applies_step_to_ranges() {
+            let step = NonZeroUsize::new(5).unwrap();
+            let start = NonZeroUsize::new(3);
+            for (s, with_step, with_step_and_start) in [
+                (":", "::5", "3::5"),
+                (":100", ":100:5", "3:100:5"),
+                ("10:20", "10:20:5", "10:20:5"),
+                ("1::2", "1::10", "1::10"),
+                ("-1:1:-1", "-1:1:-5", "-1:1:-5"),
+                ("::", "::5", "3::5"),
+                ("7", "7", "7"),
+                ("10+11", "10:20:5", "10:20:5"),
+                ("-5+3", "-5:-3:5", "-5:-3:5"),
+                ("rand(:, 5)", "rand(:, 5)", "rand(:, 5)"),
+            ] {
+                let line_selector = RawLineSelector::from_str(s).unwrap();
+                assert_eq!(line_selector.with_every(step, None).to_string(), with_step);
+                assert_eq!(
+                    line_selector.with_every(step, start).to_string(),
+                    with_step_and_start
+                );
+            }
+        }
+
+        #[test]
+        fn huge_steps_select_the_first_line() {
+            let step = NonZeroUsize::new(usize::MAX).unwrap();
+            let line_selector = RawLineSelector::from_str(":")
+                .unwrap()
+                .with_every(step, None);
+            assert_eq!(line_selector.to_string(), format!("::{}", isize::MAX));
+            let parsed =
+                ParsedLineSelector::from_raw_with_rng(line_selector, 10, &mut Rng::new(0)).unwrap();
+            assert_eq!(parsed, ParsedLineSelector::Range(0, 0, isize::MAX));
+        }
+    }
+
+    mod invert {
+        use super::*;
+
+        #[test]
+        fn selects_the_other_lines() {
+            let invert = |s: &str, n_lines| -> Vec<String> {
+                super::super::invert(&line_selectors(s, n_lines), n_lines)
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect()
+            };
+            assert_eq!(invert("1", 5), ["2:5"]);
+            assert_eq!(invert("2,4", 5), ["1", "3", "5"]);
+            assert_eq!(invert("1::2,2", 6), ["4", "6"]);
+            assert_eq!(invert("5:3:-1,2:4,9", 10), ["1", "6:8", "10"]);
+            assert!(invert("rand(:, 3)", 3).is_empty());
+            assert!(invert(":", 3).is_empty());
+        }
+    }
+
+    mod unique {
+        use super::*;
+
+        #[test]
+        fn selects_each_line_once() {
+            let unique = |s: &str, n_lines| -> Vec<String> {
+                super::super::unique(&line_selectors(s, n_lines), n_lines)
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect()
+            };
+            assert_eq!(unique("1,1:3,1:1", 5), ["1:3"]);
+            assert_eq!(unique("5,2,-1", 5), ["2", "5"]);
+            assert_eq!(unique("6:1:-2,3", 6), ["2:4", "6"]);
+            assert_eq!(unique("1:3,7:9,4:5,2:8", 10), ["1:9"]);
+            assert_eq!(unique("1:3,5:6", 10), ["1:3", "5:6"]);
+        }
+    }
+
+    mod runs {
+        use super::*;
+
+        #[test]
+        fn groups_consecutive_lines() {
+            let runs = |line_nums: &[usize]| -> Vec<String> {
+                RawLineSelector::runs(line_nums.iter().copied())
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect()
+            };
+            assert_eq!(runs(&[1, 2, 3, 7]), ["1:3", "7"]);
+            assert_eq!(runs(&[4, 6, 8, 9]), ["4", "6", "8:9"]);
+            assert!(runs(&[]).is_empty());
+        }
+    }
+
+    mod chunk {
+        use super::*;
+
+        #[test]
+        fn splits_lines_evenly() {
+            let chunks = |n, n_lines| -> Vec<_> {
+                (1..=n)
+                    .map(|k| RawLineSelector::chunk(k, n, n_lines).map(|c| c.to_string()))
+                    .collect()
+            };
+            assert_eq!(chunks(2, 10), [Some("1:5".into()), Some("6:10".into())]);
+            assert_eq!(
+                chunks(3, 10),
+                [Some("1:4".into()), Some("5:7".into()), Some("8:10".into())]
+            );
+            assert_eq!(chunks(3, 2), [Some("1".into()), Some("2".into()), None]);
+            assert_eq!(chunks(1, 7), [Some(String::from("1:7"))]);
+        }
     }
 }
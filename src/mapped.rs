@@ -0,0 +1,143 @@
+use crate::LineSource;
+use crate::line_counter::PARALLEL_THRESHOLD;
+use anyhow::Context;
+use memmap2::Mmap;
+use std::fs::File;
+use std::thread;
+
+/// Lines of a file mapped into memory, for `--mmap`. Lines are found with `memchr`, which scans
+/// much faster than skipping through a `BufReader`, and are returned without being copied.
+///
+/// Lines can be read in any order: the lines between the last read line and the next one are
+/// scanned forward or backward from there.
+pub(crate) struct MappedLines {
+    map: Mmap,
+    separator: u8,
+    /// The (zero-based) number of the line starting at `offset`
+    line_num: usize,
+    offset: usize,
+}
+
+impl MappedLines {
+    pub(crate) fn new(file: &File, separator: u8) -> anyhow::Result<Self> {
+        // SAFETY: the map is only read, and the file is assumed not to be truncated while it's
+        // mapped, as documented by `--mmap`
+        let map = unsafe { Mmap::map(file) }.context("Failed to map file into memory")?;
+        Ok(Self {
+            map,
+            separator,
+            line_num: 0,
+            offset: 0,
+        })
+    }
+
+    /// Counts the lines of the file, like [`crate::line_counter::count_lines`]. Large files are
+    /// split into `threads` parts which are counted concurrently.
+    pub(crate) fn count_lines(&self, threads: usize) -> usize {
+        let n_separators = if threads > 1 && self.map.len() as u64 >= PARALLEL_THRESHOLD {
+            let part_len = self.map.len().div_ceil(threads);
+            thread::scope(|scope| {
+                let counters: Vec<_> = self
+                    .map
+                    .chunks(part_len)
+                    .map(|part| scope.spawn(|| memchr::memchr_iter(self.separator, part).count()))
+                    .collect();
+                counters
+                    .into_iter()
+                    .map(|counter| counter.join().expect("counting threads don't panic"))
+                    .sum()
+            })
+        } else {
+            memchr::memchr_iter(self.separator, &self.map).count()
+        };
+        // the last line may lack its line ending
+        n_separators + usize::from(self.map.last().is_some_and(|&last| last != self.separator))
+    }
+
+    /// Counts the lines of the file like [`MappedLines::count_lines`], but stops at `max_lines`
+    /// lines, like [`crate::line_counter::count_lines_up_to`].
+    pub(crate) fn count_lines_up_to(&self, max_lines: usize) -> usize {
+        match memchr::memchr_iter(self.separator, &self.map).nth(max_lines.saturating_sub(1)) {
+            Some(_) => max_lines,
+            None => self.count_lines(1).min(max_lines),
+        }
+    }
+}
+
+impl LineSource for MappedLines {
+    fn get(&mut self, line_num: usize) -> anyhow::Result<&[u8]> {
+        if line_num > self.line_num {
+            let n_skipped = line_num - self.line_num;
+            // the line starts right after the `n_skipped`-th separator
+            match memchr::memchr_iter(self.separator, &self.map[self.offset..]).nth(n_skipped - 1) {
+                Some(i) => self.offset += i + 1,
+                None => self.offset = self.map.len(),
+            }
+            self.line_num = line_num;
+        }
+        while line_num < self.line_num {
+            // the byte before `offset` ends the previous line, which starts after the separator
+            // before it
+            let previous_end = self.offset - 1;
+            self.offset =
+                memchr::memrchr(self.separator, &self.map[..previous_end]).map_or(0, |i| i + 1);
+            self.line_num -= 1;
+        }
+        let rest = &self.map[self.offset..];
+        let len = memchr::memchr(self.separator, rest).map_or(rest.len(), |i| i + 1);
+        Ok(&rest[..len])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn mapped_lines(content: &[u8], separator: u8) -> MappedLines {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(content).unwrap();
+        MappedLines::new(&file, separator).unwrap()
+    }
+
+    #[test]
+    fn count() {
+        for (content, expected) in [
+            ("", 0),
+            ("\n", 1),
+            ("one", 1),
+            ("one\n", 1),
+            ("one\ntwo", 2),
+            ("\n\n\nfour\n\nsix", 6),
+        ] {
+            let lines = mapped_lines(content.as_bytes(), b'\n');
+            assert_eq!(lines.count_lines(1), expected, "content: {content:?}");
+            assert_eq!(lines.count_lines(4), expected, "content: {content:?}");
+            assert_eq!(lines.count_lines_up_to(2), expected.min(2));
+        }
+    }
+
+    #[test]
+    fn lines_in_any_order() {
+        let mut lines = mapped_lines(b"one\ntwo\r\n\nfour", b'\n');
+        for (line_num, expected) in [
+            (1, "two\r\n"),
+            (3, "four"),
+            (0, "one\n"),
+            (2, "\n"),
+            (2, "\n"),
+            (1, "two\r\n"),
+            (3, "four"),
+        ] {
+            assert_eq!(lines.get(line_num).unwrap(), expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn custom_separator() {
+        let mut lines = mapped_lines(b"a\nb\0c\0", b'\0');
+        assert_eq!(lines.count_lines(1), 2);
+        assert_eq!(lines.get(1).unwrap(), b"c\0");
+        assert_eq!(lines.get(0).unwrap(), b"a\nb\0");
+    }
+}
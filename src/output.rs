@@ -1,19 +1,42 @@
-use crate::{cli::When, line_selector::LineSelector};
-use std::io::Write;
+use crate::{
+    cli::{Cli, Emphasis, OutputFormat, ThemeChoice, When},
+    line_selector::LineSelector,
+    pager, paths, width,
+};
+use anyhow::Context;
+use std::collections::HashMap;
+use std::io::{IoSlice, Write};
+use std::num::NonZeroUsize;
+use std::path::Path;
 
 mod colored_and_decorated;
 mod colored_and_not_decorated;
+mod grid;
+mod highlight;
+mod json_lines;
+mod message_pack;
 mod not_colored_decorated;
 mod not_colored_not_decorated;
+mod template;
+mod terminal;
+mod theme;
+
+pub(crate) use template::Template;
+
+/// The latest version of the schema of `--output-format=jsonl`.
+pub(crate) const LATEST_JSON_VERSION: u64 = 2;
 
 // TODO (FIXME): handle SIGPIPE, eg: `line -n=: large_file.txt | head -n1`
 
-// TODO: make this cross-platform
-const RED: &str = "\x1b[31m";
-const GREEN_BOLD: &str = "\x1b[32;1m";
-const BOLD: &str = "\x1b[1m";
+/// Resets the style. Like other escape sequences, it's only written if the terminal interprets them
+/// (see `terminal::Capabilities::detect`)
 const CLEAR: &str = "\x1b[0m";
-const BLUE_BOLD: &str = "\x1b[36;1m";
+/// Erases the rest of the line, filling it with the current background color
+const ERASE_TO_END_OF_LINE: &str = "\x1b[K";
+/// Marks the rows of a line wrapped by `--wrap`, in place of the line number
+const CONTINUATION: &str = "↪";
+/// Enough spaces to right-align line numbers of up to 20 digits.
+const SPACES: &str = "                    ";
 
 pub(crate) enum Line<'a> {
     Context { line_num: usize, line: &'a [u8] },
@@ -21,39 +44,669 @@ pub(crate) enum Line<'a> {
 }
 
 pub(crate) trait OutputWriter: Write {
+    /// Returns true if line numbers and headers are printed.
+    fn is_decorated(&self) -> bool {
+        false
+    }
+
     fn print_line(&mut self, line: Line<'_>) -> anyhow::Result<()>;
     fn print_line_selector_header(
         &mut self,
         line_selector: &LineSelector,
         first_line: bool,
     ) -> anyhow::Result<()>;
+
+    /// Prints `separator` between the groups of selected lines and their context, for
+    /// `--group-separator`.
+    fn print_group_separator(&mut self, separator: &str) -> anyhow::Result<()> {
+        writeln!(self, "{separator}")?;
+        Ok(())
+    }
+
+    /// Prints `ruler` above the lines of a selector, aligned with the content of line `line_num`,
+    /// the first line printed below it.
+    fn print_ruler(&mut self, _line_num: usize, ruler: &str) -> anyhow::Result<()> {
+        writeln!(self, "{ruler}")?;
+        Ok(())
+    }
+
+    /// Prints `marker` where the context of a selected line is cut short by the start or the end
+    /// of the file.
+    fn print_file_boundary(&mut self, marker: &str) -> anyhow::Result<()> {
+        writeln!(self, "{marker}")?;
+        Ok(())
+    }
+
+    /// Prints what closes the output once all the lines are printed.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// One of the output writers, picked once at startup.
+///
+/// Dispatching through an enum instead of a `Box<dyn OutputWriter>` lets the compiler inline the
+/// per-line printing code, which matters when printing large selections.
+pub(crate) enum Output<W: Write> {
+    ColoredDecorated(colored_and_decorated::Writer<W>),
+    ColoredPlain(colored_and_not_decorated::Writer<W>),
+    Decorated(not_colored_decorated::Writer<W>),
+    Plain(not_colored_not_decorated::Writer<W>),
+    Grid(grid::Writer<W>),
+    MessagePack(message_pack::Writer<W>),
+    JsonLines(json_lines::Writer<W>),
+    Template(template::Writer<W>),
+}
+
+macro_rules! dispatch {
+    ($output: expr, $writer: ident => $body: expr) => {
+        match $output {
+            Output::ColoredDecorated($writer) => $body,
+            Output::ColoredPlain($writer) => $body,
+            Output::Decorated($writer) => $body,
+            Output::Plain($writer) => $body,
+            Output::Grid($writer) => $body,
+            Output::MessagePack($writer) => $body,
+            Output::JsonLines($writer) => $body,
+            Output::Template($writer) => $body,
+        }
+    };
+}
+
+impl<W: Write> Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        dispatch!(self, writer => writer.write(buf))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        dispatch!(self, writer => writer.write_all(buf))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        dispatch!(self, writer => writer.flush())
+    }
+}
+
+impl<W: Write> OutputWriter for Output<W> {
+    fn is_decorated(&self) -> bool {
+        dispatch!(self, writer => writer.is_decorated())
+    }
+
+    #[inline]
+    fn print_line(&mut self, line: Line<'_>) -> anyhow::Result<()> {
+        dispatch!(self, writer => writer.print_line(line))
+    }
+
+    fn print_line_selector_header(
+        &mut self,
+        line_selector: &LineSelector,
+        first_line: bool,
+    ) -> anyhow::Result<()> {
+        dispatch!(self, writer => writer.print_line_selector_header(line_selector, first_line))
+    }
+
+    fn print_group_separator(&mut self, separator: &str) -> anyhow::Result<()> {
+        dispatch!(self, writer => writer.print_group_separator(separator))
+    }
+
+    fn print_ruler(&mut self, line_num: usize, ruler: &str) -> anyhow::Result<()> {
+        dispatch!(self, writer => writer.print_ruler(line_num, ruler))
+    }
+
+    fn print_file_boundary(&mut self, marker: &str) -> anyhow::Result<()> {
+        dispatch!(self, writer => writer.print_file_boundary(marker))
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        dispatch!(self, writer => writer.finish())
+    }
+}
+
+/// Flushes the output after every printed line, for `--line-buffered`.
+pub(crate) struct LineBuffered<O>(pub(crate) O);
+
+impl<O: Write> Write for LineBuffered<O> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.0.write_all(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<O: OutputWriter> OutputWriter for LineBuffered<O> {
+    fn is_decorated(&self) -> bool {
+        self.0.is_decorated()
+    }
+
+    fn print_line(&mut self, line: Line<'_>) -> anyhow::Result<()> {
+        self.0.print_line(line)?;
+        self.0.flush()?;
+        Ok(())
+    }
+
+    fn print_line_selector_header(
+        &mut self,
+        line_selector: &LineSelector,
+        first_line: bool,
+    ) -> anyhow::Result<()> {
+        self.0.print_line_selector_header(line_selector, first_line)
+    }
+
+    fn print_group_separator(&mut self, separator: &str) -> anyhow::Result<()> {
+        self.0.print_group_separator(separator)
+    }
+
+    fn print_ruler(&mut self, line_num: usize, ruler: &str) -> anyhow::Result<()> {
+        self.0.print_ruler(line_num, ruler)?;
+        self.0.flush()?;
+        Ok(())
+    }
+
+    fn print_file_boundary(&mut self, marker: &str) -> anyhow::Result<()> {
+        self.0.print_file_boundary(marker)?;
+        self.0.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.0.finish()?;
+        self.0.flush()?;
+        Ok(())
+    }
 }
 
-pub(crate) fn get_output_writer<W>(
+/// Adds an offset to the displayed line numbers, for `--offset-base`.
+pub(crate) struct OffsetLineNumbers<O>(pub(crate) O, pub(crate) usize);
+
+impl<O: Write> Write for OffsetLineNumbers<O> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.0.write_all(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<O: OutputWriter> OutputWriter for OffsetLineNumbers<O> {
+    fn is_decorated(&self) -> bool {
+        self.0.is_decorated()
+    }
+
+    #[inline]
+    fn print_line(&mut self, line: Line<'_>) -> anyhow::Result<()> {
+        let line = match line {
+            Line::Context { line_num, line } => Line::Context {
+                line_num: line_num.saturating_add(self.1),
+                line,
+            },
+            Line::Selected { line_num, line } => Line::Selected {
+                line_num: line_num.saturating_add(self.1),
+                line,
+            },
+        };
+        self.0.print_line(line)
+    }
+
+    fn print_line_selector_header(
+        &mut self,
+        line_selector: &LineSelector,
+        first_line: bool,
+    ) -> anyhow::Result<()> {
+        self.0.print_line_selector_header(line_selector, first_line)
+    }
+
+    fn print_group_separator(&mut self, separator: &str) -> anyhow::Result<()> {
+        self.0.print_group_separator(separator)
+    }
+
+    fn print_ruler(&mut self, line_num: usize, ruler: &str) -> anyhow::Result<()> {
+        self.0.print_ruler(line_num.saturating_add(self.1), ruler)
+    }
+
+    fn print_file_boundary(&mut self, marker: &str) -> anyhow::Result<()> {
+        self.0.print_file_boundary(marker)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.0.finish()
+    }
+}
+
+/// The line number gutter of a decorated line, formatted into a stack buffer.
+///
+/// Formatting the gutter manually skips the machinery of `write!`, which shows up in profiles
+/// when printing many lines.
+struct Gutter {
+    // large enough for a few 24-bit color escape codes around a padded 20-digit number
+    buf: [u8; 256],
+    len: usize,
+}
+
+impl Gutter {
+    /// Formats the concatenation of `prefix`, `line_num + 1`, and `suffix`.
+    fn new(prefix: &[&str], line_num: usize, suffix: &[&str]) -> Self {
+        let mut gutter = Self {
+            buf: [0; 256],
+            len: 0,
+        };
+
+        prefix.iter().for_each(|part| gutter.push(part.as_bytes()));
+
+        let mut digits = [0; 20];
+        let mut first_digit = digits.len();
+        // line numbers shifted by `--offset-base` may be as large as `usize::MAX`
+        let mut n = line_num.saturating_add(1);
+        loop {
+            first_digit -= 1;
+            digits[first_digit] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        gutter.push(&digits[first_digit..]);
+
+        suffix.iter().for_each(|part| gutter.push(part.as_bytes()));
+
+        gutter
+    }
+
+    /// Formats the gutter of the rows of a wrapped line after the first one: the concatenation of
+    /// `prefix`, `padding` spaces, the continuation marker, and `suffix`.
+    fn continuation(prefix: &[&str], padding: usize, suffix: &[&str]) -> Self {
+        let mut gutter = Self {
+            buf: [0; 256],
+            len: 0,
+        };
+        prefix.iter().for_each(|part| gutter.push(part.as_bytes()));
+        gutter.push(&SPACES.as_bytes()[..padding.min(SPACES.len())]);
+        gutter.push(CONTINUATION.as_bytes());
+        suffix.iter().for_each(|part| gutter.push(part.as_bytes()));
+        gutter
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Returns the number of columns taken by the gutter of line `line_num`, without escape codes
+    /// (i.e.: its number and the `: ` after it).
+    fn width(line_num: usize) -> usize {
+        Self::digits(line_num) + ": ".len()
+    }
+
+    /// Returns the number of digits of line `line_num`, which is printed one-based.
+    fn digits(line_num: usize) -> usize {
+        line_num.saturating_add(1).ilog10() as usize + 1
+    }
+}
+
+/// Splits `line` into its content and its line ending (`\n`, `\r\n`, or nothing).
+fn split_line_ending(line: &[u8]) -> (&[u8], &[u8]) {
+    let content_len = match line {
+        [.., b'\r', b'\n'] => line.len() - 2,
+        [.., b'\n'] => line.len() - 1,
+        _ => line.len(),
+    };
+    line.split_at(content_len)
+}
+
+/// Writes a selected line after `prefix`, its gutter and/or style, then resets the style. With
+/// `fill`, the style of the line spans the whole width of the terminal.
+fn write_selected_line<W: Write>(
+    writer: &mut W,
+    prefix: &[u8],
+    line: &[u8],
+    fill: bool,
+) -> std::io::Result<()> {
+    if fill {
+        // the rest of the line must be erased before the line ending moves the cursor away
+        let (content, line_ending) = split_line_ending(line);
+        write_all_vectored(
+            writer,
+            &mut [
+                IoSlice::new(prefix),
+                IoSlice::new(content),
+                IoSlice::new(ERASE_TO_END_OF_LINE.as_bytes()),
+                IoSlice::new(CLEAR.as_bytes()),
+                IoSlice::new(line_ending),
+            ],
+        )
+    } else {
+        write_all_vectored(
+            writer,
+            &mut [
+                IoSlice::new(prefix),
+                IoSlice::new(line),
+                IoSlice::new(CLEAR.as_bytes()),
+            ],
+        )
+    }
+}
+
+/// Writes `line` after `gutter`, wrapping it onto the next rows when its content is wider than
+/// `width` columns. Each row after the first starts with `continuation`, which starts with the
+/// line break of the previous row. With `selected`, each row is written by `write_selected_line`
+/// with the given `fill`.
+fn write_wrapped_line<W: Write>(
+    writer: &mut W,
+    gutter: &[u8],
+    continuation: &[u8],
+    line: &[u8],
+    width: usize,
+    selected: Option<bool>,
+) -> std::io::Result<()> {
+    let mut write = |prefix: &[u8], row: &[u8]| match selected {
+        Some(fill) => write_selected_line(writer, prefix, row, fill),
+        None => write_all_vectored(writer, &mut [IoSlice::new(prefix), IoSlice::new(row)]),
+    };
+    let content_len = split_line_ending(line).0.len();
+    let mut prefix = gutter;
+    let mut offset = 0;
+    loop {
+        let row_len = width::split_at_width(&line[offset..content_len], width);
+        if offset + row_len >= content_len {
+            // the last row keeps the line ending
+            return write(prefix, &line[offset..]);
+        }
+        write(prefix, &line[offset..offset + row_len])?;
+        offset += row_len;
+        prefix = continuation;
+    }
+}
+
+/// Returns the number of columns left for the content of lines wrapped at `wrap` columns, next to
+/// a gutter of `gutter_width` columns.
+fn content_width(wrap: usize, gutter_width: usize) -> usize {
+    wrap.saturating_sub(gutter_width).max(1)
+}
+
+/// Writes all `bufs` using as few `write_vectored` calls as possible.
+///
+/// This is a stable version of the unstable `Write::write_all_vectored`. Each printed line is made
+/// of a few pieces (gutter, escape codes, and content), writing them together saves a copy into
+/// `BufWriter`'s buffer for long lines and a syscall per piece for unbuffered writers.
+fn write_all_vectored<W: Write>(
+    writer: &mut W,
+    mut bufs: &mut [IoSlice<'_>],
+) -> std::io::Result<()> {
+    // skip empty slices
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        match writer.write_vectored(bufs) {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Picks the output writer for the format, color, decoration, and theme options in `args`.
+///
+/// `max_line_num` is the largest line number printed, which sets the width of the gutter of
+/// `--grid`, and `line_offsets` are the byte offsets of the printed lines, for the `{offset}`
+/// placeholder of `--format-str`.
+///
+/// This method returns an error if the theme file given with `--theme-file` can't be loaded, or if
+/// the language given with `--language` is unknown.
+pub(crate) fn get_output_writer<W: Write>(
     writer: W,
-    color: When,
-    plain: When,
+    args: &Cli,
+    path: &Path,
     is_terminal: bool,
-) -> Box<dyn OutputWriter>
-where
-    W: Write + 'static,
-{
-    // TODO: respect env vars: https://bixense.com/clicolors/
-    // you can use: https://docs.rs/anstream/latest/anstream/struct.AutoStream.html
-    let color = match color {
+    max_line_num: usize,
+    line_offsets: HashMap<usize, u64>,
+) -> anyhow::Result<Output<W>> {
+    match args.output_format {
+        OutputFormat::MessagePack => return Ok(Output::MessagePack(message_pack::Writer(writer))),
+        OutputFormat::JsonLines => {
+            return Ok(Output::JsonLines(json_lines::Writer(
+                writer,
+                args.output_version,
+                args.separator(),
+            )));
+        }
+        OutputFormat::Text => {}
+    }
+    if let Some(template) = &args.format_str {
+        return Ok(Output::Template(template::Writer::new(
+            writer,
+            template.clone(),
+            paths::display(path).to_string(),
+            line_offsets,
+            args.offset_base,
+        )));
+    }
+
+    let capabilities = if args.canonical {
+        terminal::Capabilities::canonical()
+    } else {
+        terminal::Capabilities::detect()
+    };
+    let color = match args.color {
+        When::Auto if !args.canonical => terminal::color_from_env().unwrap_or(is_terminal),
         When::Auto => is_terminal,
         When::Always => true,
         When::Never => false,
     };
-    let decorated = match plain {
+    // escape sequences would be printed verbatim by terminals without styles
+    let color = color && (capabilities.styles || args.force);
+    let decorated = match args.plain {
+        _ if args.grid => true,
         When::Auto => is_terminal,
         When::Always => false,
         When::Never => true,
     };
-    match (color, decorated) {
-        (true, true) => Box::new(colored_and_decorated::Writer(writer)),
-        (true, false) => Box::new(colored_and_not_decorated::Writer(writer)),
-        (false, true) => Box::new(not_colored_decorated::Writer(writer)),
-        (false, false) => Box::new(not_colored_not_decorated::Writer(writer)),
+    let terminal_width = || {
+        if args.canonical {
+            terminal::DEFAULT_WIDTH
+        } else {
+            terminal::width()
+        }
+    };
+    let wrap = args
+        .wrap
+        .map(|wrap| wrap.map_or_else(terminal_width, NonZeroUsize::get));
+    let grid = |writer, palette| {
+        Output::Grid(grid::Writer::new(
+            writer,
+            palette,
+            max_line_num,
+            terminal_width(),
+            wrap,
+            paths::display(path).to_string(),
+        ))
+    };
+    let output = match (color, decorated) {
+        (true, decorated) => {
+            let theme = match args.theme {
+                // the background of the terminal isn't looked up
+                ThemeChoice::Auto if args.canonical => ThemeChoice::Dark,
+                theme => theme,
+            };
+            // the pager would read the terminal's reply to the background color query
+            let mut theme = theme::Theme::resolve(theme, is_terminal && !pager::is_active());
+            if let Some(theme_file) = &args.theme_file {
+                theme.load_overrides(theme_file)?;
+            }
+            for style_override in &args.style {
+                theme
+                    .apply_style_override(style_override)
+                    .with_context(|| format!("Invalid --style `{style_override}`"))?;
+            }
+            let highlighter = if args.highlight || args.language.is_some() {
+                highlight::Highlighter::new(
+                    args.language.as_deref(),
+                    path,
+                    theme.light_background,
+                    capabilities,
+                )?
+            } else {
+                None
+            };
+            // the syntax colors would hide the color of selected lines
+            let emphasis = match args.emphasis {
+                Emphasis::Color if highlighter.is_some() => Emphasis::Background,
+                emphasis => emphasis,
+            };
+            let mut palette = theme.palette(&capabilities, emphasis);
+            palette.highlighter = highlighter;
+            palette.pattern = args.highlight_pattern.clone();
+            if args.grid {
+                grid(writer, Some(palette))
+            } else if decorated {
+                Output::ColoredDecorated(colored_and_decorated::Writer(writer, palette, wrap))
+            } else {
+                Output::ColoredPlain(colored_and_not_decorated::Writer(writer, palette))
+            }
+        }
+        (false, true) if args.grid => grid(writer, None),
+        (false, true) => Output::Decorated(not_colored_decorated::Writer(writer, wrap)),
+        (false, false) => Output::Plain(not_colored_not_decorated::Writer(writer)),
+    };
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gutter_formats_one_based_line_numbers() {
+        for (line_num, expected) in [
+            (0, "1: "),
+            (8, "9: "),
+            (9, "10: "),
+            (998, "999: "),
+            (12344, "12345: "),
+        ] {
+            let gutter = Gutter::new(&[], line_num, &[": "]);
+            assert_eq!(gutter.as_bytes(), expected.as_bytes());
+            assert_eq!(Gutter::width(line_num), expected.len());
+        }
+    }
+
+    #[test]
+    fn gutter_with_escape_codes() {
+        let gutter = Gutter::new(&["\x1b[32;1m"], 41, &[":", CLEAR, " ", "\x1b[31m"]);
+        assert_eq!(gutter.as_bytes(), b"\x1b[32;1m42:\x1b[0m \x1b[31m");
+    }
+
+    #[test]
+    fn line_buffered_flushes_every_line() {
+        /// Records what was flushed, i.e.: what would have been seen downstream.
+        #[derive(Default)]
+        struct Downstream {
+            buffered: Vec<u8>,
+            flushed: Vec<u8>,
+        }
+
+        impl Write for Downstream {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.buffered.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.flushed.append(&mut self.buffered);
+                Ok(())
+            }
+        }
+
+        let mut output = LineBuffered(not_colored_not_decorated::Writer(Downstream::default()));
+        output
+            .print_line(Line::Selected {
+                line_num: 0,
+                line: b"one\n",
+            })
+            .unwrap();
+        assert_eq!(output.0.0.flushed, b"one\n");
+        output
+            .print_line(Line::Context {
+                line_num: 1,
+                line: b"two\n",
+            })
+            .unwrap();
+        assert_eq!(output.0.0.flushed, b"one\ntwo\n");
+    }
+
+    #[test]
+    fn offset_line_numbers() {
+        let mut output = OffsetLineNumbers(not_colored_decorated::Writer(Vec::new(), None), 100);
+        output
+            .print_line(Line::Selected {
+                line_num: 0,
+                line: b"one\n",
+            })
+            .unwrap();
+        output
+            .print_line(Line::Context {
+                line_num: 1,
+                line: b"two\n",
+            })
+            .unwrap();
+        assert_eq!(output.0.0, b"101: one\n102: two\n");
+    }
+
+    #[test]
+    fn split_line_endings() {
+        assert_eq!(split_line_ending(b"a\n"), (&b"a"[..], &b"\n"[..]));
+        assert_eq!(split_line_ending(b"a\r\n"), (&b"a"[..], &b"\r\n"[..]));
+        assert_eq!(split_line_ending(b"a\r"), (&b"a\r"[..], &b""[..]));
+        assert_eq!(split_line_ending(b""), (&b""[..], &b""[..]));
+    }
+
+    /// A writer that accepts at most `max` bytes per call, to exercise partial writes.
+    struct Trickle {
+        written: Vec<u8>,
+        max: usize,
+    }
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.max);
+            self.written.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_all_vectored_handles_partial_writes() {
+        for max in 1..8 {
+            let mut writer = Trickle {
+                written: Vec::new(),
+                max,
+            };
+            let mut bufs = [
+                IoSlice::new(b"12: "),
+                IoSlice::new(b""),
+                IoSlice::new(b"content\n"),
+                IoSlice::new(b"end"),
+            ];
+            write_all_vectored(&mut writer, &mut bufs).unwrap();
+            assert_eq!(writer.written, b"12: content\nend");
+        }
     }
 }
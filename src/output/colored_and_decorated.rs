@@ -1,8 +1,15 @@
+use crate::i18n;
 use crate::line_selector::{LineSelector, RawLineSelector};
-use crate::output::{BLUE_BOLD, BOLD, CLEAR, GREEN_BOLD, Line, OutputWriter, RED};
-use std::io::Write;
+use crate::output::theme::Palette;
+use crate::output::{
+    CLEAR, Gutter, Line, OutputWriter, content_width, write_all_vectored, write_selected_line,
+    write_wrapped_line,
+};
+use std::io::{IoSlice, Write};
 
-pub(crate) struct Writer<W: Write>(pub W);
+/// Prints decorated lines in the colors of the palette, wrapped at the number of columns of
+/// `--wrap`, if any.
+pub(crate) struct Writer<W: Write>(pub W, pub Palette, pub Option<usize>);
 
 // TODO: consider making a macro to implement Write
 impl<W: Write> Write for Writer<W> {
@@ -16,20 +23,69 @@ impl<W: Write> Write for Writer<W> {
 }
 
 impl<W: Write> OutputWriter for Writer<W> {
+    fn is_decorated(&self) -> bool {
+        true
+    }
+
     fn print_line(&mut self, line: Line<'_>) -> anyhow::Result<()> {
         match line {
             Line::Context { line_num, line } => {
-                write!(self, "{BOLD}{line_num}:{CLEAR} ", line_num = line_num + 1)?;
-                self.write_all(line)?;
+                let line = self.1.highlight(line_num, line, false);
+                let gutter =
+                    Gutter::new(&[&self.1.context_line_number], line_num, &[":", CLEAR, " "]);
+                match self.2 {
+                    Some(wrap) => {
+                        // the styles of the highlighted row must not spill into the gutter
+                        let continuation = Gutter::continuation(
+                            &[CLEAR, "\n", &self.1.context_line_number],
+                            Gutter::digits(line_num),
+                            &[CLEAR, " "],
+                        );
+                        write_wrapped_line(
+                            &mut self.0,
+                            gutter.as_bytes(),
+                            continuation.as_bytes(),
+                            &line,
+                            content_width(wrap, Gutter::width(line_num)),
+                            None,
+                        )?;
+                    }
+                    None => write_all_vectored(
+                        &mut self.0,
+                        &mut [IoSlice::new(gutter.as_bytes()), IoSlice::new(&line)],
+                    )?,
+                }
             }
             Line::Selected { line_num, line } => {
-                write!(
-                    self,
-                    "{GREEN_BOLD}{line_num}:{CLEAR} {RED}",
-                    line_num = line_num + 1
-                )?;
-                self.write_all(line)?;
-                write!(self, "{CLEAR}")?;
+                let line = self.1.highlight(line_num, line, true);
+                let gutter = Gutter::new(
+                    &[&self.1.selected_line_number],
+                    line_num,
+                    &[":", CLEAR, " ", &self.1.selected_line],
+                );
+                match self.2 {
+                    Some(wrap) => {
+                        let continuation = Gutter::continuation(
+                            &["\n", &self.1.selected_line_number],
+                            Gutter::digits(line_num),
+                            &[CLEAR, " ", &self.1.selected_line],
+                        );
+                        write_wrapped_line(
+                            &mut self.0,
+                            gutter.as_bytes(),
+                            continuation.as_bytes(),
+                            &line,
+                            content_width(wrap, Gutter::width(line_num)),
+                            Some(self.1.fill_selected_line),
+                        )?;
+                    }
+                    None => write_selected_line(
+                        &mut self.0,
+                        gutter.as_bytes(),
+                        &line,
+                        self.1.fill_selected_line,
+                    )?,
+                }
             }
         }
 
@@ -44,12 +100,41 @@ impl<W: Write> OutputWriter for Writer<W> {
         if !first_line {
             writeln!(self)?;
         }
-        let prefix = match line_selector.raw {
-            RawLineSelector::Single(_) => "Line",
-            RawLineSelector::Range(..) => "Lines",
-            RawLineSelector::RangeWithStep(..) => "Lines",
-        };
-        writeln!(self, "{BLUE_BOLD}{prefix}: {}{CLEAR}", line_selector.raw)?;
+        let prefix = i18n::message(
+            match line_selector.raw {
+                RawLineSelector::Single(_) => "line-header",
+                RawLineSelector::Range(..) => "lines-header",
+                RawLineSelector::RangeWithStep(..) => "lines-header",
+                RawLineSelector::Random(..) => "lines-header",
+                RawLineSelector::RangeWithCount(..) => "lines-header",
+            },
+            &[],
+        );
+        writeln!(
+            self.0,
+            "{}{prefix}: {}{CLEAR}",
+            self.1.header, line_selector.raw
+        )?;
+        Ok(())
+    }
+
+    fn print_ruler(&mut self, line_num: usize, ruler: &str) -> anyhow::Result<()> {
+        let padding = Gutter::width(line_num);
+        writeln!(
+            self.0,
+            "{:padding$}{}{ruler}{CLEAR}",
+            "", self.1.context_line_number
+        )?;
+        Ok(())
+    }
+
+    fn print_group_separator(&mut self, separator: &str) -> anyhow::Result<()> {
+        writeln!(self.0, "{}{separator}{CLEAR}", self.1.context_line_number)?;
+        Ok(())
+    }
+
+    fn print_file_boundary(&mut self, marker: &str) -> anyhow::Result<()> {
+        writeln!(self.0, "{}{marker}{CLEAR}", self.1.context_line_number)?;
         Ok(())
     }
 }
@@ -1,8 +1,9 @@
 use crate::line_selector::LineSelector;
-use crate::output::{CLEAR, Line, OutputWriter, RED};
+use crate::output::theme::Palette;
+use crate::output::{CLEAR, Line, OutputWriter, write_selected_line};
 use std::io::Write;
 
-pub(crate) struct Writer<W: Write>(pub W);
+pub(crate) struct Writer<W: Write>(pub W, pub Palette);
 
 impl<W: Write> Write for Writer<W> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
@@ -17,13 +18,18 @@ impl<W: Write> Write for Writer<W> {
 impl<W: Write> OutputWriter for Writer<W> {
     fn print_line(&mut self, line: Line<'_>) -> anyhow::Result<()> {
         match line {
-            Line::Context { line_num: _, line } => {
-                self.write_all(line)?;
+            Line::Context { line_num, line } => {
+                let line = self.1.highlight(line_num, line, false);
+                self.0.write_all(&line)?;
             }
-            Line::Selected { line_num: _, line } => {
-                write!(self, "{RED}")?;
-                self.write_all(line)?;
-                write!(self, "{CLEAR}")?;
+            Line::Selected { line_num, line } => {
+                let line = self.1.highlight(line_num, line, true);
+                write_selected_line(
+                    &mut self.0,
+                    self.1.selected_line.as_bytes(),
+                    &line,
+                    self.1.fill_selected_line,
+                )?;
             }
         }
 
@@ -37,4 +43,19 @@ impl<W: Write> OutputWriter for Writer<W> {
     ) -> anyhow::Result<()> {
         Ok(())
     }
+
+    fn print_ruler(&mut self, _line_num: usize, ruler: &str) -> anyhow::Result<()> {
+        writeln!(self.0, "{}{ruler}{CLEAR}", self.1.context_line_number)?;
+        Ok(())
+    }
+
+    fn print_group_separator(&mut self, separator: &str) -> anyhow::Result<()> {
+        writeln!(self.0, "{}{separator}{CLEAR}", self.1.context_line_number)?;
+        Ok(())
+    }
+
+    fn print_file_boundary(&mut self, marker: &str) -> anyhow::Result<()> {
+        writeln!(self.0, "{}{marker}{CLEAR}", self.1.context_line_number)?;
+        Ok(())
+    }
 }
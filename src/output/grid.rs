@@ -0,0 +1,250 @@
+use crate::i18n;
+use crate::line_selector::{LineSelector, RawLineSelector};
+use crate::output::theme::Palette;
+use crate::output::{
+    CLEAR, Gutter, Line, OutputWriter, SPACES, content_width, write_all_vectored,
+    write_selected_line, write_wrapped_line,
+};
+use std::borrow::Cow;
+use std::io::{IoSlice, Write};
+
+/// Prints lines in a grid for `--grid`, like `bat`:
+///
+/// ```text
+/// ────┬──────────────────
+///     │ File: notes.txt
+/// ────┼──────────────────
+///     │ Lines: 9:10
+///   9 │ nine
+///  10 │ ten
+/// ────┴──────────────────
+/// ```
+pub(crate) struct Writer<W: Write> {
+    out: W,
+    /// The styles of lines, or `None` without colors
+    palette: Option<Palette>,
+    /// The style of the grid, which is the one of the numbers of context lines
+    grid_style: String,
+    header_style: String,
+    clear: &'static str,
+    /// The number of digits of the largest line number printed
+    digits: usize,
+    /// The number of columns of horizontal rules
+    width: usize,
+    /// The number of columns lines are wrapped at, for `--wrap`
+    wrap: Option<usize>,
+    path: String,
+    /// Whether the top of the grid is printed, in which case its bottom is printed by `finish`
+    is_open: bool,
+}
+
+impl<W: Write> Writer<W> {
+    pub(crate) fn new(
+        out: W,
+        palette: Option<Palette>,
+        max_line_num: usize,
+        width: usize,
+        wrap: Option<usize>,
+        path: String,
+    ) -> Self {
+        let (grid_style, header_style, clear) = match &palette {
+            Some(palette) => (
+                palette.context_line_number.clone(),
+                palette.header.clone(),
+                CLEAR,
+            ),
+            None => (String::new(), String::new(), ""),
+        };
+        Self {
+            out,
+            palette,
+            grid_style,
+            header_style,
+            clear,
+            digits: Gutter::digits(max_line_num),
+            width,
+            wrap,
+            path,
+            is_open: false,
+        }
+    }
+
+    /// Prints a horizontal rule, crossing the vertical line after the gutter with `junction`.
+    fn print_rule(&mut self, junction: char) -> std::io::Result<()> {
+        let gutter_width = self.digits + 2;
+        writeln!(
+            self.out,
+            "{}{}{junction}{}{}",
+            self.grid_style,
+            "─".repeat(gutter_width),
+            "─".repeat(self.width.saturating_sub(gutter_width + 1)),
+            self.clear
+        )
+    }
+
+    /// Prints `text` in the style of headers, next to an empty gutter.
+    fn print_header(&mut self, text: &str) -> std::io::Result<()> {
+        let gutter_width = self.digits + 2;
+        writeln!(
+            self.out,
+            "{}{:gutter_width$}│{} {}{text}{}",
+            self.grid_style, "", self.clear, self.header_style, self.clear
+        )
+    }
+
+    /// Prints `text` in the style of the grid, next to an empty gutter.
+    fn print_note(&mut self, text: &str) -> std::io::Result<()> {
+        let gutter_width = self.digits + 2;
+        let separator = if text.is_empty() { "" } else { " " };
+        writeln!(
+            self.out,
+            "{}{:gutter_width$}│{separator}{text}{}",
+            self.grid_style, "", self.clear
+        )
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl<W: Write> OutputWriter for Writer<W> {
+    fn is_decorated(&self) -> bool {
+        true
+    }
+
+    fn print_line(&mut self, line: Line<'_>) -> anyhow::Result<()> {
+        let (line_num, line, is_selected) = match line {
+            Line::Context { line_num, line } => (line_num, line, false),
+            Line::Selected { line_num, line } => (line_num, line, true),
+        };
+        // context lines after the last selected line may be shorter than the largest one
+        let padding = &SPACES[..self.digits.saturating_sub(Gutter::digits(line_num))];
+        let (gutter, continuation, line, selected) = match &mut self.palette {
+            None => (
+                Gutter::new(&[" ", padding], line_num, &[" │ "]),
+                Gutter::continuation(&["\n "], self.digits - 1, &[" │ "]),
+                Cow::Borrowed(line),
+                None,
+            ),
+            Some(palette) if is_selected => (
+                Gutter::new(
+                    &[" ", padding, &palette.selected_line_number],
+                    line_num,
+                    &[
+                        CLEAR,
+                        " ",
+                        &palette.context_line_number,
+                        "│",
+                        CLEAR,
+                        " ",
+                        &palette.selected_line,
+                    ],
+                ),
+                Gutter::continuation(
+                    &["\n ", &palette.selected_line_number],
+                    self.digits - 1,
+                    &[
+                        CLEAR,
+                        " ",
+                        &palette.context_line_number,
+                        "│",
+                        CLEAR,
+                        " ",
+                        &palette.selected_line,
+                    ],
+                ),
+                palette.highlight(line_num, line, true),
+                Some(palette.fill_selected_line),
+            ),
+            Some(palette) => (
+                Gutter::new(
+                    &[" ", padding, &palette.context_line_number],
+                    line_num,
+                    &[" │", CLEAR, " "],
+                ),
+                // the styles of the highlighted row must not spill into the gutter
+                Gutter::continuation(
+                    &[CLEAR, "\n ", &palette.context_line_number],
+                    self.digits - 1,
+                    &[" │", CLEAR, " "],
+                ),
+                palette.highlight(line_num, line, false),
+                None,
+            ),
+        };
+        match (self.wrap, selected) {
+            (Some(wrap), selected) => write_wrapped_line(
+                &mut self.out,
+                gutter.as_bytes(),
+                continuation.as_bytes(),
+                &line,
+                content_width(wrap, self.digits + 4),
+                selected,
+            )?,
+            (None, Some(fill)) => {
+                write_selected_line(&mut self.out, gutter.as_bytes(), &line, fill)?
+            }
+            (None, None) => write_all_vectored(
+                &mut self.out,
+                &mut [IoSlice::new(gutter.as_bytes()), IoSlice::new(&line)],
+            )?,
+        }
+        Ok(())
+    }
+
+    fn print_line_selector_header(
+        &mut self,
+        line_selector: &LineSelector,
+        first_line: bool,
+    ) -> anyhow::Result<()> {
+        if first_line && !self.is_open {
+            self.is_open = true;
+            self.print_rule('┬')?;
+            let file_header = i18n::message("file-header", &[]);
+            let path = std::mem::take(&mut self.path);
+            self.print_header(&format!("{file_header}: {path}"))?;
+        }
+        self.print_rule('┼')?;
+        let prefix = i18n::message(
+            match line_selector.raw {
+                RawLineSelector::Single(_) => "line-header",
+                RawLineSelector::Range(..) => "lines-header",
+                RawLineSelector::RangeWithStep(..) => "lines-header",
+                RawLineSelector::Random(..) => "lines-header",
+                RawLineSelector::RangeWithCount(..) => "lines-header",
+            },
+            &[],
+        );
+        self.print_header(&format!("{prefix}: {}", line_selector.raw))?;
+        Ok(())
+    }
+
+    fn print_group_separator(&mut self, separator: &str) -> anyhow::Result<()> {
+        self.print_note(separator)?;
+        Ok(())
+    }
+
+    fn print_ruler(&mut self, _line_num: usize, ruler: &str) -> anyhow::Result<()> {
+        self.print_note(ruler)?;
+        Ok(())
+    }
+
+    fn print_file_boundary(&mut self, marker: &str) -> anyhow::Result<()> {
+        self.print_note(marker)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        if self.is_open {
+            self.print_rule('┴')?;
+        }
+        Ok(())
+    }
+}
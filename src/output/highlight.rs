@@ -0,0 +1,208 @@
+use crate::output::terminal::Capabilities;
+use crate::output::theme::{Color, Style};
+use anyhow::Context;
+use std::borrow::Cow;
+use std::path::Path;
+use syntect::highlighting::{self, HighlightIterator, HighlightState, ThemeSet};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
+
+/// Resets the foreground color set by the highlighted tokens, leaving the background and the
+/// attributes of selected lines alone.
+const DEFAULT_FOREGROUND: &str = "\x1b[39m";
+
+/// Colors the tokens of printed lines by their syntax, for `--highlight`.
+///
+/// Lines are highlighted in the order they're printed. The parsing state is carried over to the
+/// next line only if it follows the previous one in the file, so a construct that starts above
+/// the printed lines (e.g.: a block comment) isn't recognized.
+pub(crate) struct Highlighter {
+    syntax_set: SyntaxSet,
+    syntax: SyntaxReference,
+    theme: highlighting::Theme,
+    capabilities: Capabilities,
+    /// The state after the last highlighted line, and the number of the line that follows it
+    state: Option<(ParseState, HighlightState, usize)>,
+}
+
+impl Highlighter {
+    /// Returns a highlighter for the language called `language` (by name or extension, e.g.:
+    /// `rust` or `rs`), or for the language of the file at `path` if it's `None`. Returns `None`
+    /// if the language of the file can't be told.
+    ///
+    /// This method returns an error if `language` is unknown.
+    pub(crate) fn new(
+        language: Option<&str>,
+        path: &Path,
+        light_background: bool,
+        capabilities: Capabilities,
+    ) -> anyhow::Result<Option<Self>> {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let syntax = match language {
+            Some(language) => syntax_set
+                .find_syntax_by_token(language)
+                .with_context(|| format!("Unknown language `{language}`"))?,
+            None => match syntax_set.find_syntax_for_file(path).ok().flatten() {
+                Some(syntax) => syntax,
+                None => return Ok(None),
+            },
+        };
+        let syntax = syntax.clone();
+
+        let theme_name = if light_background {
+            "InspiredGitHub"
+        } else {
+            "base16-ocean.dark"
+        };
+        let theme = ThemeSet::load_defaults()
+            .themes
+            .remove(theme_name)
+            .expect("the default themes include this theme");
+
+        Ok(Some(Self {
+            syntax_set,
+            syntax,
+            theme,
+            capabilities,
+            state: None,
+        }))
+    }
+
+    /// Colors the tokens of line `line_num`. Lines that aren't valid UTF-8 are returned as is.
+    pub(crate) fn highlight<'l>(&mut self, line_num: usize, line: &'l [u8]) -> Cow<'l, [u8]> {
+        let Ok(text) = std::str::from_utf8(line) else {
+            self.state = None;
+            return Cow::Borrowed(line);
+        };
+        let highlighter = highlighting::Highlighter::new(&self.theme);
+        let (mut parse_state, mut highlight_state) = match self.state.take() {
+            Some((parse_state, highlight_state, next_line_num)) if next_line_num == line_num => {
+                (parse_state, highlight_state)
+            }
+            _ => (
+                ParseState::new(&self.syntax),
+                HighlightState::new(&highlighter, ScopeStack::new()),
+            ),
+        };
+        let Ok(ops) = parse_state.parse_line(text, &self.syntax_set) else {
+            return Cow::Borrowed(line);
+        };
+
+        // the line ending is printed after the colors are reset, like the rest of the output
+        let content_len = text.trim_end_matches(['\r', '\n']).len();
+        let mut highlighted = String::with_capacity(2 * line.len());
+        let mut pos = 0;
+        let mut last_escape = String::new();
+        for (style, token) in HighlightIterator::new(&mut highlight_state, &ops, text, &highlighter)
+        {
+            let token = &token[..token.len().min(content_len.saturating_sub(pos))];
+            pos += token.len();
+            if token.is_empty() {
+                continue;
+            }
+            let foreground = style.foreground;
+            let style = Style {
+                foreground: Some(Color::Rgb(foreground.r, foreground.g, foreground.b)),
+                ..Style::default()
+            };
+            // neighboring tokens often share a color
+            let escape = style.escape(&self.capabilities);
+            if escape != last_escape {
+                highlighted.push_str(&escape);
+                last_escape = escape;
+            }
+            highlighted.push_str(token);
+        }
+        highlighted.push_str(DEFAULT_FOREGROUND);
+        highlighted.push_str(&text[content_len..]);
+
+        self.state = Some((parse_state, highlight_state, line_num.saturating_add(1)));
+        Cow::Owned(highlighted.into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::terminal::ColorSupport;
+
+    fn highlighter_for(language: &str) -> Highlighter {
+        let capabilities = Capabilities {
+            styles: true,
+            colors: ColorSupport::TrueColor,
+            italic: true,
+            dim: true,
+        };
+        Highlighter::new(Some(language), Path::new(""), false, capabilities)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn strip_escapes(line: &[u8]) -> String {
+        let line = String::from_utf8(line.to_vec()).unwrap();
+        let mut stripped = String::new();
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                stripped.push(c);
+            }
+        }
+        stripped
+    }
+
+    #[test]
+    fn keeps_content_and_line_ending() {
+        let mut highlighter = highlighter_for("rs");
+        let line = highlighter.highlight(0, b"fn main() {}\r\n");
+        assert_ne!(line.as_ref(), b"fn main() {}\r\n");
+        assert!(line.ends_with(b"\x1b[39m\r\n"));
+        assert_eq!(strip_escapes(&line), "fn main() {}\r\n");
+    }
+
+    #[test]
+    fn state_is_kept_for_consecutive_lines() {
+        let mut highlighter = highlighter_for("Rust");
+        let comment = highlighter
+            .highlight(1, b"still a comment */\n")
+            .into_owned();
+
+        let mut highlighter = highlighter_for("rust");
+        highlighter.highlight(0, b"/* a comment\n");
+        let continued = highlighter
+            .highlight(1, b"still a comment */\n")
+            .into_owned();
+        assert_ne!(comment, continued);
+
+        // a gap resets the state
+        highlighter.highlight(0, b"/* a comment\n");
+        assert_eq!(
+            highlighter
+                .highlight(2, b"still a comment */\n")
+                .into_owned(),
+            comment
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_is_kept() {
+        let mut highlighter = highlighter_for("rs");
+        assert_eq!(highlighter.highlight(0, b"\xff\n").as_ref(), b"\xff\n");
+    }
+
+    #[test]
+    fn unknown_language() {
+        let capabilities = Capabilities::canonical();
+        assert!(Highlighter::new(Some("nope"), Path::new(""), false, capabilities).is_err());
+        assert!(
+            Highlighter::new(
+                None,
+                Path::new("file.unknown-extension"),
+                false,
+                capabilities
+            )
+            .unwrap()
+            .is_none()
+        );
+    }
+}
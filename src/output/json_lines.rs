@@ -0,0 +1,166 @@
+use crate::escape::split_terminator;
+use crate::line_selector::LineSelector;
+use crate::output::{Line, OutputWriter};
+use serde_json::json;
+use std::io::Write;
+
+/// Writes each printed line as a JSON object on its own line, of the form
+/// `{"version": 2, "number": 42, "content": "...", "line_ending": "\n", "selected": true}`.
+///
+/// `version` is the version of the schema of the objects, given by `--output-version`. Fields may
+/// be added to a version, but never removed or changed, which needs a new version:
+///
+/// - version 1: `version`, `number` (one-based), `content` (the line with its line ending, with
+///   invalid UTF-8 replaced by U+FFFD), and `selected`, then `content_base64` (the exact bytes of
+///   the line in base64, only for lines that aren't valid UTF-8)
+/// - version 2: like version 1, but `content` and `content_base64` leave out the line ending,
+///   which is in `line_ending` instead (empty for a last line without one)
+///
+/// The line ending is the separator of lines given as the third field (`\r\n` counts as one
+/// when it's `\n`).
+pub(crate) struct Writer<W: Write>(pub W, pub u64, pub u8);
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> OutputWriter for Writer<W> {
+    fn print_line(&mut self, line: Line<'_>) -> anyhow::Result<()> {
+        let (line_num, line, selected) = match line {
+            Line::Context { line_num, line } => (line_num, line, false),
+            Line::Selected { line_num, line } => (line_num, line, true),
+        };
+
+        let (content, line_ending) = if self.1 == 1 {
+            (line, None)
+        } else {
+            let (content, line_ending) = split_terminator(line, self.2);
+            (content, Some(line_ending))
+        };
+        let mut object = json!({
+            "version": self.1,
+            "number": (line_num as u64).saturating_add(1),
+            "content": String::from_utf8_lossy(content),
+            "selected": selected,
+        });
+        if let Some(line_ending) = line_ending {
+            object["line_ending"] = String::from_utf8_lossy(line_ending).into();
+        }
+        // JSON strings can't hold invalid UTF-8, so the exact bytes are given alongside
+        if std::str::from_utf8(content).is_err() {
+            object["content_base64"] = encode_base64(content).into();
+        }
+        serde_json::to_writer(&mut self.0, &object)?;
+        self.0.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn print_line_selector_header(
+        &mut self,
+        _line_selector: &LineSelector,
+        _first_line: bool,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_group_separator(&mut self, _separator: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_ruler(&mut self, _line_num: usize, _ruler: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_file_boundary(&mut self, _marker: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Encodes `bytes` in the standard base64 alphabet, with padding.
+fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &byte)| bits | u32::from(byte) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let sextet = (bits >> (18 - 6 * i)) & 0x3f;
+                encoded.push(char::from(ALPHABET[sextet as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_object_per_line() {
+        let mut writer = Writer(Vec::new(), 2, b'\n');
+        writer
+            .print_line(Line::Context {
+                line_num: 0,
+                line: b"one\r\n",
+            })
+            .unwrap();
+        writer
+            .print_line(Line::Selected {
+                line_num: 1,
+                line: b"t\xffo",
+            })
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(writer.0).unwrap(),
+            "{\"content\":\"one\",\"line_ending\":\"\\r\\n\",\"number\":1,\"selected\":false,\
+             \"version\":2}\n\
+             {\"content\":\"t\u{fffd}o\",\"content_base64\":\"dP9v\",\"line_ending\":\"\",\"number\":2,\
+             \"selected\":true,\"version\":2}\n"
+        );
+    }
+
+    #[test]
+    fn version_1_keeps_line_endings() {
+        let mut writer = Writer(Vec::new(), 1, b'\n');
+        writer
+            .print_line(Line::Context {
+                line_num: 0,
+                line: b"one\n",
+            })
+            .unwrap();
+        writer
+            .print_line(Line::Selected {
+                line_num: 1,
+                line: b"t\xffo",
+            })
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(writer.0).unwrap(),
+            "{\"content\":\"one\\n\",\"number\":1,\"selected\":false,\"version\":1}\n\
+             {\"content\":\"t\u{fffd}o\",\"content_base64\":\"dP9v\",\"number\":2,\
+             \"selected\":true,\"version\":1}\n"
+        );
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"h"), "aA==");
+        assert_eq!(encode_base64(b"hi"), "aGk=");
+        assert_eq!(encode_base64(b"hello"), "aGVsbG8=");
+        assert_eq!(encode_base64(b"\xff\xfe\xfd\n"), "//79Cg==");
+    }
+}
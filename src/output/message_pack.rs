@@ -0,0 +1,199 @@
+use crate::line_selector::LineSelector;
+use crate::output::{Line, OutputWriter};
+use std::io::Write;
+
+/// Writes each printed line as a MessagePack map of the form
+/// `{"number": 42, "content": "...", "selected": true}`, one map after the other.
+///
+/// `content` is encoded as a string when the line is valid UTF-8, and as binary otherwise. Line
+/// numbers are one-based, just like in the other writers.
+pub(crate) struct Writer<W: Write>(pub W);
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> OutputWriter for Writer<W> {
+    fn print_line(&mut self, line: Line<'_>) -> anyhow::Result<()> {
+        let (line_num, line, selected) = match line {
+            Line::Context { line_num, line } => (line_num, line, false),
+            Line::Selected { line_num, line } => (line_num, line, true),
+        };
+
+        // fixmap with 3 entries
+        self.write_all(&[0x83])?;
+        write_str(self, b"number")?;
+        write_uint(self, (line_num as u64).saturating_add(1))?;
+        write_str(self, b"content")?;
+        if std::str::from_utf8(line).is_ok() {
+            write_str(self, line)?;
+        } else {
+            write_bin(self, line)?;
+        }
+        write_str(self, b"selected")?;
+        self.write_all(&[if selected { 0xc3 } else { 0xc2 }])?;
+
+        Ok(())
+    }
+
+    fn print_line_selector_header(
+        &mut self,
+        _line_selector: &LineSelector,
+        _first_line: bool,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_group_separator(&mut self, _separator: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_ruler(&mut self, _line_num: usize, _ruler: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_file_boundary(&mut self, _marker: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+fn write_uint(writer: &mut impl Write, n: u64) -> std::io::Result<()> {
+    match n {
+        // positive fixint
+        0..=0x7f => writer.write_all(&[n as u8]),
+        0x80..=0xff => writer.write_all(&[0xcc, n as u8]),
+        0x100..=0xffff => {
+            writer.write_all(&[0xcd])?;
+            writer.write_all(&(n as u16).to_be_bytes())
+        }
+        0x1_0000..=0xffff_ffff => {
+            writer.write_all(&[0xce])?;
+            writer.write_all(&(n as u32).to_be_bytes())
+        }
+        _ => {
+            writer.write_all(&[0xcf])?;
+            writer.write_all(&n.to_be_bytes())
+        }
+    }
+}
+
+/// Writes `s`, which must be valid UTF-8, as a MessagePack string.
+fn write_str(writer: &mut impl Write, s: &[u8]) -> std::io::Result<()> {
+    let len = s.len();
+    match len {
+        // fixstr
+        0..=31 => writer.write_all(&[0xa0 | len as u8])?,
+        32..=0xff => writer.write_all(&[0xd9, len as u8])?,
+        0x100..=0xffff => {
+            writer.write_all(&[0xda])?;
+            writer.write_all(&(len as u16).to_be_bytes())?;
+        }
+        _ => {
+            writer.write_all(&[0xdb])?;
+            writer.write_all(&len_as_u32(len)?.to_be_bytes())?;
+        }
+    }
+    writer.write_all(s)
+}
+
+fn write_bin(writer: &mut impl Write, bytes: &[u8]) -> std::io::Result<()> {
+    let len = bytes.len();
+    match len {
+        0..=0xff => writer.write_all(&[0xc4, len as u8])?,
+        0x100..=0xffff => {
+            writer.write_all(&[0xc5])?;
+            writer.write_all(&(len as u16).to_be_bytes())?;
+        }
+        _ => {
+            writer.write_all(&[0xc6])?;
+            writer.write_all(&len_as_u32(len)?.to_be_bytes())?;
+        }
+    }
+    writer.write_all(bytes)
+}
+
+/// MessagePack can't represent strings or binaries of 4 GiB or more.
+fn len_as_u32(len: usize) -> std::io::Result<u32> {
+    u32::try_from(len).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "lines of 4 GiB or more can't be encoded as MessagePack",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(f: impl FnOnce(&mut Vec<u8>) -> std::io::Result<()>) -> Vec<u8> {
+        let mut buf = Vec::new();
+        f(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn uints() {
+        assert_eq!(encode(|w| write_uint(w, 0)), [0x00]);
+        assert_eq!(encode(|w| write_uint(w, 0x7f)), [0x7f]);
+        assert_eq!(encode(|w| write_uint(w, 0x80)), [0xcc, 0x80]);
+        assert_eq!(encode(|w| write_uint(w, 0x1234)), [0xcd, 0x12, 0x34]);
+        assert_eq!(
+            encode(|w| write_uint(w, 0x1234_5678)),
+            [0xce, 0x12, 0x34, 0x56, 0x78]
+        );
+        assert_eq!(
+            encode(|w| write_uint(w, 0x1_0000_0000)),
+            [0xcf, 0, 0, 0, 1, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn strs() {
+        assert_eq!(encode(|w| write_str(w, b"")), [0xa0]);
+        assert_eq!(encode(|w| write_str(w, b"ab")), [0xa2, b'a', b'b']);
+
+        let s = [b'x'; 32];
+        assert_eq!(encode(|w| write_str(w, &s))[..2], [0xd9, 32]);
+
+        let s = [b'x'; 0x100];
+        assert_eq!(encode(|w| write_str(w, &s))[..3], [0xda, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn bins() {
+        assert_eq!(encode(|w| write_bin(w, &[0xff])), [0xc4, 1, 0xff]);
+
+        let bytes = [0; 0x100];
+        assert_eq!(encode(|w| write_bin(w, &bytes))[..3], [0xc5, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn lines() {
+        let mut writer = Writer(Vec::new());
+        writer
+            .print_line(Line::Selected {
+                line_num: 1,
+                line: b"hi\n",
+            })
+            .unwrap();
+        writer
+            .print_line(Line::Context {
+                line_num: 2,
+                line: &[0xff],
+            })
+            .unwrap();
+
+        let mut expected = vec![0x83];
+        expected.extend_from_slice(b"\xa6number\x02\xa7content\xa3hi\n\xa8selected\xc3");
+        expected.push(0x83);
+        expected.extend_from_slice(b"\xa6number\x03\xa7content\xc4\x01\xff\xa8selected\xc2");
+        assert_eq!(writer.0, expected);
+    }
+}
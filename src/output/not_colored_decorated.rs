@@ -1,8 +1,12 @@
+use crate::i18n;
 use crate::line_selector::{LineSelector, RawLineSelector};
-use crate::output::{Line, OutputWriter};
-use std::io::Write;
+use crate::output::{
+    Gutter, Line, OutputWriter, content_width, write_all_vectored, write_wrapped_line,
+};
+use std::io::{IoSlice, Write};
 
-pub(crate) struct Writer<W: Write>(pub W);
+/// Prints decorated lines without colors, wrapped at the number of columns of `--wrap`, if any.
+pub(crate) struct Writer<W: Write>(pub W, pub Option<usize>);
 
 impl<W: Write> Write for Writer<W> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
@@ -15,11 +19,32 @@ impl<W: Write> Write for Writer<W> {
 }
 
 impl<W: Write> OutputWriter for Writer<W> {
+    fn is_decorated(&self) -> bool {
+        true
+    }
+
     fn print_line(&mut self, line: Line<'_>) -> anyhow::Result<()> {
         match line {
             Line::Context { line_num, line } | Line::Selected { line_num, line } => {
-                write!(self, "{line_num}: ", line_num = line_num + 1)?;
-                self.write_all(line)?;
+                let gutter = Gutter::new(&[], line_num, &[": "]);
+                match self.1 {
+                    Some(wrap) => {
+                        let digits = Gutter::digits(line_num);
+                        let continuation = Gutter::continuation(&["\n"], digits, &[" "]);
+                        write_wrapped_line(
+                            &mut self.0,
+                            gutter.as_bytes(),
+                            continuation.as_bytes(),
+                            line,
+                            content_width(wrap, Gutter::width(line_num)),
+                            None,
+                        )?;
+                    }
+                    None => write_all_vectored(
+                        &mut self.0,
+                        &mut [IoSlice::new(gutter.as_bytes()), IoSlice::new(line)],
+                    )?,
+                }
             }
         }
 
@@ -34,12 +59,23 @@ impl<W: Write> OutputWriter for Writer<W> {
         if !first_line {
             writeln!(self)?;
         }
-        let prefix = match line_selector.raw {
-            RawLineSelector::Single(_) => "Line",
-            RawLineSelector::Range(..) => "Lines",
-            RawLineSelector::RangeWithStep(..) => "Lines",
-        };
+        let prefix = i18n::message(
+            match line_selector.raw {
+                RawLineSelector::Single(_) => "line-header",
+                RawLineSelector::Range(..) => "lines-header",
+                RawLineSelector::RangeWithStep(..) => "lines-header",
+                RawLineSelector::Random(..) => "lines-header",
+                RawLineSelector::RangeWithCount(..) => "lines-header",
+            },
+            &[],
+        );
         writeln!(self, "{prefix}: {}", line_selector.raw)?;
         Ok(())
     }
+
+    fn print_ruler(&mut self, line_num: usize, ruler: &str) -> anyhow::Result<()> {
+        let padding = Gutter::width(line_num);
+        writeln!(self, "{:padding$}{ruler}", "")?;
+        Ok(())
+    }
 }
@@ -0,0 +1,217 @@
+use crate::line_selector::LineSelector;
+use crate::output::{Line, OutputWriter, split_line_ending};
+use anyhow::Context;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// A template of `--format-str` (e.g.: `{file}\t{num}\t{line}`), parsed once up front.
+#[derive(Clone, Debug)]
+pub(crate) struct Template(Vec<Piece>);
+
+#[derive(Clone, Debug)]
+enum Piece {
+    Text(String),
+    /// `{num}`, the one-based line number
+    Num,
+    /// `{line}`, the line without its line ending
+    Line,
+    /// `{file}`, the path of the file
+    File,
+    /// `{offset}`, the byte offset of the start of the line
+    Offset,
+    /// `{selected}`, `true` for selected lines and `false` for context lines
+    Selected,
+}
+
+impl Template {
+    /// Parses `s`, where `{num}`, `{line}`, `{file}`, `{offset}`, and `{selected}` are
+    /// placeholders, `{{` and `}}` are literal braces, and `\t`, `\n`, `\r`, `\0`, and `\\` are
+    /// escapes.
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Self> {
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let (name, rest) = chars
+                        .as_str()
+                        .split_once('}')
+                        .with_context(|| format!("Unclosed `{{` in `{s}`"))?;
+                    let piece = match name {
+                        "num" => Piece::Num,
+                        "line" => Piece::Line,
+                        "file" => Piece::File,
+                        "offset" => Piece::Offset,
+                        "selected" => Piece::Selected,
+                        _ => anyhow::bail!(
+                            "Unknown placeholder `{{{name}}}` (expected `{{num}}`, `{{line}}`, \
+                            `{{file}}`, `{{offset}}`, or `{{selected}}`)"
+                        ),
+                    };
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(piece);
+                    chars = rest.chars();
+                }
+                '}' => anyhow::bail!("Unmatched `}}` in `{s}` (use `}}}}` for a literal `}}`)"),
+                '\\' => text.push(match chars.next() {
+                    Some('t') => '\t',
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('0') => '\0',
+                    Some('\\') => '\\',
+                    _ => anyhow::bail!(
+                        "Unknown escape in `{s}` (expected `\\t`, `\\n`, `\\r`, `\\0`, or `\\\\`)"
+                    ),
+                }),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+        Ok(Self(pieces))
+    }
+
+    /// Returns whether the template has an `{offset}` placeholder, whose offsets must be found
+    /// before printing.
+    pub(crate) fn uses_offset(&self) -> bool {
+        self.0.iter().any(|piece| matches!(piece, Piece::Offset))
+    }
+}
+
+/// Prints each line as its `--format-str` template filled in, followed by the line ending of the
+/// line. Headers, separators, and other decorations aren't printed.
+pub(crate) struct Writer<W: Write> {
+    out: W,
+    template: Template,
+    path: String,
+    /// The byte offsets of the printed lines, by zero-based line number, if the template has an
+    /// `{offset}` placeholder
+    offsets: HashMap<usize, u64>,
+    /// The `--offset-base` added to the line numbers given to `print_line`
+    offset_base: usize,
+}
+
+impl<W: Write> Writer<W> {
+    pub(crate) fn new(
+        out: W,
+        template: Template,
+        path: String,
+        offsets: HashMap<usize, u64>,
+        offset_base: usize,
+    ) -> Self {
+        Self {
+            out,
+            template,
+            path,
+            offsets,
+            offset_base,
+        }
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl<W: Write> OutputWriter for Writer<W> {
+    fn print_line(&mut self, line: Line<'_>) -> anyhow::Result<()> {
+        let (line_num, line, selected) = match line {
+            Line::Context { line_num, line } => (line_num, line, false),
+            Line::Selected { line_num, line } => (line_num, line, true),
+        };
+        let (content, line_ending) = split_line_ending(line);
+
+        for piece in &self.template.0 {
+            match piece {
+                Piece::Text(text) => self.out.write_all(text.as_bytes())?,
+                Piece::Num => write!(self.out, "{}", line_num.saturating_add(1))?,
+                Piece::Line => self.out.write_all(content)?,
+                Piece::File => self.out.write_all(self.path.as_bytes())?,
+                Piece::Offset => {
+                    let offset = self.offsets.get(&(line_num - self.offset_base));
+                    if let Some(offset) = offset {
+                        write!(self.out, "{offset}")?;
+                    }
+                }
+                Piece::Selected => write!(self.out, "{selected}")?,
+            }
+        }
+        self.out.write_all(line_ending)?;
+        Ok(())
+    }
+
+    fn print_line_selector_header(
+        &mut self,
+        _line_selector: &LineSelector,
+        _first_line: bool,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_group_separator(&mut self, _separator: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_ruler(&mut self, _line_num: usize, _ruler: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn print_file_boundary(&mut self, _marker: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_placeholders() {
+        let template = Template::parse("{file}\\t{num}:{offset}:{selected} {{{line}}}").unwrap();
+        let offsets = HashMap::from([(0, 0), (1, 4)]);
+        let mut writer = Writer::new(Vec::new(), template, "a.txt".to_string(), offsets, 0);
+        writer
+            .print_line(Line::Context {
+                line_num: 0,
+                line: b"one\n",
+            })
+            .unwrap();
+        writer
+            .print_line(Line::Selected {
+                line_num: 1,
+                line: b"two\r\n",
+            })
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(writer.out).unwrap(),
+            "a.txt\t1:0:false {one}\na.txt\t2:4:true {two}\r\n"
+        );
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert!(Template::parse("{nums}").is_err());
+        assert!(Template::parse("{num").is_err());
+        assert!(Template::parse("num}").is_err());
+        assert!(Template::parse("\\x").is_err());
+        assert!(Template::parse("{{num}}").is_ok_and(|template| !template.uses_offset()));
+    }
+}
@@ -0,0 +1,195 @@
+use std::ffi::OsStr;
+use std::io::IsTerminal;
+
+/// The number of colors a terminal can display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ColorSupport {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+/// What a terminal can display, guessed from the `COLORTERM` and `TERM` environment variables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Capabilities {
+    /// Whether the terminal understands escape sequences at all, which `TERM=dumb` ones and old
+    /// Windows consoles don't
+    pub(crate) styles: bool,
+    pub(crate) colors: ColorSupport,
+    pub(crate) italic: bool,
+    pub(crate) dim: bool,
+}
+
+/// Terminals that display neither italics nor dim text. Their names are matched up to the first
+/// `-`, so `vt100` covers `vt100-am` as well.
+const WITHOUT_ITALIC_AND_DIM: &[&str] = &["ansi", "cons25", "cygwin", "linux", "vt100", "vt220"];
+
+impl Capabilities {
+    pub(crate) fn detect() -> Self {
+        let mut capabilities = Self::from_env(
+            std::env::var("COLORTERM").ok().as_deref(),
+            std::env::var("TERM").ok().as_deref(),
+        );
+        // consoles on Windows only interpret escape sequences once virtual terminal processing is
+        // turned on, which fails on consoles older than Windows 10. Escape sequences written to a
+        // pipe or a file are left to whatever reads them
+        if capabilities.styles
+            && std::io::stdout().is_terminal()
+            && anstyle_query::windows::enable_ansi_colors() == Some(false)
+        {
+            capabilities.styles = false;
+        }
+        capabilities
+    }
+
+    /// The capabilities assumed by `--canonical`, which are the same as without any environment
+    /// variables.
+    pub(crate) fn canonical() -> Self {
+        Self::from_env(None, None)
+    }
+
+    /// Terminals are assumed to support 16 colors, italics, and dim text, unless their
+    /// environment variables say otherwise.
+    fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        let colors = if matches!(colorterm, Some("truecolor" | "24bit"))
+            || term.is_some_and(|term| term.ends_with("-direct"))
+        {
+            ColorSupport::TrueColor
+        } else if term.is_some_and(|term| term.contains("256color")) {
+            ColorSupport::Ansi256
+        } else {
+            ColorSupport::Ansi16
+        };
+
+        let base_term = term.map(|term| term.split('-').next().unwrap_or(term));
+        let has_italic_and_dim =
+            !base_term.is_some_and(|base_term| WITHOUT_ITALIC_AND_DIM.contains(&base_term));
+
+        Self {
+            styles: term != Some("dumb"),
+            colors,
+            italic: has_italic_and_dim,
+            dim: has_italic_and_dim,
+        }
+    }
+}
+
+/// The number of columns assumed when the width of the terminal is unknown, and by `--canonical`.
+pub(crate) const DEFAULT_WIDTH: usize = 80;
+
+/// Returns the number of columns of the terminal, from `COLUMNS` or the terminal that stdout or
+/// stderr is connected to (stdout is a pipe when paging).
+pub(crate) fn width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0)
+        .or_else(query_width)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+#[cfg(unix)]
+fn query_width() -> Option<usize> {
+    [libc::STDOUT_FILENO, libc::STDERR_FILENO]
+        .into_iter()
+        .find_map(|fd| {
+            // SAFETY: `winsize` is a plain C struct for which all zeros is a valid value
+            let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+            // SAFETY: `size` is a valid pointer to a `winsize`, and `fd` not being a terminal
+            // only makes the call fail
+            let is_terminal = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } == 0;
+            (is_terminal && size.ws_col > 0).then_some(usize::from(size.ws_col))
+        })
+}
+
+#[cfg(not(unix))]
+fn query_width() -> Option<usize> {
+    None
+}
+
+/// Returns whether colors are turned on or off by the `NO_COLOR`, `CLICOLOR_FORCE`, and `CLICOLOR`
+/// environment variables (see https://no-color.org and https://bixense.com/clicolors), or `None`
+/// if they're left to `--color=auto`.
+pub(crate) fn color_from_env() -> Option<bool> {
+    color_from_vars(
+        std::env::var_os("NO_COLOR").as_deref(),
+        std::env::var_os("CLICOLOR_FORCE").as_deref(),
+        std::env::var_os("CLICOLOR").as_deref(),
+    )
+}
+
+/// `NO_COLOR` turns colors off if it's set to anything but an empty string, and takes precedence
+/// over `CLICOLOR_FORCE`, which turns them on unless it's `0`. `CLICOLOR=0` turns them off.
+fn color_from_vars(
+    no_color: Option<&OsStr>,
+    clicolor_force: Option<&OsStr>,
+    clicolor: Option<&OsStr>,
+) -> Option<bool> {
+    if no_color.is_some_and(|no_color| !no_color.is_empty()) {
+        Some(false)
+    } else if clicolor_force.is_some_and(|force| !force.is_empty() && force != "0") {
+        Some(true)
+    } else if clicolor.is_some_and(|clicolor| clicolor == "0") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_support() {
+        for (colorterm, term, expected) in [
+            (
+                Some("truecolor"),
+                Some("xterm-256color"),
+                ColorSupport::TrueColor,
+            ),
+            (Some("24bit"), None, ColorSupport::TrueColor),
+            (None, Some("xterm-direct"), ColorSupport::TrueColor),
+            (None, Some("screen-256color"), ColorSupport::Ansi256),
+            (Some("yes"), Some("xterm"), ColorSupport::Ansi16),
+            (None, None, ColorSupport::Ansi16),
+        ] {
+            assert_eq!(Capabilities::from_env(colorterm, term).colors, expected);
+        }
+    }
+
+    #[test]
+    fn attributes() {
+        let capabilities = Capabilities::from_env(None, Some("xterm-256color"));
+        assert!(capabilities.styles && capabilities.italic && capabilities.dim);
+
+        let capabilities = Capabilities::from_env(None, Some("linux"));
+        assert!(capabilities.styles && !capabilities.italic && !capabilities.dim);
+
+        let capabilities = Capabilities::from_env(None, Some("vt100-am"));
+        assert!(!capabilities.italic && !capabilities.dim);
+
+        let capabilities = Capabilities::from_env(Some("truecolor"), Some("dumb"));
+        assert!(!capabilities.styles);
+    }
+
+    #[test]
+    fn color_env_vars() {
+        let vars = |no_color: Option<&str>, force: Option<&str>, clicolor: Option<&str>| {
+            color_from_vars(
+                no_color.map(OsStr::new),
+                force.map(OsStr::new),
+                clicolor.map(OsStr::new),
+            )
+        };
+        assert_eq!(vars(None, None, None), None);
+        assert_eq!(vars(Some("1"), None, None), Some(false));
+        assert_eq!(vars(Some(""), None, None), None);
+        assert_eq!(vars(None, Some("1"), None), Some(true));
+        assert_eq!(vars(None, Some("0"), None), None);
+        assert_eq!(vars(Some("1"), Some("1"), None), Some(false));
+        assert_eq!(vars(None, None, Some("0")), Some(false));
+        assert_eq!(vars(None, None, Some("1")), None);
+        assert_eq!(vars(None, Some("1"), Some("0")), Some(true));
+    }
+}
@@ -0,0 +1,749 @@
+use crate::cli::{Emphasis, ThemeChoice};
+use crate::output::highlight::Highlighter;
+use crate::output::terminal::{Capabilities, ColorSupport};
+use crate::output::{CLEAR, split_line_ending};
+use crate::paths;
+use anyhow::Context;
+use regex::bytes::Regex;
+use std::borrow::Cow;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A color of the terminal's palette, which terminals adapt to their own look, or a 24-bit color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    const NAMED: [(&str, Color); 8] = [
+        ("black", Color::Black),
+        ("red", Color::Red),
+        ("green", Color::Green),
+        ("yellow", Color::Yellow),
+        ("blue", Color::Blue),
+        ("magenta", Color::Magenta),
+        ("cyan", Color::Cyan),
+        ("white", Color::White),
+    ];
+
+    /// Returns the SGR parameters that set this color as the foreground or the background. RGB
+    /// colors are downgraded to the closest color the terminal can display.
+    fn params(self, color_support: ColorSupport, background: bool) -> String {
+        let (base, bright_base, extended) = if background {
+            (40, 100, 48)
+        } else {
+            (30, 90, 38)
+        };
+        let palette_index = match self {
+            Color::Black => 0,
+            Color::Red => 1,
+            Color::Green => 2,
+            Color::Yellow => 3,
+            Color::Blue => 4,
+            Color::Magenta => 5,
+            Color::Cyan => 6,
+            Color::White => 7,
+            Color::Rgb(red, green, blue) => match color_support {
+                ColorSupport::TrueColor => return format!("{extended};2;{red};{green};{blue}"),
+                ColorSupport::Ansi256 => {
+                    return format!("{extended};5;{}", rgb_to_ansi256(red, green, blue));
+                }
+                ColorSupport::Ansi16 => rgb_to_ansi16(red, green, blue),
+            },
+        };
+        match palette_index {
+            0..=7 => (base + palette_index).to_string(),
+            _ => (bright_base + palette_index - 8).to_string(),
+        }
+    }
+}
+
+/// Maps an RGB color to the closest color of the 6x6x6 cube or the gray ramp of the 256-color
+/// palette.
+fn rgb_to_ansi256(red: u8, green: u8, blue: u8) -> u8 {
+    if red == green && green == blue {
+        return match red {
+            0..8 => 16,
+            249.. => 231,
+            _ => 232 + ((u16::from(red) - 8) * 24 / 247) as u8,
+        };
+    }
+    // the cube levels are 0, 95, 135, 175, 215, and 255
+    let level = |component: u8| match component {
+        0..48 => 0,
+        48..115 => 1,
+        _ => (component - 35) / 40,
+    };
+    16 + 36 * level(red) + 6 * level(green) + level(blue)
+}
+
+/// Maps an RGB color to the index of the closest of the 16 basic colors, as xterm displays them.
+fn rgb_to_ansi16(red: u8, green: u8, blue: u8) -> u8 {
+    const PALETTE: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    let distance = |(r, g, b): (u8, u8, u8)| {
+        let square = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+        square(r, red) + square(g, green) + square(b, blue)
+    };
+    (0..16)
+        .min_by_key(|&i| distance(PALETTE[i as usize]))
+        .expect("the palette isn't empty")
+}
+
+impl FromStr for Color {
+    type Err = anyhow::Error;
+
+    /// Parses a color name like `red`, or a hex color like `#ff5f87`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(hex) = s.strip_prefix('#') {
+            if hex.len() != 6 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                anyhow::bail!("Invalid hex color `{s}` (expected a color like `#ff5f87`)");
+            }
+            let component = |i: usize| {
+                u8::from_str_radix(&hex[i..i + 2], 16).expect("we checked the hex digits")
+            };
+            return Ok(Color::Rgb(component(0), component(2), component(4)));
+        }
+        Self::NAMED
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|&(_, color)| color)
+            .with_context(|| format!("Unknown color `{s}`"))
+    }
+}
+
+/// The colors and attributes of a piece of colored output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Style {
+    pub(crate) foreground: Option<Color>,
+    pub(crate) background: Option<Color>,
+    pub(crate) bold: bool,
+    pub(crate) dim: bool,
+    pub(crate) italic: bool,
+    pub(crate) underline: bool,
+    pub(crate) reverse: bool,
+}
+
+impl Style {
+    const fn new(foreground: Option<Color>, bold: bool) -> Self {
+        Self {
+            foreground,
+            background: None,
+            bold,
+            dim: false,
+            italic: false,
+            underline: false,
+            reverse: false,
+        }
+    }
+
+    const fn on(self, background: Color) -> Self {
+        Self {
+            background: Some(background),
+            ..self
+        }
+    }
+
+    /// Returns the escape sequence that turns this style on, or an empty string for the default
+    /// style. Colors and attributes that the terminal can't display are downgraded or skipped.
+    pub(crate) fn escape(&self, capabilities: &Capabilities) -> String {
+        let mut params = Vec::new();
+        if let Some(foreground) = self.foreground {
+            params.push(foreground.params(capabilities.colors, false));
+        }
+        if let Some(background) = self.background {
+            params.push(background.params(capabilities.colors, true));
+        }
+        if self.bold {
+            params.push("1".to_owned());
+        }
+        if self.dim && capabilities.dim {
+            params.push("2".to_owned());
+        }
+        if self.italic && capabilities.italic {
+            params.push("3".to_owned());
+        }
+        if self.underline {
+            params.push("4".to_owned());
+        }
+        if self.reverse {
+            params.push("7".to_owned());
+        }
+
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("\x1b[{}m", params.join(";"))
+        }
+    }
+}
+
+impl FromStr for Style {
+    type Err = anyhow::Error;
+
+    /// Parses a space-separated list of attributes (`bold`, `dim`, `italic`, `underline`, and
+    /// `reverse`), at most one
+    /// foreground color, and at most one background color preceded by `on`, like `#ff5f87 bold`
+    /// or `white on blue`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut style = Style::default();
+        let mut words = s.split_whitespace();
+        while let Some(word) = words.next() {
+            if word.eq_ignore_ascii_case("bold") {
+                style.bold = true;
+            } else if word.eq_ignore_ascii_case("dim") {
+                style.dim = true;
+            } else if word.eq_ignore_ascii_case("italic") {
+                style.italic = true;
+            } else if word.eq_ignore_ascii_case("underline") {
+                style.underline = true;
+            } else if word.eq_ignore_ascii_case("reverse") {
+                style.reverse = true;
+            } else if word.eq_ignore_ascii_case("on") {
+                let background = words.next().context("Expected a color after `on`")?;
+                if style.background.is_some() {
+                    anyhow::bail!("A style can't have more than one background color");
+                }
+                style.background = Some(background.parse()?);
+            } else if style.foreground.is_none() {
+                style.foreground = Some(word.parse()?);
+            } else {
+                anyhow::bail!("A style can't have more than one color");
+            }
+        }
+        Ok(style)
+    }
+}
+
+/// The styles used by the colored writers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Theme {
+    pub(crate) selected_line: Style,
+    /// Used instead of `selected_line` with `--emphasis=background`
+    pub(crate) selected_line_background: Style,
+    pub(crate) selected_line_number: Style,
+    pub(crate) context_line_number: Style,
+    pub(crate) header: Style,
+    /// Used for the text matching `--highlight-pattern`
+    pub(crate) matched_text: Style,
+    /// Whether the theme is made for terminals with a light background
+    pub(crate) light_background: bool,
+}
+
+impl Theme {
+    /// The default theme, made for terminals with a dark background.
+    pub(crate) const DARK: Self = Self {
+        selected_line: Style::new(Some(Color::Red), false),
+        selected_line_background: Style::new(None, false).on(Color::Rgb(0x44, 0x44, 0x44)),
+        selected_line_number: Style::new(Some(Color::Green), true),
+        context_line_number: Style::new(None, true),
+        header: Style::new(Some(Color::Cyan), true),
+        matched_text: Style::new(Some(Color::Black), true).on(Color::Yellow),
+        light_background: false,
+    };
+
+    /// A theme for terminals with a light background, avoiding the pale green and cyan of
+    /// [`Theme::DARK`].
+    pub(crate) const LIGHT: Self = Self {
+        selected_line: Style::new(Some(Color::Red), false),
+        selected_line_background: Style::new(None, false).on(Color::Rgb(0xdd, 0xdd, 0xdd)),
+        selected_line_number: Style::new(Some(Color::Blue), true),
+        context_line_number: Style::new(None, true),
+        header: Style::new(Some(Color::Magenta), true),
+        matched_text: Style::new(Some(Color::Black), true).on(Color::Yellow),
+        light_background: true,
+    };
+
+    /// Returns the theme picked by `--theme`. With `auto`, the terminal is only queried if
+    /// `is_terminal` is true, since a query can't be answered through a pipe.
+    pub(crate) fn resolve(choice: ThemeChoice, is_terminal: bool) -> Self {
+        match choice {
+            ThemeChoice::Dark => Self::DARK,
+            ThemeChoice::Light => Self::LIGHT,
+            ThemeChoice::Auto => match detect_light_background(is_terminal) {
+                Some(true) => Self::LIGHT,
+                Some(false) | None => Self::DARK,
+            },
+        }
+    }
+
+    /// Overrides the styles set in the theme file at `path`. A theme file is a TOML file with
+    /// the optional keys `selected`, `selected_background`, `selected_number`, `context_number`,
+    /// `header`, and `match`, e.g.: `selected = "#ff5f87 bold"`.
+    pub(crate) fn load_overrides(&mut self, path: &Path) -> anyhow::Result<()> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read theme file '{}'", paths::display(path)))?;
+        self.apply_overrides(&content)
+            .with_context(|| format!("Invalid theme file '{}'", paths::display(path)))
+    }
+
+    fn apply_overrides(&mut self, content: &str) -> anyhow::Result<()> {
+        let table: toml::Table = content.parse()?;
+        for (key, value) in table {
+            let Some(value) = value.as_str() else {
+                anyhow::bail!("The value of `{key}` must be a string");
+            };
+            self.set_style(&key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Overrides a style given as `NAME=STYLE` with `--style`, where NAME is a key of theme files
+    /// (e.g.: `selected=yellow bold`).
+    pub(crate) fn apply_style_override(&mut self, style_override: &str) -> anyhow::Result<()> {
+        let (key, value) = style_override
+            .split_once('=')
+            .context("Styles are given as NAME=STYLE (e.g.: `selected=yellow bold`)")?;
+        self.set_style(key.trim(), value)
+    }
+
+    fn set_style(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        let style = match key {
+            "selected" => &mut self.selected_line,
+            "selected_background" => &mut self.selected_line_background,
+            "selected_number" => &mut self.selected_line_number,
+            "context_number" => &mut self.context_line_number,
+            "header" => &mut self.header,
+            "match" => &mut self.matched_text,
+            _ => anyhow::bail!("Unknown key `{key}`"),
+        };
+        *style = value
+            .parse()
+            .with_context(|| format!("Invalid style for `{key}`"))?;
+        Ok(())
+    }
+
+    /// Renders the escape sequences of each style once, so they can be reused for every line.
+    pub(crate) fn palette(&self, capabilities: &Capabilities, emphasis: Emphasis) -> Palette {
+        let selected_line = match emphasis {
+            Emphasis::Color => self.selected_line,
+            Emphasis::Background => self.selected_line_background,
+            // these don't rely on telling colors apart, so they ignore the theme
+            Emphasis::Bold => Style::new(None, true),
+            Emphasis::Underline => Style {
+                underline: true,
+                ..Style::default()
+            },
+            Emphasis::Reverse => Style {
+                reverse: true,
+                ..Style::default()
+            },
+        };
+        Palette {
+            selected_line: selected_line.escape(capabilities),
+            fill_selected_line: matches!(emphasis, Emphasis::Background),
+            selected_line_number: self.selected_line_number.escape(capabilities),
+            context_line_number: self.context_line_number.escape(capabilities),
+            header: self.header.escape(capabilities),
+            matched_text: self.matched_text.escape(capabilities),
+            highlighter: None,
+            pattern: None,
+        }
+    }
+}
+
+/// The escape sequences of a [`Theme`].
+pub(crate) struct Palette {
+    pub(crate) selected_line: String,
+    /// Whether the style of selected lines spans the whole width of the terminal
+    pub(crate) fill_selected_line: bool,
+    pub(crate) selected_line_number: String,
+    pub(crate) context_line_number: String,
+    pub(crate) header: String,
+    pub(crate) matched_text: String,
+    /// Colors the tokens of printed lines, for `--highlight`
+    pub(crate) highlighter: Option<Highlighter>,
+    /// The text to color within printed lines, for `--highlight-pattern`
+    pub(crate) pattern: Option<Regex>,
+}
+
+impl Palette {
+    /// Colors the tokens of line `line_num` if `--highlight` is given, or the text matching
+    /// `--highlight-pattern`. The style of selected lines is restored after each match.
+    pub(crate) fn highlight<'l>(
+        &mut self,
+        line_num: usize,
+        line: &'l [u8],
+        is_selected: bool,
+    ) -> Cow<'l, [u8]> {
+        if let Some(highlighter) = &mut self.highlighter {
+            return highlighter.highlight(line_num, line);
+        }
+        let Some(pattern) = &self.pattern else {
+            return Cow::Borrowed(line);
+        };
+        // the line ending is left out, so that `$` matches before it
+        let (content, line_ending) = split_line_ending(line);
+        let mut matches = pattern
+            .find_iter(content)
+            .filter(|m| !m.is_empty())
+            .peekable();
+        if matches.peek().is_none() {
+            return Cow::Borrowed(line);
+        }
+        let line_style = if is_selected { &self.selected_line } else { "" };
+        let mut highlighted = Vec::with_capacity(line.len() + 16);
+        let mut end = 0;
+        for m in matches {
+            highlighted.extend_from_slice(&content[end..m.start()]);
+            highlighted.extend_from_slice(self.matched_text.as_bytes());
+            highlighted.extend_from_slice(m.as_bytes());
+            highlighted.extend_from_slice(CLEAR.as_bytes());
+            highlighted.extend_from_slice(line_style.as_bytes());
+            end = m.end();
+        }
+        highlighted.extend_from_slice(&content[end..]);
+        highlighted.extend_from_slice(line_ending);
+        Cow::Owned(highlighted)
+    }
+}
+
+/// Returns whether the terminal has a light background, or `None` if that can't be told.
+///
+/// The `COLORFGBG` environment variable, set by some terminals, is checked first. Otherwise, the
+/// terminal is asked for its background color with an OSC 11 query.
+fn detect_light_background(is_terminal: bool) -> Option<bool> {
+    if let Ok(colorfgbg) = std::env::var("COLORFGBG")
+        && let Some(is_light) = parse_colorfgbg(&colorfgbg)
+    {
+        return Some(is_light);
+    }
+    if !is_terminal || std::env::var_os("TERM").is_some_and(|term| term == "dumb") {
+        return None;
+    }
+    let (red, green, blue) = query_background_color()?;
+    // relative luminance, see https://www.w3.org/TR/WCAG20/#relativeluminancedef
+    Some(0.2126 * red + 0.7152 * green + 0.0722 * blue > 0.5)
+}
+
+/// Parses `COLORFGBG`, which looks like `15;0` (foreground and background) or `15;default;0`,
+/// into whether the background is light.
+fn parse_colorfgbg(colorfgbg: &str) -> Option<bool> {
+    let background: u8 = colorfgbg.rsplit(';').next()?.parse().ok()?;
+    // 7 is light gray and 9-15 are the bright colors, except 8, which is dark gray
+    match background {
+        0..=6 | 8 => Some(false),
+        7 | 9..=15 => Some(true),
+        _ => None,
+    }
+}
+
+/// Parses the reply to an OSC 11 query, like `\x1b]11;rgb:ffff/ffff/ffff\x07`, into red, green,
+/// and blue components between 0 and 1.
+fn parse_osc11_reply(reply: &[u8]) -> Option<(f64, f64, f64)> {
+    let reply = std::str::from_utf8(reply).ok()?;
+    let reply = reply
+        .strip_suffix('\x07')
+        .or_else(|| reply.strip_suffix("\x1b\\"))?;
+    let rgb = reply.strip_prefix("\x1b]11;rgb:")?;
+
+    // each component has 1 to 4 hex digits
+    let parse_component = |component: &str| {
+        if component.is_empty() || component.len() > 4 {
+            return None;
+        }
+        let max = (1u32 << (4 * component.len())) - 1;
+        let value = u32::from_str_radix(component, 16).ok()?;
+        Some(f64::from(value) / f64::from(max))
+    };
+    let mut components = rgb.split('/').map(parse_component);
+    let rgb = (
+        components.next()??,
+        components.next()??,
+        components.next()??,
+    );
+    components.next().is_none().then_some(rgb)
+}
+
+/// Asks the terminal for its background color, giving up if it doesn't reply quickly.
+#[cfg(unix)]
+fn query_background_color() -> Option<(f64, f64, f64)> {
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+
+    const TIMEOUT_MS: libc::c_int = 100;
+
+    let mut tty = std::fs::File::options()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    let fd = tty.as_raw_fd();
+
+    // the reply must be read without waiting for a newline and without echoing it
+    // SAFETY: `termios` is a plain C struct for which all zeros is a valid value
+    let mut original_termios: libc::termios = unsafe { std::mem::zeroed() };
+    // SAFETY: `fd` is an open file descriptor and `original_termios` is a valid pointer
+    if unsafe { libc::tcgetattr(fd, &mut original_termios) } != 0 {
+        return None;
+    }
+    let mut raw_termios = original_termios;
+    raw_termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+    // SAFETY: same as above
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw_termios) } != 0 {
+        return None;
+    }
+
+    let mut read_reply = || {
+        tty.write_all(b"\x1b]11;?\x07").ok()?;
+        let mut reply = Vec::new();
+        let mut byte = [0];
+        loop {
+            let mut pollfd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: `pollfd` is a valid pointer to exactly one `pollfd`
+            if unsafe { libc::poll(&mut pollfd, 1, TIMEOUT_MS) } != 1 {
+                return None;
+            }
+            tty.read_exact(&mut byte).ok()?;
+            reply.push(byte[0]);
+            if byte[0] == b'\x07' || reply.ends_with(b"\x1b\\") {
+                return Some(reply);
+            }
+            if reply.len() > 64 {
+                return None;
+            }
+        }
+    };
+    let reply = read_reply();
+
+    // SAFETY: same as above
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &original_termios) };
+
+    parse_osc11_reply(&reply?)
+}
+
+#[cfg(not(unix))]
+fn query_background_color() -> Option<(f64, f64, f64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(colors: ColorSupport) -> Capabilities {
+        Capabilities {
+            styles: true,
+            colors,
+            italic: true,
+            dim: true,
+        }
+    }
+
+    #[test]
+    fn escapes() {
+        let escape = |style: Style| style.escape(&capabilities(ColorSupport::Ansi16));
+        assert_eq!(escape(Style::new(Some(Color::Red), false)), "\x1b[31m");
+        assert_eq!(escape(Style::new(Some(Color::Green), true)), "\x1b[32;1m");
+        assert_eq!(escape(Style::new(None, true)), "\x1b[1m");
+        assert_eq!(escape(Style::default()), "");
+    }
+
+    #[test]
+    fn attribute_escapes() {
+        let style: Style = "italic dim bold".parse().unwrap();
+        assert_eq!(
+            style.escape(&capabilities(ColorSupport::Ansi16)),
+            "\x1b[1;2;3m"
+        );
+
+        let linux_console = Capabilities {
+            italic: false,
+            dim: false,
+            ..capabilities(ColorSupport::Ansi16)
+        };
+        assert_eq!(style.escape(&linux_console), "\x1b[1m");
+        let style: Style = "italic".parse().unwrap();
+        assert_eq!(style.escape(&linux_console), "");
+
+        let style: Style = "reverse underline".parse().unwrap();
+        assert_eq!(style.escape(&linux_console), "\x1b[4;7m");
+    }
+
+    #[test]
+    fn emphasis_ignores_theme_colors() {
+        let capabilities = capabilities(ColorSupport::Ansi16);
+        for theme in [Theme::DARK, Theme::LIGHT] {
+            let palette = |emphasis| theme.palette(&capabilities, emphasis).selected_line;
+            assert_eq!(palette(Emphasis::Bold), "\x1b[1m");
+            assert_eq!(palette(Emphasis::Underline), "\x1b[4m");
+            assert_eq!(palette(Emphasis::Reverse), "\x1b[7m");
+        }
+    }
+
+    #[test]
+    fn rgb_escapes() {
+        let style = Style::new(Some(Color::Rgb(0xff, 0x5f, 0x87)), true);
+        assert_eq!(
+            style.escape(&capabilities(ColorSupport::TrueColor)),
+            "\x1b[38;2;255;95;135;1m"
+        );
+        assert_eq!(
+            style.escape(&capabilities(ColorSupport::Ansi256)),
+            "\x1b[38;5;204;1m"
+        );
+        assert_eq!(
+            style.escape(&capabilities(ColorSupport::Ansi16)),
+            "\x1b[35;1m"
+        );
+    }
+
+    #[test]
+    fn background_escapes() {
+        let style = Style::new(None, false).on(Color::Rgb(0x44, 0x44, 0x44));
+        assert_eq!(
+            style.escape(&capabilities(ColorSupport::TrueColor)),
+            "\x1b[48;2;68;68;68m"
+        );
+        assert_eq!(
+            style.escape(&capabilities(ColorSupport::Ansi256)),
+            "\x1b[48;5;237m"
+        );
+        assert_eq!(
+            style.escape(&capabilities(ColorSupport::Ansi16)),
+            "\x1b[100m"
+        );
+
+        let style = Style::new(Some(Color::Yellow), true).on(Color::Blue);
+        assert_eq!(
+            style.escape(&capabilities(ColorSupport::Ansi16)),
+            "\x1b[33;44;1m"
+        );
+    }
+
+    #[test]
+    fn downgrade_to_256_colors() {
+        assert_eq!(rgb_to_ansi256(0, 0, 0), 16);
+        assert_eq!(rgb_to_ansi256(255, 255, 255), 231);
+        assert_eq!(rgb_to_ansi256(128, 128, 128), 243);
+        assert_eq!(rgb_to_ansi256(0, 0, 255), 21);
+        assert_eq!(rgb_to_ansi256(0xff, 0xaf, 0x00), 214);
+    }
+
+    #[test]
+    fn downgrade_to_16_colors() {
+        assert_eq!(rgb_to_ansi16(0, 0, 0), 0);
+        assert_eq!(rgb_to_ansi16(200, 10, 10), 1);
+        assert_eq!(rgb_to_ansi16(250, 250, 250), 15);
+        assert_eq!(rgb_to_ansi16(120, 120, 130), 8);
+    }
+
+    #[test]
+    fn parse_styles() {
+        assert_eq!(
+            "#FF5f87 bold".parse::<Style>().unwrap(),
+            Style::new(Some(Color::Rgb(0xff, 0x5f, 0x87)), true)
+        );
+        assert_eq!(
+            "Yellow".parse::<Style>().unwrap(),
+            Style::new(Some(Color::Yellow), false)
+        );
+        assert_eq!("".parse::<Style>().unwrap(), Style::default());
+        assert!("#ff5f8".parse::<Style>().is_err());
+        assert!("#gg5f87".parse::<Style>().is_err());
+        assert!("pink".parse::<Style>().is_err());
+        assert!("red blue".parse::<Style>().is_err());
+        assert_eq!(
+            "white on #444444".parse::<Style>().unwrap(),
+            Style::new(Some(Color::White), false).on(Color::Rgb(0x44, 0x44, 0x44))
+        );
+        assert!("on".parse::<Style>().is_err());
+        assert!("on red on blue".parse::<Style>().is_err());
+    }
+
+    #[test]
+    fn highlights_matches() {
+        let mut palette = Theme::DARK.palette(&capabilities(ColorSupport::Ansi16), Emphasis::Color);
+        palette.pattern = Some(Regex::new("o+|x*").unwrap());
+        assert_eq!(
+            palette.highlight(0, b"foo boo\n", false).as_ref(),
+            b"f\x1b[30;43;1moo\x1b[0m b\x1b[30;43;1moo\x1b[0m\n"
+        );
+        assert_eq!(
+            palette.highlight(0, b"foo", true).as_ref(),
+            b"f\x1b[30;43;1moo\x1b[0m\x1b[31m"
+        );
+        assert!(matches!(
+            palette.highlight(0, b"bar\n", true),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn theme_overrides() {
+        let mut theme = Theme::DARK;
+        theme
+            .apply_overrides("selected = \"#ff5f87\"\nheader = \"bold\"\nmatch = \"red\"\n")
+            .unwrap();
+        assert_eq!(
+            theme.selected_line,
+            Style::new(Some(Color::Rgb(0xff, 0x5f, 0x87)), false)
+        );
+        assert_eq!(theme.header, Style::new(None, true));
+        assert_eq!(theme.matched_text, Style::new(Some(Color::Red), false));
+        assert_eq!(theme.selected_line_number, Theme::DARK.selected_line_number);
+
+        theme.apply_style_override("header=yellow bold").unwrap();
+        assert_eq!(theme.header, Style::new(Some(Color::Yellow), true));
+        assert!(theme.apply_style_override("header").is_err());
+        assert!(theme.apply_style_override("unknown=red").is_err());
+
+        assert!(theme.apply_overrides("selected = 1").is_err());
+        assert!(theme.apply_overrides("unknown = \"red\"").is_err());
+        assert!(theme.apply_overrides("header = \"pink\"").is_err());
+    }
+
+    #[test]
+    fn colorfgbg() {
+        assert_eq!(parse_colorfgbg("15;0"), Some(false));
+        assert_eq!(parse_colorfgbg("0;15"), Some(true));
+        assert_eq!(parse_colorfgbg("0;default;7"), Some(true));
+        assert_eq!(parse_colorfgbg("0;8"), Some(false));
+        assert_eq!(parse_colorfgbg("default;default"), None);
+        assert_eq!(parse_colorfgbg(""), None);
+    }
+
+    #[test]
+    fn osc11_reply() {
+        assert_eq!(
+            parse_osc11_reply(b"\x1b]11;rgb:ffff/ffff/ffff\x07"),
+            Some((1.0, 1.0, 1.0))
+        );
+        assert_eq!(
+            parse_osc11_reply(b"\x1b]11;rgb:0000/8080/ff\x1b\\"),
+            Some((0.0, f64::from(0x8080) / f64::from(0xffff), 1.0))
+        );
+        assert_eq!(parse_osc11_reply(b"\x1b]11;rgb:ffff/ffff\x07"), None);
+        assert_eq!(parse_osc11_reply(b"\x1b]11;rgb:ffff/ffff/ffff"), None);
+        assert_eq!(parse_osc11_reply(b"\x1b]11;rgb:fffff/0/0\x07"), None);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::paths;
+use anyhow::Context;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// The file given to `--output`, or edited by `--in-place`, which is written aside then renamed
+/// over `path` once the whole output is written, so that `path` never holds a partial output
+/// (e.g.: after an error, or when it's also the input file). The file at `path`, if any, keeps
+/// its permissions and, where the user is allowed to keep it, its owner. If `path` is a symbolic
+/// link, the file it points to is replaced, and the link is kept.
+///
+/// The output is written to a new file with a random name, which is never opened through a link
+/// planted there by someone else, and which is removed if the output isn't persisted.
+pub(crate) struct OutputFile {
+    file: BufWriter<NamedTempFile>,
+    path: PathBuf,
+}
+
+impl OutputFile {
+    pub(crate) fn create(path: &Path, buffer_size: usize) -> anyhow::Result<Self> {
+        // the output is written next to the file the link points to, so that renaming it replaces
+        // that file instead of the link
+        let path = match fs::symlink_metadata(paths::for_opening(path)) {
+            Ok(metadata) if metadata.is_symlink() => fs::canonicalize(paths::for_opening(path))
+                .with_context(|| format!("Couldn't resolve `{}`", paths::display(path)))?,
+            _ => path.to_owned(),
+        };
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut prefix = path.file_name().unwrap_or_default().to_owned();
+        prefix.push(".");
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(&prefix).suffix(".tmp");
+        // new files get the usual permissions, restricted by the umask, instead of the private
+        // ones of temporary files
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            builder.permissions(fs::Permissions::from_mode(0o666));
+        }
+        let file = builder
+            .tempfile_in(paths::for_opening(dir))
+            .with_context(|| format!("Couldn't create a file in `{}`", paths::display(dir)))?;
+        if let Ok(metadata) = fs::metadata(paths::for_opening(&path)) {
+            // only privileged users can give a file away, so the owner is kept on a best effort
+            // basis, like `sed -i` does
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                let _ = std::os::unix::fs::fchown(
+                    file.as_file(),
+                    Some(metadata.uid()),
+                    Some(metadata.gid()),
+                );
+            }
+            file.as_file()
+                .set_permissions(metadata.permissions())
+                .with_context(|| {
+                    format!(
+                        "Couldn't set permissions of `{}`",
+                        paths::display(file.path())
+                    )
+                })?;
+        }
+        Ok(Self {
+            file: BufWriter::with_capacity(buffer_size, file),
+            path,
+        })
+    }
+
+    /// Moves the written output to its path, replacing the file there if any.
+    pub(crate) fn persist(self) -> anyhow::Result<()> {
+        let file = self
+            .file
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)
+            .and_then(|file| file.as_file().sync_all().map(|()| file))
+            .with_context(|| format!("Failed to write `{}`", paths::display(&self.path)))?;
+        file.persist(paths::for_opening(&self.path))
+            .with_context(|| format!("Couldn't write `{}`", paths::display(&self.path)))?;
+        Ok(())
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.file.write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_file_once_persisted() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("out");
+        fs::write(&path, "old").unwrap();
+
+        let mut output = OutputFile::create(&path, 8).unwrap();
+        output.write_all(b"new output").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        output.persist().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new output");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("out");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o751)).unwrap();
+
+        OutputFile::create(&path, 8).unwrap().persist().unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o751);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replaces_the_target_of_symlinks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let target = dir.path().join("target");
+        let link = dir.path().join("link");
+        fs::write(&target, "old").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let mut output = OutputFile::create(&link, 8).unwrap();
+        output.write_all(b"new output").unwrap();
+        output.persist().unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new output");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_owner() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("out");
+        fs::write(&path, "old").unwrap();
+        // only privileged users can give the file away, otherwise it keeps its current owner
+        let _ = std::os::unix::fs::chown(&path, Some(4242), Some(4242));
+        let metadata = fs::metadata(&path).unwrap();
+
+        OutputFile::create(&path, 8).unwrap().persist().unwrap();
+        let persisted = fs::metadata(&path).unwrap();
+        assert_eq!(
+            (persisted.uid(), persisted.gid()),
+            (metadata.uid(), metadata.gid())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_at_temporary_paths_are_not_followed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let victim = dir.path().join("victim");
+        fs::write(&victim, "precious").unwrap();
+        let path = dir.path().join("out");
+        // where the output used to be written aside
+        let planted = dir.path().join(format!("out.{}.tmp", std::process::id()));
+        std::os::unix::fs::symlink(&victim, &planted).unwrap();
+
+        let mut output = OutputFile::create(&path, 8).unwrap();
+        output.write_all(b"new output").unwrap();
+        output.persist().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new output");
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "precious");
+        assert!(fs::symlink_metadata(&planted).unwrap().is_symlink());
+    }
+
+    #[test]
+    fn dropped_output_is_removed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("out");
+        let mut output = OutputFile::create(&path, 8).unwrap();
+        output.write_all(b"partial").unwrap();
+        drop(output);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
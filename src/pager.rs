@@ -0,0 +1,121 @@
+use crate::cli::When;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The pager used when `PAGER` isn't set. `-R` shows colors, `-F` quits right away if the output
+/// fits on one screen, and `-X` leaves the output on the screen after quitting.
+const DEFAULT_PAGER: &str = "less -RFX";
+
+/// Whether stdout was redirected to a pager.
+static IS_ACTIVE: AtomicBool = AtomicBool::new(false);
+/// Whether the pager displays the output on a terminal, since stdout is no longer one once it's
+/// redirected to the pager.
+static IS_ON_TERMINAL: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn is_active() -> bool {
+    IS_ACTIVE.load(Ordering::Relaxed)
+}
+
+pub(crate) fn is_on_terminal() -> bool {
+    IS_ON_TERMINAL.load(Ordering::Relaxed)
+}
+
+/// A pager that stdout is redirected to. Dropping it closes the pager's input and waits for the
+/// user to quit it.
+pub(crate) struct Pager {
+    child: std::process::Child,
+    #[cfg(unix)]
+    original_stdout: std::os::fd::OwnedFd,
+}
+
+/// Starts `PAGER` (`less -RFX` by default) and redirects stdout to it, if `paging` calls for it.
+/// With `auto`, a pager is only used if `is_terminal` is true.
+///
+/// Returns `None` if paging is off, `PAGER` is empty, or the pager can't be started, in which case
+/// the output goes straight to stdout.
+pub(crate) fn start(paging: When, is_terminal: bool) -> Option<Pager> {
+    let use_pager = match paging {
+        When::Auto => is_terminal,
+        When::Always => true,
+        When::Never => false,
+    };
+    // stdout can only be redirected on unix
+    if !use_pager || cfg!(not(unix)) {
+        return None;
+    }
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_owned());
+    let mut words = pager.split_whitespace();
+    let program = words.next()?;
+    let child = std::process::Command::new(program)
+        .args(words)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .ok()?;
+    let pager = redirect_stdout(child)?;
+    IS_ACTIVE.store(true, Ordering::Relaxed);
+    IS_ON_TERMINAL.store(is_terminal, Ordering::Relaxed);
+    Some(pager)
+}
+
+#[cfg(unix)]
+fn redirect_stdout(mut child: std::process::Child) -> Option<Pager> {
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    let stdin = child.stdin.take().expect("the pager's stdin is piped");
+    // SAFETY: `dup` has no preconditions
+    let original_stdout = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if original_stdout < 0 {
+        // the pager exits once its input is closed
+        drop(stdin);
+        let _ = child.wait();
+        return None;
+    }
+    // SAFETY: `original_stdout` is a new descriptor owned by nothing else
+    let original_stdout = unsafe { std::os::fd::OwnedFd::from_raw_fd(original_stdout) };
+    // SAFETY: `dup2` has no preconditions. stdout gets its own copy of the pipe, so the pager
+    // keeps its input after `stdin` is dropped
+    if unsafe { libc::dup2(stdin.as_raw_fd(), libc::STDOUT_FILENO) } < 0 {
+        drop(stdin);
+        let _ = child.wait();
+        return None;
+    }
+    Some(Pager {
+        child,
+        original_stdout,
+    })
+}
+
+#[cfg(not(unix))]
+fn redirect_stdout(_child: std::process::Child) -> Option<Pager> {
+    unreachable!("pagers aren't started on this platform")
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        use std::io::Write;
+
+        let _ = std::io::stdout().flush();
+        // restoring stdout closes the last copy of the pipe, which ends the pager's input
+        #[cfg(unix)]
+        {
+            use std::os::fd::AsRawFd;
+            // SAFETY: both descriptors are valid
+            unsafe { libc::dup2(self.original_stdout.as_raw_fd(), libc::STDOUT_FILENO) };
+        }
+        let _ = self.child.wait();
+    }
+}
+
+/// Returns whether `err` was caused by the pager exiting before all the output was written to
+/// it, e.g.: because the user quit it early.
+pub(crate) fn is_closed_early(err: &anyhow::Error) -> bool {
+    is_active() && is_broken_pipe(err)
+}
+
+/// Returns whether `err` was caused by writing to a pipe that was closed by its reader.
+pub(crate) fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|err| err.kind() == std::io::ErrorKind::BrokenPipe)
+    })
+}
@@ -0,0 +1,105 @@
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::path::Path;
+
+/// Displays a path in error messages and output. On Windows, the `\\?\` prefix of verbatim paths
+/// (which are needed for paths longer than 260 characters) is dropped, so the path looks the way
+/// users typed it.
+pub(crate) struct DisplayPath<'a>(&'a Path);
+
+pub(crate) fn display(path: &Path) -> DisplayPath<'_> {
+    DisplayPath(path)
+}
+
+impl Display for DisplayPath<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = self.0.to_string_lossy();
+        if cfg!(windows) {
+            f.write_str(&strip_verbatim_prefix(&path))
+        } else {
+            f.write_str(&path)
+        }
+    }
+}
+
+/// Returns the path to pass to the OS when opening `path`. Windows doesn't normalize verbatim
+/// paths, so forward slashes in them must be turned into backslashes beforehand.
+pub(crate) fn for_opening(path: &Path) -> Cow<'_, Path> {
+    if cfg!(windows)
+        && let Some(path_str) = path.to_str()
+        && let Cow::Owned(normalized) = normalize_verbatim_separators(path_str)
+    {
+        return Cow::Owned(normalized.into());
+    }
+    Cow::Borrowed(path)
+}
+
+/// Turns `\\?\C:\dir\file` into `C:\dir\file`, and `\\?\UNC\server\share` into
+/// `\\server\share`. Other verbatim paths, like `\\?\Volume{...}\`, have no shorter form and are
+/// kept as is.
+fn strip_verbatim_prefix(path: &str) -> Cow<'_, str> {
+    let Some(rest) = path.strip_prefix(r"\\?\") else {
+        return Cow::Borrowed(path);
+    };
+    if let Some(unc) = rest.strip_prefix(r"UNC\") {
+        return Cow::Owned(format!(r"\\{unc}"));
+    }
+    match rest.as_bytes() {
+        [drive, b':', ..] if drive.is_ascii_alphabetic() => Cow::Borrowed(rest),
+        _ => Cow::Borrowed(path),
+    }
+}
+
+fn normalize_verbatim_separators(path: &str) -> Cow<'_, str> {
+    if path.starts_with(r"\\?\") && path.contains('/') {
+        Cow::Owned(path.replace('/', r"\"))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_verbatim_prefixes() {
+        for (path, expected) in [
+            (r"\\?\C:\logs\app.log", r"C:\logs\app.log"),
+            (r"\\?\UNC\server\share\app.log", r"\\server\share\app.log"),
+            (r"\\?\Volume{1234}\app.log", r"\\?\Volume{1234}\app.log"),
+            (r"\\server\share\app.log", r"\\server\share\app.log"),
+            (r"C:\logs\app.log", r"C:\logs\app.log"),
+            ("logs/app.log", "logs/app.log"),
+        ] {
+            assert_eq!(strip_verbatim_prefix(path), expected);
+        }
+    }
+
+    #[test]
+    fn long_verbatim_path() {
+        let long_path = format!(r"\\?\C:\{}\app.log", "d".repeat(300));
+        assert_eq!(strip_verbatim_prefix(&long_path), &long_path[4..]);
+    }
+
+    #[test]
+    fn normalize_separators() {
+        assert_eq!(
+            normalize_verbatim_separators(r"\\?\C:\logs/app.log"),
+            r"\\?\C:\logs\app.log"
+        );
+        assert_eq!(
+            normalize_verbatim_separators("logs/app.log"),
+            "logs/app.log"
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn display_verbatim_path() {
+        assert_eq!(
+            display(Path::new(r"\\?\C:\logs\app.log")).to_string(),
+            r"C:\logs\app.log"
+        );
+    }
+}
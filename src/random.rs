@@ -0,0 +1,142 @@
+use std::hash::{BuildHasher, RandomState};
+
+/// A small xorshift64* generator, good enough to pick lines at random but not for cryptography.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    /// Returns a generator that picks the same numbers on every run for the same `seed`.
+    pub(crate) fn new(seed: u64) -> Self {
+        // scramble the seed with splitmix64 so that small seeds don't start in a weak state, and
+        // make sure the state is never zero, which xorshift can't leave
+        let mut state = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        state ^= state >> 31;
+        Self(state.max(1))
+    }
+
+    /// Seeds the generator from the randomly keyed hasher of the standard library.
+    pub(crate) fn from_entropy() -> Self {
+        Self::new(RandomState::new().hash_one(std::process::id()))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a number in `0..n`, where `n` must not be 0.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        // multiply-shift instead of `%`, which favors small numbers
+        ((u128::from(self.next_u64()) * n as u128) >> 64) as usize
+    }
+}
+
+/// Picks `k` items uniformly at random in a single pass over `items`, keeping only `k` items in
+/// memory (reservoir sampling). All items are returned if there are `k` or fewer. The order of the
+/// returned items isn't random, so [`shuffle`] them if it matters.
+pub(crate) fn reservoir_sample<T>(
+    items: impl IntoIterator<Item = T>,
+    k: usize,
+    rng: &mut Rng,
+) -> Vec<T> {
+    let items = items.into_iter();
+    // `k` may be far more than the number of items (e.g.: `rand(:, 18446744073709551615)`), so the
+    // reservoir only grows with the items actually seen
+    let mut reservoir = Vec::with_capacity(k.min(items.size_hint().0));
+    for (i, item) in items.enumerate() {
+        if i < k {
+            reservoir.push(item);
+        } else {
+            let j = rng.below(i + 1);
+            if j < k {
+                reservoir[j] = item;
+            }
+        }
+    }
+    reservoir
+}
+
+/// Shuffles `items` in place (Fisher-Yates).
+pub(crate) fn shuffle<T>(items: &mut [T], rng: &mut Rng) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, rng.below(i + 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn below_stays_in_range() {
+        let mut rng = Rng::new(0);
+        for n in [1, 2, 3, 10, usize::MAX] {
+            for _ in 0..100 {
+                assert!(rng.below(n) < n);
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        for _ in 0..10 {
+            assert_eq!(a.below(1000), b.below(1000));
+        }
+    }
+
+    #[test]
+    fn sample_is_distinct_and_in_range() {
+        let mut rng = Rng::new(7);
+        let mut sample = reservoir_sample(0..1000, 10, &mut rng);
+        assert_eq!(sample.len(), 10);
+        assert!(sample.iter().all(|&item| item < 1000));
+        sample.sort_unstable();
+        sample.dedup();
+        assert_eq!(sample.len(), 10);
+    }
+
+    #[test]
+    fn sample_larger_than_input() {
+        let mut rng = Rng::new(7);
+        assert_eq!(reservoir_sample(0..3, 5, &mut rng), [0, 1, 2]);
+        assert!(reservoir_sample(0..0, 5, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn huge_sample_size() {
+        let mut rng = Rng::new(7);
+        assert_eq!(reservoir_sample(0..3, usize::MAX, &mut rng), [0, 1, 2]);
+        let items = (0..3).filter(|_| true);
+        assert_eq!(reservoir_sample(items, usize::MAX, &mut rng), [0, 1, 2]);
+    }
+
+    #[test]
+    fn sample_is_roughly_uniform() {
+        let mut rng = Rng::new(1);
+        let mut counts = [0; 10];
+        for _ in 0..10_000 {
+            for item in reservoir_sample(0..10, 2, &mut rng) {
+                counts[item] += 1;
+            }
+        }
+        // each item is expected 2000 times
+        assert!(
+            counts.iter().all(|&count| (1800..2200).contains(&count)),
+            "{counts:?}"
+        );
+    }
+
+    #[test]
+    fn shuffle_keeps_items() {
+        let mut rng = Rng::new(3);
+        let mut items: Vec<_> = (0..50).collect();
+        shuffle(&mut items, &mut rng);
+        assert_ne!(items, (0..50).collect::<Vec<_>>());
+        items.sort_unstable();
+        assert_eq!(items, (0..50).collect::<Vec<_>>());
+    }
+}
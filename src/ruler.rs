@@ -0,0 +1,27 @@
+/// Rulers are at least this wide, so that they always show a column number.
+const MIN_WIDTH: usize = 10;
+
+/// Formats a ruler spanning `width` columns, like `----+----1----+----2`. Every fifth column is
+/// marked with `+`, and every tenth one with the last digit of its tens (e.g.: `2` for column 20
+/// and 120).
+pub(crate) fn format(width: usize) -> String {
+    (1..=width.max(MIN_WIDTH))
+        .map(|column| match column % 10 {
+            0 => char::from(b'0' + (column / 10 % 10) as u8),
+            5 => '+',
+            _ => '-',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_every_fifth_column() {
+        assert_eq!(format(23), "----+----1----+----2---");
+        assert_eq!(format(3), "----+----1");
+        assert_eq!(&format(120)[90..], "----+----0----+----1----+----2");
+    }
+}
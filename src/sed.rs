@@ -0,0 +1,569 @@
+use crate::escape::split_terminator;
+use crate::line_selector::RawLineSelector;
+use anyhow::Context;
+use regex::bytes::{Regex, RegexBuilder};
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek};
+use std::path::Path;
+
+/// The name under which line behaves like `line --compat sed`, e.g.: through a symlink.
+const APPLET_NAME: &str = "linep";
+
+/// A sed address expression of the form `ADDR1[,ADDR2][p]`, e.g.: `12,20p`, `/start/,$`, or
+/// `0~4`, for `--expression`.
+#[derive(Clone, Debug)]
+pub(crate) struct Expression {
+    start: Address,
+    end: Option<EndAddress>,
+}
+
+#[derive(Clone, Debug)]
+enum Address {
+    /// Line number (1-based), where 0 is only allowed in `0,/regex/`
+    Line(usize),
+    /// `$`, the last line
+    Last,
+    /// `FIRST~STEP`, every STEP-th line starting with line FIRST
+    Step { first: usize, step: usize },
+    /// `/regex/`, the lines matching the regex
+    Regex(Regex),
+}
+
+#[derive(Clone, Debug)]
+enum EndAddress {
+    Address(Address),
+    /// `+N`, the N lines after the first line of the range
+    Plus(usize),
+    /// `~N`, up to the next line whose number is a multiple of N
+    MultipleOf(usize),
+}
+
+impl Expression {
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Self> {
+        let usage = || {
+            format!(
+                "Expression `{s}` isn't a sed address expression of the form ADDR1[,ADDR2][p] \
+                (e.g.: 12,20p, /start/,$, or 0~4)"
+            )
+        };
+
+        let (start, rest) = Address::parse(s.trim()).with_context(usage)?;
+        let (end, rest) = match rest.strip_prefix(',') {
+            Some(rest) => {
+                let (end, rest) = EndAddress::parse(rest).with_context(usage)?;
+                (Some(end), rest)
+            }
+            None => (None, rest),
+        };
+        // the `p` command of `sed -n 'ADDR1,ADDR2p'` is accepted to ease copying expressions
+        if !matches!(rest.trim_end(), "" | "p") {
+            anyhow::bail!(usage());
+        }
+
+        if let Address::Line(0) = start
+            && !matches!(end, Some(EndAddress::Address(Address::Regex(_))))
+        {
+            anyhow::bail!("Line 0 can only start an expression of the form 0,/regex/");
+        }
+        Ok(Self { start, end })
+    }
+
+    fn has_regex(&self) -> bool {
+        matches!(self.start, Address::Regex(_))
+            || matches!(self.end, Some(EndAddress::Address(Address::Regex(_))))
+    }
+}
+
+impl Address {
+    /// Parses the address at the start of `s`, returning it along with the rest of `s`.
+    fn parse(s: &str) -> anyhow::Result<(Self, &str)> {
+        if let Some(rest) = s.strip_prefix('$') {
+            return Ok((Self::Last, rest));
+        }
+        if let Some(rest) = s.strip_prefix('/') {
+            return parse_regex(rest);
+        }
+
+        let (first, rest) = parse_number(s)?;
+        match rest.strip_prefix('~') {
+            Some(rest) => {
+                let (step, rest) = parse_number(rest)?;
+                Ok((Self::Step { first, step }, rest))
+            }
+            None => Ok((Self::Line(first), rest)),
+        }
+    }
+
+    /// Returns true if line `line_num` (1-based), whose content is `line`, is addressed.
+    fn matches(&self, line_num: usize, line: &[u8], n_lines: usize) -> bool {
+        match self {
+            Self::Line(n) => line_num == *n,
+            Self::Last => line_num == n_lines,
+            Self::Step { first, step: 0 } => line_num == *first,
+            Self::Step { first, step } => {
+                line_num >= *first && (line_num - first).is_multiple_of(*step)
+            }
+            Self::Regex(regex) => regex.is_match(line),
+        }
+    }
+}
+
+impl EndAddress {
+    fn parse(s: &str) -> anyhow::Result<(Self, &str)> {
+        if let Some(rest) = s.strip_prefix('+') {
+            let (n, rest) = parse_number(rest)?;
+            return Ok((Self::Plus(n), rest));
+        }
+        if let Some(rest) = s.strip_prefix('~') {
+            let (n, rest) = parse_number(rest)?;
+            return Ok((Self::MultipleOf(n), rest));
+        }
+        let (address, rest) = Address::parse(s)?;
+        Ok((Self::Address(address), rest))
+    }
+}
+
+/// Parses the number at the start of `s`, returning it along with the rest of `s`.
+fn parse_number(s: &str) -> anyhow::Result<(usize, &str)> {
+    let len = s.bytes().take_while(u8::is_ascii_digit).count();
+    let (digits, rest) = s.split_at(len);
+    let n = digits
+        .parse()
+        .with_context(|| format!("Expected a line number at `{s}`"))?;
+    Ok((n, rest))
+}
+
+/// Parses a regex address after its opening `/`, up to its closing `/` and optional `I` flag
+/// (case insensitive). A `/` in the regex must be escaped as `\/`.
+fn parse_regex(s: &str) -> anyhow::Result<(Address, &str)> {
+    let mut pattern = String::new();
+    let mut chars = s.char_indices();
+    let rest = loop {
+        match chars.next() {
+            Some((i, '/')) => break &s[i + 1..],
+            Some((_, '\\')) => match chars.next() {
+                Some((_, '/')) => pattern.push('/'),
+                Some((_, c)) => {
+                    pattern.push('\\');
+                    pattern.push(c);
+                }
+                None => pattern.push('\\'),
+            },
+            Some((_, c)) => pattern.push(c),
+            None => anyhow::bail!("Regex `/{s}` isn't closed by a `/`"),
+        }
+    };
+    if pattern.is_empty() {
+        anyhow::bail!("The regex of an address can't be empty");
+    }
+
+    let (case_insensitive, rest) = match rest.strip_prefix('I') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+        .with_context(|| format!("Invalid regex `{pattern}`"))?;
+    Ok((Address::Regex(regex), rest))
+}
+
+/// Resolves `expressions` to the lines they address in `file`, which has `n_lines` lines ending
+/// with `separator`, then rewinds `file`.
+///
+/// Like with sed, lines are returned in file order, and a line addressed by several expressions
+/// is returned once per expression. The file is only read if an expression has a regex address.
+pub(crate) fn resolve(
+    expressions: &[Expression],
+    file: &mut BufReader<File>,
+    n_lines: usize,
+    separator: u8,
+) -> anyhow::Result<Vec<RawLineSelector>> {
+    let mut matchers: Vec<_> = expressions.iter().map(Matcher::new).collect();
+    let mut ranges = Ranges::default();
+    if expressions.iter().any(Expression::has_regex) {
+        let mut buf = Vec::new();
+        for line_num in 1..=n_lines {
+            buf.clear();
+            file.read_until(separator, &mut buf)
+                .context("Failed to read from file")?;
+            let (line, _) = split_terminator(&buf, separator);
+            ranges.feed(&mut matchers, line_num, line, n_lines);
+        }
+        file.rewind().context("Failed to rewind file")?;
+    } else {
+        for line_num in 1..=n_lines {
+            ranges.feed(&mut matchers, line_num, b"", n_lines);
+        }
+    }
+
+    Ok(ranges
+        .0
+        .into_iter()
+        .map(|(first, last)| {
+            if first == last {
+                RawLineSelector::Single(first as isize)
+            } else {
+                RawLineSelector::Range(Some(first as isize), Some(last as isize))
+            }
+        })
+        .collect())
+}
+
+/// Ranges of consecutive addressed lines (1-based, inclusive).
+#[derive(Default)]
+struct Ranges(Vec<(usize, usize)>);
+
+impl Ranges {
+    /// Adds line `line_num` once per matcher that addresses it.
+    fn feed(&mut self, matchers: &mut [Matcher], line_num: usize, line: &[u8], n_lines: usize) {
+        for matcher in matchers {
+            if matcher.feed(line_num, line, n_lines) {
+                self.push(line_num);
+            }
+        }
+    }
+
+    fn push(&mut self, line_num: usize) {
+        match self.0.last_mut() {
+            Some((_, last)) if *last + 1 == line_num => *last = line_num,
+            _ => self.0.push((line_num, line_num)),
+        }
+    }
+}
+
+/// Where the range being matched ends.
+#[derive(Clone, Copy)]
+enum RangeEnd {
+    /// At this line (1-based)
+    Line(usize),
+    /// At the next line matching the end address
+    EndAddress,
+}
+
+/// Follows the lines addressed by an expression, one line at a time, like sed does.
+struct Matcher<'a> {
+    expression: &'a Expression,
+    /// The end of the range being matched, if any
+    range_end: Option<RangeEnd>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(expression: &'a Expression) -> Self {
+        // `0,/regex/` is already in its range before line 1, so the regex can end it at line 1
+        let range_end = match expression.start {
+            Address::Line(0) => Some(RangeEnd::EndAddress),
+            _ => None,
+        };
+        Self {
+            expression,
+            range_end,
+        }
+    }
+
+    /// Returns true if line `line_num` (1-based), whose content is `line`, is addressed. Lines
+    /// must be fed in order.
+    fn feed(&mut self, line_num: usize, line: &[u8], n_lines: usize) -> bool {
+        if let Some(range_end) = self.range_end {
+            let is_end = match (range_end, &self.expression.end) {
+                (RangeEnd::Line(end), _) => line_num >= end,
+                (RangeEnd::EndAddress, Some(EndAddress::Address(end))) => {
+                    end.matches(line_num, line, n_lines)
+                }
+                (RangeEnd::EndAddress, _) => true,
+            };
+            if is_end {
+                self.range_end = None;
+            }
+            return true;
+        }
+
+        if !self.expression.start.matches(line_num, line, n_lines) {
+            return false;
+        }
+        // ranges whose end is at or before their first line only span their first line
+        self.range_end = match &self.expression.end {
+            None => None,
+            Some(EndAddress::Plus(n)) => (*n > 0).then_some(RangeEnd::Line(line_num + n)),
+            Some(EndAddress::MultipleOf(n)) => (*n > 0 && !line_num.is_multiple_of(*n))
+                .then(|| RangeEnd::Line(line_num.next_multiple_of(*n))),
+            Some(EndAddress::Address(Address::Line(end))) => {
+                (*end > line_num).then_some(RangeEnd::Line(*end))
+            }
+            Some(EndAddress::Address(_)) => Some(RangeEnd::EndAddress),
+        };
+        true
+    }
+}
+
+/// Splits a sed script into its commands, which are separated by `;` or new lines outside of
+/// regexes.
+fn split_script(script: &str) -> impl Iterator<Item = &str> {
+    let mut in_regex = false;
+    let mut escaped = false;
+    script
+        .split(move |c| {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_regex => escaped = true,
+                '/' => in_regex = !in_regex,
+                ';' | '\n' => return !in_regex,
+                _ => {}
+            }
+            false
+        })
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+}
+
+/// Returns the arguments after the program name if `args` follow the calling convention of
+/// `sed -n`, i.e.: if the program is called `linep`, or if its first argument is `--compat sed`.
+pub(crate) fn compat_args(args: &[OsString]) -> Option<&[OsString]> {
+    let is_applet = args
+        .first()
+        .and_then(|program| Path::new(program).file_stem())
+        .is_some_and(|program| program == APPLET_NAME);
+    match args {
+        [_, sed_args @ ..] if is_applet => Some(sed_args),
+        [_, compat, sed_args @ ..] if compat == "--compat=sed" => Some(sed_args),
+        [_, compat, sed, sed_args @ ..] if compat == "--compat" && sed == "sed" => Some(sed_args),
+        _ => None,
+    }
+}
+
+/// Translates the arguments of `sed -n [-e] SCRIPT FILE` into those of `program` (i.e.: line),
+/// selecting the lines printed by SCRIPT in plain output, just like sed prints them.
+///
+/// Only scripts made of `p` commands are supported, e.g.: `12,20p` or `/start/,/end/p;$p`.
+pub(crate) fn translate_compat_args(
+    program: &OsStr,
+    sed_args: &[OsString],
+) -> anyhow::Result<Vec<OsString>> {
+    let mut quiet = false;
+    let mut scripts = Vec::new();
+    let mut operands = Vec::new();
+    let mut sed_args = sed_args.iter();
+    while let Some(arg) = sed_args.next() {
+        let Some(option) = arg
+            .to_str()
+            .filter(|arg| arg.starts_with('-') && *arg != "-")
+        else {
+            operands.push(arg);
+            continue;
+        };
+        match option {
+            "--" => operands.extend(sed_args.by_ref()),
+            "--quiet" | "--silent" => quiet = true,
+            // regexes are always extended
+            "--regexp-extended" => {}
+            "--expression" => scripts.push(next_script(&mut sed_args)?),
+            _ if option.starts_with("--expression=") => {
+                scripts.push(&option["--expression=".len()..]);
+            }
+            _ if option.starts_with("--") => {
+                anyhow::bail!("sed option `{option}` isn't supported")
+            }
+            // clusters of short options, e.g.: `-ne '12p'` or `-nE`
+            _ => {
+                for (i, flag) in option.char_indices().skip(1) {
+                    match flag {
+                        'n' => quiet = true,
+                        'E' | 'r' => {}
+                        'e' => {
+                            let script = &option[i + 1..];
+                            if script.is_empty() {
+                                scripts.push(next_script(&mut sed_args)?);
+                            } else {
+                                scripts.push(script);
+                            }
+                            break;
+                        }
+                        _ => anyhow::bail!("sed option `-{flag}` isn't supported"),
+                    }
+                }
+            }
+        }
+    }
+
+    if !quiet {
+        anyhow::bail!("Only `sed -n` is supported, which prints nothing but the selected lines");
+    }
+    let mut operands = operands.into_iter();
+    if scripts.is_empty() {
+        let script = operands.next().context("Missing sed script")?;
+        scripts.push(
+            script
+                .to_str()
+                .context("The sed script isn't valid UTF-8")?,
+        );
+    }
+    let file = match (operands.next(), operands.next()) {
+        (Some(file), None) => file,
+        (None, _) => anyhow::bail!("Missing input file (stdin isn't supported)"),
+        (Some(_), Some(_)) => anyhow::bail!("Only one input file is supported"),
+    };
+
+    let mut args = vec![
+        program.to_owned(),
+        OsString::from("--plain"),
+        OsString::from("--color=never"),
+        OsString::from("--allow-binary-files"),
+    ];
+    for command in scripts.into_iter().flat_map(split_script) {
+        let Some(expression) = command.strip_suffix('p') else {
+            anyhow::bail!("sed command `{command}` isn't supported, only `p` commands are");
+        };
+        args.push(OsString::from(format!("--expression={expression}")));
+    }
+    args.push(OsString::from("--"));
+    args.push(file.to_owned());
+    Ok(args)
+}
+
+/// Returns the script given to `-e` from the next argument.
+fn next_script<'a>(sed_args: &mut impl Iterator<Item = &'a OsString>) -> anyhow::Result<&'a str> {
+    sed_args
+        .next()
+        .context("Option -e requires a script")?
+        .to_str()
+        .context("The sed script isn't valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the ranges addressed by the `;`-separated expressions in `lines`.
+    fn ranges(expressions: &str, lines: &[&str]) -> Vec<(usize, usize)> {
+        let expressions: Vec<_> = split_script(expressions)
+            .map(|expression| Expression::parse(expression).unwrap())
+            .collect();
+        let mut matchers: Vec<_> = expressions.iter().map(Matcher::new).collect();
+        let mut ranges = Ranges::default();
+        for (i, line) in lines.iter().enumerate() {
+            ranges.feed(&mut matchers, i + 1, line.as_bytes(), lines.len());
+        }
+        ranges.0
+    }
+
+    const LINES: &[&str] = &["a", "start", "b", "end", "c", "START", "d", "e", "end", "f"];
+
+    #[test]
+    fn line_numbers() {
+        assert_eq!(ranges("3", LINES), [(3, 3)]);
+        assert_eq!(ranges("3,5p", LINES), [(3, 5)]);
+        assert_eq!(ranges(" 5,3 ", LINES), [(5, 5)]);
+        assert_eq!(ranges("8,$", LINES), [(8, 10)]);
+        assert_eq!(ranges("$", LINES), [(10, 10)]);
+        assert_eq!(ranges("42", LINES), []);
+    }
+
+    #[test]
+    fn regexes() {
+        assert_eq!(ranges("/^start$/", LINES), [(2, 2)]);
+        assert_eq!(ranges("/^start$/I", LINES), [(2, 2), (6, 6)]);
+        assert_eq!(ranges("/start/I,/end/", LINES), [(2, 4), (6, 9)]);
+        // the end regex is only tried from the line after the start of the range
+        assert_eq!(ranges("/end/,/end/", LINES), [(4, 9)]);
+        assert_eq!(ranges("/a/,/^a$/", LINES), [(1, 10)]);
+        assert_eq!(ranges("0,/a/", LINES), [(1, 1)]);
+        assert_eq!(ranges("1,/a/", LINES), [(1, 2)]);
+        assert_eq!(ranges(r"/x\/y/", &["x/y", "xy"]), [(1, 1)]);
+    }
+
+    #[test]
+    fn steps() {
+        assert_eq!(ranges("0~4", LINES), [(4, 4), (8, 8)]);
+        assert_eq!(ranges("2~3", LINES), [(2, 2), (5, 5), (8, 8)]);
+        assert_eq!(ranges("2~0", LINES), [(2, 2)]);
+        assert_eq!(ranges("/end/,+1", LINES), [(4, 5), (9, 10)]);
+        assert_eq!(ranges("2,~4", LINES), [(2, 4)]);
+        assert_eq!(ranges("4,~4", LINES), [(4, 4)]);
+    }
+
+    #[test]
+    fn several_expressions() {
+        // lines are in file order, once per expression addressing them
+        assert_eq!(ranges("5p;2p", LINES), [(2, 2), (5, 5)]);
+        assert_eq!(ranges("2,3p;3p;4p", LINES), [(2, 3), (3, 4)]);
+        assert_eq!(ranges(r"/;/p;/\//p", &["a;b", "c/d"]), [(1, 2)]);
+    }
+
+    #[test]
+    fn compat_args() {
+        let os_strings =
+            |args: &[&str]| -> Vec<OsString> { args.iter().map(OsString::from).collect() };
+        let translate = |args: &[&str]| {
+            let args = os_strings(args);
+            let sed_args = super::compat_args(&args)?;
+            Some(translate_compat_args(OsStr::new("line"), sed_args).map_err(|e| e.to_string()))
+        };
+
+        let expected = os_strings(&[
+            "line",
+            "--plain",
+            "--color=never",
+            "--allow-binary-files",
+            "--expression=12,20",
+            "--expression=$",
+            "--",
+            "file",
+        ]);
+        for args in [
+            &["line", "--compat", "sed", "-n", "12,20p;$p", "file"][..],
+            &["line", "--compat=sed", "file", "-ne", "12,20p", "-e$p"],
+            &[
+                "/usr/bin/linep",
+                "--quiet",
+                "--expression=12,20p\n$p",
+                "--",
+                "file",
+            ],
+        ] {
+            assert_eq!(translate(args), Some(Ok(expected.clone())), "{args:?}");
+        }
+
+        assert_eq!(translate(&["line", "-n=12", "file"]), None);
+        for (args, error) in [
+            (&["linep", "12p", "file"][..], "Only `sed -n` is supported"),
+            (
+                &["linep", "-n", "12d", "file"],
+                "sed command `12d` isn't supported",
+            ),
+            (
+                &["linep", "-ni", "12p", "file"],
+                "sed option `-i` isn't supported",
+            ),
+            (&["linep", "-n", "12p"], "Missing input file"),
+            (
+                &["linep", "-n", "12p", "a", "b"],
+                "Only one input file is supported",
+            ),
+        ] {
+            let result = translate(args).unwrap();
+            assert!(
+                result.as_ref().is_err_and(|e| e.starts_with(error)),
+                "{result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expression in [
+            "",
+            "a",
+            "1,",
+            "1;2",
+            "3,5d",
+            "/unclosed",
+            "//",
+            "/(/",
+            "0",
+            "0,5",
+        ] {
+            assert!(Expression::parse(expression).is_err(), "{expression}");
+        }
+    }
+}
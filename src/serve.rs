@@ -0,0 +1,500 @@
+use crate::escape::split_terminator;
+use crate::line_counter::count_lines;
+use crate::line_reader::LineReader;
+use crate::line_selector::RawLineSelector;
+use crate::random::Rng;
+use crate::{
+    LineSource, available_threads, get_context_lines_endpoints, inspect_content, open_file,
+    parse_line_selectors, read_lines,
+};
+use anyhow::Context;
+use serde_json::json;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+/// How long a client may take to send its request before the connection is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a client may take to receive its response before the connection is dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most lines returned for a request, context lines included, so that a single request can't
+/// make the server hold a huge file (or many copies of its lines) in memory.
+const MAX_RESPONSE_LINES: usize = 100_000;
+
+/// Longest request line accepted, which is plenty for a path and a few selectors.
+const MAX_REQUEST_LINE_LEN: u64 = 8 * 1024;
+
+/// Most connections handled at once per available thread. Connections mostly wait on their
+/// client, so there are more of them than threads.
+const CONNECTIONS_PER_THREAD: usize = 4;
+
+/// Counts the connections being handled, so that no more than `max` are handled at once.
+struct ConnectionLimit {
+    active: Mutex<usize>,
+    freed: Condvar,
+    max: usize,
+}
+
+impl ConnectionLimit {
+    fn new(max: usize) -> Arc<Self> {
+        Arc::new(Self {
+            active: Mutex::new(0),
+            freed: Condvar::new(),
+            max,
+        })
+    }
+
+    /// Waits until fewer than `max` connections are being handled, then counts one more until
+    /// the returned slot is dropped.
+    fn acquire(self: &Arc<Self>) -> ConnectionSlot {
+        let mut active = self
+            .freed
+            .wait_while(
+                self.active.lock().unwrap_or_else(PoisonError::into_inner),
+                |active| *active >= self.max,
+            )
+            .unwrap_or_else(PoisonError::into_inner);
+        *active += 1;
+        ConnectionSlot(Arc::clone(self))
+    }
+}
+
+/// A connection counted by a [`ConnectionLimit`], until it's dropped.
+struct ConnectionSlot(Arc<ConnectionLimit>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        *self.0.active.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: json!({ "error": message.into() }).to_string().into_bytes(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// Serves the files under `root` over HTTP on `listen` until the process is killed.
+///
+/// `GET /file?path=PATH&n=SELECTORS` returns the selected lines of `PATH`, relative to `root`,
+/// using the same selector syntax as `-n`. `context` (or `before` and `after`) adds context lines
+/// and `format=text` returns the lines as plain text instead of JSON. Each connection is handled on
+/// a thread of its own, so that a slow client doesn't hold up the others. Past a few connections
+/// per available thread, new connections wait to be accepted until one of them is closed.
+pub(crate) fn run(listen: SocketAddr, root: &Path) -> anyhow::Result<()> {
+    let root: Arc<Path> = root
+        .canonicalize()
+        .with_context(|| format!("Couldn't open directory `{}`", root.display()))?
+        .into();
+    let listener =
+        TcpListener::bind(listen).with_context(|| format!("Couldn't listen on {listen}"))?;
+    let local_addr = listener
+        .local_addr()
+        .context("Couldn't get the listening address")?;
+    println!("Listening on http://{local_addr}");
+    std::io::stdout()
+        .flush()
+        .context("Failed to flush output")?;
+
+    let limit = ConnectionLimit::new(available_threads() * CONNECTIONS_PER_THREAD);
+    loop {
+        // the connections that can't be handled yet wait in the backlog of the listener
+        let slot = limit.acquire();
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                eprintln!("Failed to accept connection: {err}");
+                continue;
+            }
+        };
+        // a misbehaving client (or a bug hit by its request) only takes its own connection down
+        let root = Arc::clone(&root);
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(err) = handle_connection(stream, &root) {
+                eprintln!("{err:#}");
+            }
+        });
+    }
+}
+
+fn handle_connection(stream: TcpStream, root: &Path) -> anyhow::Result<()> {
+    stream
+        .set_read_timeout(Some(READ_TIMEOUT))
+        .context("Failed to set read timeout")?;
+    stream
+        .set_write_timeout(Some(WRITE_TIMEOUT))
+        .context("Failed to set write timeout")?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    (&mut reader)
+        .take(MAX_REQUEST_LINE_LEN)
+        .read_line(&mut request_line)
+        .context("Failed to read request")?;
+    // the headers are read but ignored, since nothing in them changes the response
+    let mut header = String::new();
+    while (&mut reader)
+        .take(MAX_REQUEST_LINE_LEN)
+        .read_line(&mut header)
+        .context("Failed to read request")?
+        > 2
+    {
+        header.clear();
+    }
+
+    let response = respond(&request_line, root);
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len()
+    )
+    .and_then(|()| stream.write_all(&response.body))
+    .and_then(|()| stream.flush())
+    .context("Failed to send response")
+}
+
+/// Builds the response to a request line such as `GET /file?path=a.txt&n=1:5 HTTP/1.1`.
+fn respond(request_line: &str, root: &Path) -> Response {
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Response::error(400, "Malformed request");
+    };
+    if method != "GET" {
+        return Response::error(405, "Only GET requests are supported");
+    }
+    let (route, query) = target.split_once('?').unwrap_or((target, ""));
+    if route != "/file" {
+        return Response::error(404, format!("Unknown route `{route}` (use /file)"));
+    }
+
+    let params = match Params::parse(query) {
+        Ok(params) => params,
+        Err(err) => return Response::error(400, format!("{err:#}")),
+    };
+    let path = match resolve_path(root, &params.path) {
+        Ok(path) => path,
+        Err(response) => return response,
+    };
+    match extract(&path, &params) {
+        Ok(response) => response,
+        Err(err) => Response::error(400, format!("{err:#}")),
+    }
+}
+
+/// The query parameters of `/file`.
+struct Params {
+    path: String,
+    line_selectors: Vec<RawLineSelector>,
+    before: usize,
+    after: usize,
+    text: bool,
+}
+
+impl Params {
+    fn parse(query: &str) -> anyhow::Result<Self> {
+        let mut path = None;
+        let mut line_selectors = None;
+        let (mut before, mut after) = (0, 0);
+        let mut text = false;
+
+        let parse_count = |key: &str, value: &str| {
+            value
+                .parse::<usize>()
+                .with_context(|| format!("Value `{value}` of `{key}` is not a number"))
+        };
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value)?;
+            match key {
+                "path" => path = Some(value),
+                "n" => line_selectors = Some(RawLineSelector::list_from_str(&value)?.0),
+                "context" => {
+                    before = parse_count(key, &value)?;
+                    after = before;
+                }
+                "before" => before = parse_count(key, &value)?,
+                "after" => after = parse_count(key, &value)?,
+                "format" => match value.as_str() {
+                    "json" => text = false,
+                    "text" => text = true,
+                    _ => anyhow::bail!("Unknown format `{value}` (use json or text)"),
+                },
+                _ => anyhow::bail!("Unknown parameter `{key}`"),
+            }
+        }
+
+        Ok(Self {
+            path: path.context("Missing parameter `path`")?,
+            line_selectors: line_selectors.context("Missing parameter `n`")?,
+            before,
+            after,
+            text,
+        })
+    }
+}
+
+/// Decodes `%XX` escapes and `+` (a space) in a query string value.
+fn percent_decode(value: &str) -> anyhow::Result<String> {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = [bytes.next(), bytes.next()];
+                let byte = match hex {
+                    [Some(high), Some(low)] => std::str::from_utf8(&[high, low])
+                        .ok()
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+                    _ => None,
+                };
+                decoded.push(byte.with_context(|| format!("Invalid escape in `{value}`"))?);
+            }
+            _ => decoded.push(byte),
+        }
+    }
+    String::from_utf8(decoded).with_context(|| format!("`{value}` isn't valid UTF-8"))
+}
+
+/// Resolves `path` relative to `root`, refusing paths that lead outside of `root` (e.g.: through
+/// `..` or a symbolic link).
+///
+/// Files outside of `root` are reported as not found, so that clients can't probe which files
+/// exist elsewhere.
+fn resolve_path(root: &Path, path: &str) -> Result<PathBuf, Response> {
+    let not_found = || Response::error(404, format!("File `{path}` not found"));
+    let resolved = root
+        .join(path.trim_start_matches('/'))
+        .canonicalize()
+        .map_err(|_| not_found())?;
+    if !resolved.starts_with(root) {
+        return Err(not_found());
+    }
+    Ok(resolved)
+}
+
+/// Reads the selected lines and their context lines of the file at `path`.
+fn extract(path: &Path, params: &Params) -> anyhow::Result<Response> {
+    let mut file = BufReader::new(open_file(path)?);
+    if inspect_content(&mut file)?.is_binary() {
+        anyhow::bail!("File `{}` appears to be a binary file", params.path);
+    }
+    let n_lines = count_lines(&mut file, available_threads(), b'\n')?;
+    let line_selectors =
+        parse_line_selectors(&params.line_selectors, n_lines, &mut Rng::from_entropy())?;
+    // every selected line is returned with its context, even where the context of lines overlaps
+    let n_response_lines = line_selectors
+        .iter()
+        .fold(0, |n: usize, line_selector| {
+            n.saturating_add(line_selector.n_selected())
+        })
+        .saturating_mul(
+            params
+                .before
+                .saturating_add(params.after)
+                .saturating_add(1)
+                .min(n_lines),
+        );
+    if n_response_lines > MAX_RESPONSE_LINES {
+        anyhow::bail!(
+            "Too many lines requested ({n_response_lines}), at most {MAX_RESPONSE_LINES} lines \
+             can be returned, context lines included"
+        );
+    }
+    let mut lines = read_lines(
+        LineReader::new(file),
+        &line_selectors,
+        params.before,
+        params.after,
+        n_lines,
+    )?;
+
+    let mut text = Vec::new();
+    let mut json_lines = Vec::new();
+    for line_selector in &line_selectors {
+        for selected_line_num in line_selector.iter_in_selection_order() {
+            let (first_line, last_line) = get_context_lines_endpoints(
+                selected_line_num,
+                params.before,
+                params.after,
+                n_lines,
+            );
+            for line_num in first_line..=last_line {
+                let line = lines.get(line_num)?;
+                if params.text {
+                    text.extend_from_slice(line);
+                } else {
+                    let (content, line_ending) = split_terminator(line, b'\n');
+                    json_lines.push(json!({
+                        "number": line_num + 1,
+                        "content": String::from_utf8_lossy(content),
+                        "line_ending": String::from_utf8_lossy(line_ending),
+                        "selected": line_num == selected_line_num,
+                    }));
+                }
+            }
+        }
+    }
+
+    Ok(if params.text {
+        Response {
+            status: 200,
+            content_type: "text/plain; charset=utf-8",
+            body: text,
+        }
+    } else {
+        Response {
+            status: 200,
+            content_type: "application/json",
+            body: json!({ "path": params.path, "lines": json_lines })
+                .to_string()
+                .into_bytes(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root_with_file(content: &str) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("file.txt"), content).unwrap();
+        root
+    }
+
+    fn get(root: &Path, target: &str) -> Response {
+        respond(
+            &format!("GET {target} HTTP/1.1\r\n"),
+            &root.canonicalize().unwrap(),
+        )
+    }
+
+    #[test]
+    fn json_lines() {
+        let root = root_with_file("one\ntwo\nthree\n");
+        let response = get(root.path(), "/file?path=file.txt&n=2&context=1");
+        assert_eq!(response.status, 200);
+        let body: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "path": "file.txt",
+                "lines": [
+                    { "number": 1, "content": "one", "line_ending": "\n", "selected": false },
+                    { "number": 2, "content": "two", "line_ending": "\n", "selected": true },
+                    { "number": 3, "content": "three", "line_ending": "\n", "selected": false },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn text_lines() {
+        let root = root_with_file("one\ntwo\nthree\n");
+        let response = get(root.path(), "/file?path=file.txt&n=3%2C1&format=text");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"three\none\n");
+    }
+
+    #[test]
+    fn errors() {
+        let root = root_with_file("one\n");
+        assert_eq!(get(root.path(), "/file?path=file.txt&n=5").status, 400);
+        assert_eq!(get(root.path(), "/file?path=file.txt").status, 400);
+        assert_eq!(get(root.path(), "/file?path=file.txt&n=1&x=1").status, 400);
+        assert_eq!(get(root.path(), "/file?path=missing.txt&n=1").status, 404);
+        assert_eq!(get(root.path(), "/lines?path=file.txt&n=1").status, 404);
+        let response = respond("POST /file?path=file.txt&n=1 HTTP/1.1", root.path());
+        assert_eq!(response.status, 405);
+    }
+
+    #[test]
+    fn too_many_lines_are_refused() {
+        let root = root_with_file(&"line\n".repeat(400));
+        assert_eq!(
+            get(root.path(), "/file?path=file.txt&n=:&context=200").status,
+            400
+        );
+        assert_eq!(
+            get(root.path(), "/file?path=file.txt&n=:&context=100").status,
+            200
+        );
+        let target = format!("/file?path=file.txt&n=1&after={}", usize::MAX);
+        assert_eq!(get(root.path(), &target).status, 200);
+    }
+
+    #[test]
+    fn paths_outside_of_root_are_refused() {
+        let parent = tempfile::tempdir().unwrap();
+        std::fs::write(parent.path().join("secret.txt"), "secret\n").unwrap();
+        let root = parent.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+
+        assert_eq!(get(&root, "/file?path=../secret.txt&n=1").status, 404);
+        assert_eq!(get(&root, "/file?path=%2E%2E%2Fsecret.txt&n=1").status, 404);
+        let secret = parent.path().join("secret.txt").canonicalize().unwrap();
+        let target = format!("/file?path={}&n=1", secret.display());
+        assert_eq!(get(&root, &target).status, 404);
+    }
+
+    #[test]
+    fn decode() {
+        assert_eq!(percent_decode("a+b%2Cc%3a").unwrap(), "a b,c:");
+        assert_eq!(percent_decode("caf%C3%A9").unwrap(), "café");
+        assert!(percent_decode("%2").is_err());
+        assert!(percent_decode("%zz").is_err());
+        assert!(percent_decode("%FF").is_err());
+    }
+
+    #[test]
+    fn connections_wait_for_a_free_slot() {
+        let limit = ConnectionLimit::new(2);
+        let slots = [limit.acquire(), limit.acquire()];
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let waiting_limit = Arc::clone(&limit);
+        let waiting = thread::spawn(move || {
+            let slot = waiting_limit.acquire();
+            sender.send(()).unwrap();
+            slot
+        });
+        let timeout = Duration::from_millis(100);
+        assert!(receiver.recv_timeout(timeout).is_err());
+
+        drop(slots);
+        assert!(receiver.recv_timeout(Duration::from_secs(10)).is_ok());
+        drop(waiting.join().unwrap());
+        assert_eq!(*limit.active.lock().unwrap(), 0);
+    }
+}
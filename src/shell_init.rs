@@ -0,0 +1,60 @@
+use crate::cli::Shell;
+use anyhow::Context;
+use std::io::Write;
+
+/// Ctrl-G reads a selector, then turns the word before the cursor into `line -n=SELECTOR WORD`.
+const BASH: &str = r#"__line_widget() {
+    local before="${READLINE_LINE:0:READLINE_POINT}"
+    local after="${READLINE_LINE:READLINE_POINT}"
+    local file="${before##* }"
+    local selector
+    read -r -p "line -n=" selector </dev/tty || return
+    [[ -n "$selector" ]] || return
+    local command="line -n=$(printf '%q' "$selector") $file"
+    before="${before%"$file"}$command"
+    READLINE_LINE="$before$after"
+    READLINE_POINT=${#before}
+}
+bind -m emacs-standard -x '"\C-g": __line_widget'
+bind -m vi-insert -x '"\C-g": __line_widget'
+"#;
+
+const ZSH: &str = r#"__line_widget() {
+    local file="${LBUFFER##* }"
+    local selector
+    zle -I
+    read -r "selector?line -n=" </dev/tty
+    if [[ -n "$selector" ]]; then
+        LBUFFER="${LBUFFER%$file}line -n=${(q)selector} $file"
+    fi
+    zle reset-prompt
+}
+zle -N __line_widget
+bindkey -M emacs '^G' __line_widget
+bindkey -M viins '^G' __line_widget
+"#;
+
+const FISH: &str = r#"function __line_widget
+    set -l file (commandline --current-token)
+    read --local --prompt-str 'line -n=' selector </dev/tty
+    if test -n "$selector"
+        commandline --current-token -- "line -n="(string escape -- $selector)" $file"
+    end
+    commandline --function repaint
+end
+bind \cg __line_widget
+bind --mode insert \cg __line_widget 2>/dev/null
+"#;
+
+/// Prints the shell integration script of `shell`, meant to be evaluated by the shell's startup
+/// file (e.g.: `eval "$(line shell-init bash)"` in `~/.bashrc`).
+pub(crate) fn run(shell: Shell) -> anyhow::Result<()> {
+    let script = match shell {
+        Shell::Bash => BASH,
+        Shell::Zsh => ZSH,
+        Shell::Fish => FISH,
+    };
+    std::io::stdout()
+        .write_all(script.as_bytes())
+        .context("Failed to output shell integration")
+}
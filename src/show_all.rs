@@ -0,0 +1,102 @@
+use crate::escape::{push_hex, split_terminator};
+use std::borrow::Cow;
+use unicode_width::UnicodeWidthChar;
+
+/// Marks a tab, which is followed by spaces up to the next tab stop.
+const TAB: char = '→';
+/// Marks a space at the end of a line.
+const TRAILING_SPACE: char = '·';
+/// Marks a delete character, the other control characters being marked by their control picture
+/// (e.g.: `␍` for a carriage return).
+const DEL: char = '␡';
+
+/// Makes the tabs, trailing spaces, control characters, and invalid UTF-8 of `line` visible, for
+/// `--show-all`: tabs become `→` followed by spaces up to the next tab stop (tab stops being
+/// `tab_width` columns apart), trailing spaces become `·`, control characters become their
+/// control picture (e.g.: `␛` for an escape character), and invalid UTF-8 is hex-escaped. The line
+/// ending is kept as is.
+pub(crate) fn show_all(line: &[u8], terminator: u8, tab_width: usize) -> Cow<'_, [u8]> {
+    let (content, line_ending) = split_terminator(line, terminator);
+    let trailing_spaces = content.iter().rev().take_while(|&&b| b == b' ').count();
+    let (content, trailing) = content.split_at(content.len() - trailing_spaces);
+    if trailing.is_empty() && !needs_marking(content) {
+        return Cow::Borrowed(line);
+    }
+
+    let mut shown = String::with_capacity(line.len() + 16);
+    let mut column = 0;
+    for chunk in content.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c == '\t' {
+                shown.push(TAB);
+                let n_spaces = tab_width - 1 - column % tab_width;
+                shown.extend(std::iter::repeat_n(' ', n_spaces));
+                column += n_spaces + 1;
+                continue;
+            }
+            let c = match c {
+                '\0'..='\x1f' => char::from_u32(0x2400 + c as u32).unwrap_or(c),
+                '\x7f' => DEL,
+                c => c,
+            };
+            if c.is_control() {
+                let mut bytes = [0; 4];
+                for byte in c.encode_utf8(&mut bytes).bytes() {
+                    push_hex(&mut shown, byte);
+                    column += 4;
+                }
+            } else {
+                shown.push(c);
+                column += c.width().unwrap_or(0);
+            }
+        }
+        for &byte in chunk.invalid() {
+            push_hex(&mut shown, byte);
+            column += 4;
+        }
+    }
+    shown.extend(std::iter::repeat_n(TRAILING_SPACE, trailing.len()));
+
+    let mut shown = shown.into_bytes();
+    shown.extend_from_slice(line_ending);
+    Cow::Owned(shown)
+}
+
+/// Returns true if `content` has a tab, a control character, or invalid UTF-8.
+fn needs_marking(content: &[u8]) -> bool {
+    content
+        .utf8_chunks()
+        .any(|chunk| !chunk.invalid().is_empty() || chunk.valid().chars().any(char::is_control))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(line: &[u8]) -> Cow<'_, [u8]> {
+        show_all(line, b'\n', 4)
+    }
+
+    #[test]
+    fn readable_lines_are_borrowed() {
+        for line in [&b"plain\n"[..], b"windows\r\n", "café ✓".as_bytes(), b""] {
+            assert!(matches!(show(line), Cow::Borrowed(_)), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn marks_invisible_characters() {
+        for (line, shown) in [
+            (&b"a\tb\n"[..], "a→  b\n"),
+            (b"\tab\tc", "→   ab→ c"),
+            (b"trailing  \r\n", "trailing··\r\n"),
+            (b"in the middle \n", "in the middle·\n"),
+            (b"old mac\r", "old mac␍"),
+            (b"\x1b[31mred\x00\x7f", "␛[31mred␀␡"),
+            (b"invalid \xff", "invalid \\xff"),
+            ("next line\u{85}".as_bytes(), "next line\\xc2\\x85"),
+        ] {
+            assert_eq!(show(line), shown.as_bytes(), "{line:?}");
+        }
+    }
+}
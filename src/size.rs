@@ -0,0 +1,105 @@
+use anyhow::Context;
+
+/// Parses a human-readable size such as `512`, `64K`, `4KiB`, `1M`, or `2GB` into bytes.
+///
+/// Suffixes are case-insensitive. `K`, `M`, and `G` (optionally followed by `iB`) are powers of
+/// 1024, while `KB`, `MB`, and `GB` are powers of 1000, following GNU coreutils. A trailing `B`
+/// alone means bytes.
+///
+/// # Errors:
+///
+/// This method returns an error if the number can't be parsed, the suffix is unknown, or the size
+/// doesn't fit into a `usize`.
+pub(crate) fn parse_size(s: &str) -> anyhow::Result<usize> {
+    let s = s.trim();
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, suffix) = s.split_at(digits_end);
+
+    if num.is_empty() {
+        anyhow::bail!("Size `{s}` doesn't start with a number");
+    }
+    let num: usize = num
+        .parse()
+        .with_context(|| format!("Size `{s}` is too large"))?;
+
+    let multiplier: usize = match suffix.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        _ => anyhow::bail!("Unknown size suffix `{suffix}` (use B, K, M, or G)"),
+    };
+
+    num.checked_mul(multiplier)
+        .with_context(|| format!("Size `{s}` is too large"))
+}
+
+/// Formats `bytes` as a human-readable size with one decimal, e.g.: `512 B`, `9.4 KiB`, or
+/// `1.2 GiB`.
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(9626), "9.4 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.0 MiB");
+        assert_eq!(format_size(1288490189), "1.2 GiB");
+    }
+
+    #[test]
+    fn plain_bytes() {
+        assert_eq!(parse_size("0").unwrap(), 0);
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("512B").unwrap(), 512);
+        assert_eq!(parse_size(" 512 ").unwrap(), 512);
+    }
+
+    #[test]
+    fn binary_suffixes() {
+        assert_eq!(parse_size("64K").unwrap(), 64 * 1024);
+        assert_eq!(parse_size("64k").unwrap(), 64 * 1024);
+        assert_eq!(parse_size("4KiB").unwrap(), 4 * 1024);
+        assert_eq!(parse_size("1M").unwrap(), 1024 * 1024);
+        assert_eq!(parse_size("2G").unwrap(), 2 * 1024 * 1024 * 1024);
+    }
+
+    #[test]
+    fn decimal_suffixes() {
+        assert_eq!(parse_size("3KB").unwrap(), 3_000);
+        assert_eq!(parse_size("3mb").unwrap(), 3_000_000);
+        assert_eq!(parse_size("1GB").unwrap(), 1_000_000_000);
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("K").is_err());
+        assert!(parse_size("-1K").is_err());
+        assert!(parse_size("1.5M").is_err());
+        assert!(parse_size("12X").is_err());
+        assert!(parse_size("1 K").is_err());
+        assert!(parse_size(&format!("{}G", usize::MAX)).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::cli::SortOrder;
+
+/// Sorts `lines`, given as pairs of a line number and the content to print, by their content.
+/// The sort is stable, so equal lines keep their selection order. Lines starting with the same
+/// number are sorted by their whole content, like `sort -n` does.
+pub(crate) fn sort_lines(lines: &mut [(usize, Vec<u8>)], order: SortOrder) {
+    match order {
+        SortOrder::Lexical => lines.sort_by(|(_, a), (_, b)| a.cmp(b)),
+        SortOrder::Reverse => lines.sort_by(|(_, a), (_, b)| b.cmp(a)),
+        SortOrder::Numeric => {
+            lines.sort_by(|(_, a), (_, b)| {
+                numeric_key(a)
+                    .total_cmp(&numeric_key(b))
+                    .then_with(|| a.cmp(b))
+            });
+        }
+    }
+}
+
+/// Returns the number at the start of `line`, after any leading whitespace, like `sort -n`.
+/// Lines that don't start with a number are treated as 0.
+fn numeric_key(line: &[u8]) -> f64 {
+    let line = line.trim_ascii_start();
+    let mut end = usize::from(line.first() == Some(&b'-'));
+    let mut seen_dot = false;
+    while let Some(&b) = line.get(end) {
+        match b {
+            b'0'..=b'9' => {}
+            b'.' if !seen_dot => seen_dot = true,
+            _ => break,
+        }
+        end += 1;
+    }
+    std::str::from_utf8(&line[..end])
+        .ok()
+        .and_then(|number| number.parse().ok())
+        .unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(lines: &[&str], order: SortOrder) -> Vec<usize> {
+        let mut lines: Vec<_> = lines
+            .iter()
+            .enumerate()
+            .map(|(line_num, line)| (line_num, line.as_bytes().to_vec()))
+            .collect();
+        sort_lines(&mut lines, order);
+        lines.into_iter().map(|(line_num, _)| line_num).collect()
+    }
+
+    #[test]
+    fn lexical() {
+        assert_eq!(
+            sorted(&["b\n", "a\n", "c\n", "a\n"], SortOrder::Lexical),
+            [1, 3, 0, 2]
+        );
+        assert_eq!(sorted(&["10\n", "9\n"], SortOrder::Lexical), [0, 1]);
+    }
+
+    #[test]
+    fn reverse() {
+        assert_eq!(
+            sorted(&["b\n", "a\n", "c\n"], SortOrder::Reverse),
+            [2, 0, 1]
+        );
+    }
+
+    #[test]
+    fn numeric() {
+        let lines = ["10 apples\n", "  9\n", "-2.5\n", "none\n", "1.5.3\n"];
+        assert_eq!(sorted(&lines, SortOrder::Numeric), [2, 3, 4, 1, 0]);
+    }
+
+    #[test]
+    fn numeric_ties() {
+        let lines = ["2 b\n", "1\n", "2 a\n", "x\n", "2 b\n", "0\n"];
+        assert_eq!(sorted(&lines, SortOrder::Numeric), [5, 3, 1, 2, 0, 4]);
+    }
+
+    #[test]
+    fn numeric_key_prefixes() {
+        assert_eq!(numeric_key(b"42\n"), 42.0);
+        assert_eq!(numeric_key(b"\t-3.25 x"), -3.25);
+        assert_eq!(numeric_key(b"-"), 0.0);
+        assert_eq!(numeric_key(b"."), 0.0);
+        assert_eq!(numeric_key(b""), 0.0);
+    }
+}
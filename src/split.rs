@@ -0,0 +1,146 @@
+use crate::paths;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+/// Where the printed lines are written: stdout (or the `--output` file), or numbered files with
+/// `--split-every`.
+pub(crate) enum Destination<W: Write> {
+    Stdout(W),
+    Files(SplitFiles),
+}
+
+impl<W: Write> Write for Destination<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Destination::Stdout(writer) => writer.write(buf),
+            Destination::Files(writer) => writer.write(buf),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Destination::Stdout(writer) => writer.write_all(buf),
+            Destination::Files(writer) => writer.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Destination::Stdout(writer) => writer.flush(),
+            Destination::Files(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Writes lines into consecutive files of `lines_per_file` lines each, named after a prefix and
+/// their two-digit (or more) index, like `split -d` does: `part_00`, `part_01`, etc.
+///
+/// A file is only created once a byte is written into it, so there are no empty files.
+pub(crate) struct SplitFiles {
+    prefix: String,
+    lines_per_file: usize,
+    separator: u8,
+    buffer_size: usize,
+    /// The file being written, and the number of lines written into it
+    current: Option<(BufWriter<File>, usize)>,
+    n_files: usize,
+}
+
+impl SplitFiles {
+    pub(crate) fn new(
+        prefix: String,
+        lines_per_file: usize,
+        separator: u8,
+        buffer_size: usize,
+    ) -> Self {
+        Self {
+            prefix,
+            lines_per_file,
+            separator,
+            buffer_size,
+            current: None,
+            n_files: 0,
+        }
+    }
+
+    fn next_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}{:02}", self.prefix, self.n_files))
+    }
+}
+
+impl Write for SplitFiles {
+    /// Writes `buf` up to the end of the current file, if it ends within `buf`.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let (file, n_lines) = match &mut self.current {
+            Some(current) => current,
+            None => {
+                let path = self.next_path();
+                let file = File::create(&path).map_err(|err| {
+                    io::Error::new(
+                        err.kind(),
+                        format!("Couldn't create `{}`: {err}", paths::display(&path)),
+                    )
+                })?;
+                self.n_files += 1;
+                self.current
+                    .insert((BufWriter::with_capacity(self.buffer_size, file), 0))
+            }
+        };
+
+        let lines_left = self.lines_per_file - *n_lines;
+        let (len, is_full) = match buf
+            .iter()
+            .enumerate()
+            .filter(|&(_, &byte)| byte == self.separator)
+            .nth(lines_left - 1)
+        {
+            Some((i, _)) => (i + 1, true),
+            None => (buf.len(), false),
+        };
+        let written = file.write(&buf[..len])?;
+        *n_lines += buf[..written]
+            .iter()
+            .filter(|&&byte| byte == self.separator)
+            .count();
+
+        if is_full && written == len {
+            let (mut file, _) = self.current.take().expect("the current file was just set");
+            file.flush()?;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.current {
+            Some((file, _)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_every_n_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let prefix = format!("{}/part_", dir.path().display());
+
+        let mut files = SplitFiles::new(prefix.clone(), 2, b'\n', 16);
+        files.write_all(b"1\n2\n3").unwrap();
+        files.write_all(b"\n4\n").unwrap();
+        files.write_all(b"5\n").unwrap();
+        files.flush().unwrap();
+
+        let read = |i: usize| std::fs::read_to_string(format!("{prefix}{i:02}")).unwrap();
+        assert_eq!(read(0), "1\n2\n");
+        assert_eq!(read(1), "3\n4\n");
+        assert_eq!(read(2), "5\n");
+        assert!(!dir.path().join("part_03").exists());
+    }
+}
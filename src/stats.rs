@@ -0,0 +1,228 @@
+use crate::escape::split_terminator;
+use crate::histogram::Histogram;
+use crate::i18n;
+use crate::line_counter::{LineEndings, count_lines};
+use crate::line_reader::LineReader;
+use crate::line_selector::{LineSelector, RawLineSelector};
+use crate::size::format_size;
+use crate::{LineSource, available_threads, default_read_buffer_size, open_file};
+use crate::{parse_line_selectors, paths, random};
+use anyhow::Context;
+use content_inspector::ContentType;
+use std::fmt::Display;
+use std::io::{BufReader, Write};
+use std::path::Path;
+
+/// Prints the number of lines of the file at `path` and statistics about their lengths, followed
+/// by a histogram of the lengths if `histogram` is true. With a `line_selector`, only the lines it
+/// selects are measured.
+///
+/// Lengths are in bytes and don't include line endings.
+pub(crate) fn run(
+    path: &Path,
+    line_selector: Option<RawLineSelector>,
+    histogram: bool,
+) -> anyhow::Result<()> {
+    let file = open_file(path)?;
+    let read_buffer = default_read_buffer_size(&file)?;
+    let mut file = BufReader::with_capacity(read_buffer, file);
+    let n_lines = count_lines(&mut file, available_threads(), b'\n')?;
+
+    let line_selectors = match line_selector {
+        Some(raw_line_selector) => {
+            let mut rng = random::Rng::from_entropy();
+            Some(parse_line_selectors(
+                &[raw_line_selector],
+                n_lines,
+                &mut rng,
+            )?)
+        }
+        None => None,
+    };
+    let line_nums: Box<dyn Iterator<Item = usize>> = match &line_selectors {
+        Some(line_selectors) => {
+            // lines are read in order, and each of them is measured once
+            let mut line_nums: Vec<_> = line_selectors[0].iter().collect();
+            line_nums.sort_unstable();
+            line_nums.dedup();
+            Box::new(line_nums.into_iter())
+        }
+        None => Box::new(0..n_lines),
+    };
+
+    let mut line_reader = LineReader::new(file);
+    let mut buf = Vec::new();
+    let mut lengths = LineLengths::default();
+    let mut histogram = histogram.then(Histogram::default);
+    for line_num in line_nums {
+        buf.clear();
+        line_reader.read_specific_line(&mut buf, line_num)?;
+        let (content, _) = split_terminator(&buf, b'\n');
+        lengths.add(line_num, content.len());
+        if let Some(histogram) = &mut histogram {
+            histogram.add(content.len());
+        }
+    }
+
+    print_line_lengths(&lengths, histogram.as_ref()).context("Failed to output statistics")
+}
+
+fn print_line_lengths(lengths: &LineLengths, histogram: Option<&Histogram>) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    write!(stdout, "{lengths}")?;
+    if let Some(histogram) = histogram
+        && lengths.n_lines != 0
+    {
+        writeln!(stdout)?;
+        histogram.print(&mut stdout)?;
+    }
+    Ok(())
+}
+
+/// Statistics about the lengths of lines.
+#[derive(Default)]
+struct LineLengths {
+    n_lines: usize,
+    total_len: u64,
+    /// The length and line number of the shortest line
+    shortest: Option<(usize, usize)>,
+    /// The length and line number of the longest line
+    longest: Option<(usize, usize)>,
+}
+
+impl LineLengths {
+    fn add(&mut self, line_num: usize, len: usize) {
+        self.n_lines += 1;
+        self.total_len += len as u64;
+        if self.shortest.is_none_or(|(shortest, _)| len < shortest) {
+            self.shortest = Some((len, line_num));
+        }
+        if self.longest.is_none_or(|(longest, _)| len > longest) {
+            self.longest = Some((len, line_num));
+        }
+    }
+}
+
+impl Display for LineLengths {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Line count: {}", self.n_lines)?;
+        if let (Some((shortest, shortest_line_num)), Some((longest, longest_line_num))) =
+            (self.shortest, self.longest)
+        {
+            let mean = self.total_len / self.n_lines as u64;
+            writeln!(
+                f,
+                "Shortest line: {} (line {})",
+                format_size(shortest as u64),
+                shortest_line_num + 1
+            )?;
+            writeln!(
+                f,
+                "Longest line: {} (line {})",
+                format_size(longest as u64),
+                longest_line_num + 1
+            )?;
+            writeln!(f, "Mean line length: {}", format_size(mean))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the number of content bytes selected by each line selector.
+pub(crate) fn count_bytes(
+    line_selectors: &[LineSelector],
+    lines: &mut impl LineSource,
+) -> anyhow::Result<Vec<usize>> {
+    line_selectors
+        .iter()
+        .map(|line_selector| {
+            line_selector
+                .iter_in_selection_order()
+                .map(|line_num| Ok(lines.get(line_num)?.len()))
+                .sum()
+        })
+        .collect()
+}
+
+/// Prints the byte count of each line selector followed by their total, `wc -c` style.
+pub(crate) fn print_byte_counts(
+    writer: &mut impl Write,
+    line_selectors: &[LineSelector],
+    byte_counts: &[usize],
+) -> std::io::Result<()> {
+    let total: usize = byte_counts.iter().sum();
+    let width = total.to_string().len();
+    for (line_selector, byte_count) in line_selectors.iter().zip(byte_counts) {
+        writeln!(writer, "{byte_count:>width$} {}", line_selector.raw)?;
+    }
+    writeln!(writer, "{total:>width$} total")
+}
+
+/// Wraps a [`LineSource`], keeping a tally of the lines and bytes read through it.
+///
+/// Each printed line is read exactly once, so this tallies the printed lines as well.
+pub(crate) struct TalliedLines<'a, S> {
+    lines: &'a mut S,
+    n_lines: usize,
+    n_bytes: u64,
+}
+
+impl<'a, S: LineSource> TalliedLines<'a, S> {
+    pub(crate) fn new(lines: &'a mut S) -> Self {
+        Self {
+            lines,
+            n_lines: 0,
+            n_bytes: 0,
+        }
+    }
+}
+
+impl<S: LineSource> LineSource for TalliedLines<'_, S> {
+    fn get(&mut self, line_num: usize) -> anyhow::Result<&[u8]> {
+        let line = self.lines.get(line_num)?;
+        self.n_lines += 1;
+        self.n_bytes += line.len() as u64;
+        Ok(line)
+    }
+}
+
+/// Formats a summary like `3 selectors, 128 lines, 9.4 KiB from file.txt`.
+pub(crate) fn format_summary<S>(
+    n_selectors: usize,
+    lines: &TalliedLines<'_, S>,
+    path: &Path,
+) -> String {
+    i18n::message(
+        "summary",
+        &[
+            ("selectors", n_selectors.into()),
+            ("lines", lines.n_lines.into()),
+            ("size", format_size(lines.n_bytes).into()),
+            ("path", paths::display(path).to_string().into()),
+        ],
+    )
+}
+
+/// Metadata about the input file, printed by `--show-meta`.
+pub(crate) struct FileMeta<'a> {
+    pub(crate) path: &'a Path,
+    pub(crate) len: u64,
+    pub(crate) n_lines: usize,
+    pub(crate) content_type: ContentType,
+    pub(crate) line_endings: LineEndings,
+}
+
+impl Display for FileMeta<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "File: {}", paths::display(self.path))?;
+        if self.len < 1024 {
+            writeln!(f, "Size: {}", format_size(self.len))?;
+        } else {
+            writeln!(f, "Size: {} ({} bytes)", format_size(self.len), self.len)?;
+        }
+        writeln!(f, "Line count: {}", self.n_lines)?;
+        writeln!(f, "Encoding: {}", self.content_type)?;
+        writeln!(f, "Line endings: {}", self.line_endings)?;
+        writeln!(f)
+    }
+}
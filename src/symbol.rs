@@ -0,0 +1,164 @@
+use crate::line_selector::RawLineSelector;
+use crate::paths;
+use anyhow::Context;
+use serde_json::Value;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::Command;
+
+/// The ctags kinds that are treated as functions by `--function`. The names of the kinds vary
+/// between the languages supported by ctags.
+const FUNCTION_KINDS: &[&str] = &["function", "method", "func", "subroutine", "procedure"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SymbolKind {
+    Any,
+    Function,
+}
+
+/// Resolves the symbols called `name` in the file at `path` to the line ranges they span, using
+/// universal-ctags.
+///
+/// # Errors:
+///
+/// This method returns an error if ctags can't be run or fails, or if no symbol called `name`
+/// is found.
+pub(crate) fn resolve(
+    path: &Path,
+    name: &str,
+    kind: SymbolKind,
+) -> anyhow::Result<Vec<RawLineSelector>> {
+    let output = Command::new("ctags")
+        .args([
+            "--output-format=json",
+            "--fields=+neK",
+            "--sort=no",
+            "-o",
+            "-",
+        ])
+        .arg(path)
+        .output()
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => {
+                anyhow::anyhow!("universal-ctags must be installed to select lines by symbol name")
+            }
+            _ => anyhow::Error::new(err).context("Failed to run ctags"),
+        })?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "ctags failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+
+    let line_selectors = find_symbol(&output.stdout, name, kind)?;
+    if line_selectors.is_empty() {
+        match kind {
+            SymbolKind::Any => {
+                anyhow::bail!("Symbol `{name}` not found in '{}'", paths::display(path))
+            }
+            SymbolKind::Function => {
+                anyhow::bail!("Function `{name}` not found in '{}'", paths::display(path))
+            }
+        }
+    }
+    Ok(line_selectors)
+}
+
+/// Returns the line ranges of the symbols called `name` in the JSON output of ctags, in the order
+/// in which ctags reported them.
+fn find_symbol(
+    ctags_output: &[u8],
+    name: &str,
+    kind: SymbolKind,
+) -> anyhow::Result<Vec<RawLineSelector>> {
+    let mut line_selectors = Vec::new();
+    for line in ctags_output.split(|&byte| byte == b'\n') {
+        if line.is_empty() {
+            continue;
+        }
+        let tag: Value = serde_json::from_slice(line).context("Failed to parse ctags output")?;
+        if tag["_type"] != "tag" || tag["name"] != name {
+            continue;
+        }
+        if kind == SymbolKind::Function
+            && !tag["kind"]
+                .as_str()
+                .is_some_and(|kind| FUNCTION_KINDS.contains(&kind))
+        {
+            continue;
+        }
+
+        let Some(start) = tag["line"].as_i64() else {
+            continue;
+        };
+        let start = start as isize;
+        // tags without an end, like variables in some languages, span a single line
+        match tag["end"].as_i64() {
+            Some(end) if end as isize > start => {
+                line_selectors.push(RawLineSelector::Range(Some(start), Some(end as isize)));
+            }
+            _ => line_selectors.push(RawLineSelector::Single(start)),
+        }
+    }
+    Ok(line_selectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CTAGS_OUTPUT: &[u8] = br#"{"_type": "ptag", "name": "JSON_OUTPUT_VERSION", "path": "0.0"}
+{"_type": "tag", "name": "Cli", "path": "src/cli.rs", "line": 10, "kind": "struct", "end": 40}
+{"_type": "tag", "name": "parse", "path": "src/cli.rs", "line": 42, "kind": "method", "end": 50}
+{"_type": "tag", "name": "parse", "path": "src/cli.rs", "line": 60, "kind": "field"}
+{"_type": "tag", "name": "main", "path": "src/cli.rs", "line": 70, "kind": "function", "end": 70}
+"#;
+
+    #[test]
+    fn any_symbol() {
+        assert_eq!(
+            find_symbol(CTAGS_OUTPUT, "Cli", SymbolKind::Any).unwrap(),
+            [RawLineSelector::Range(Some(10), Some(40))]
+        );
+        assert_eq!(
+            find_symbol(CTAGS_OUTPUT, "parse", SymbolKind::Any).unwrap(),
+            [
+                RawLineSelector::Range(Some(42), Some(50)),
+                RawLineSelector::Single(60)
+            ]
+        );
+    }
+
+    #[test]
+    fn function() {
+        assert_eq!(
+            find_symbol(CTAGS_OUTPUT, "parse", SymbolKind::Function).unwrap(),
+            [RawLineSelector::Range(Some(42), Some(50))]
+        );
+        assert_eq!(
+            find_symbol(CTAGS_OUTPUT, "main", SymbolKind::Function).unwrap(),
+            [RawLineSelector::Single(70)]
+        );
+        assert!(
+            find_symbol(CTAGS_OUTPUT, "Cli", SymbolKind::Function)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn missing_symbol() {
+        assert!(
+            find_symbol(CTAGS_OUTPUT, "JSON_OUTPUT_VERSION", SymbolKind::Any)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn invalid_output() {
+        assert!(find_symbol(b"not json\n", "main", SymbolKind::Any).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+use crate::width::display_width;
+use std::borrow::Cow;
+
+/// Returns `line` with its tabs replaced by spaces up to the next tab stop, tab stops being
+/// `tab_width` columns apart, for `--tabs`. Columns are counted like terminals do, so escape
+/// sequences take up none and wide characters take up two.
+pub(crate) fn expand_tabs(line: &[u8], tab_width: usize) -> Cow<'_, [u8]> {
+    if !line.contains(&b'\t') {
+        return Cow::Borrowed(line);
+    }
+    let mut expanded = Vec::with_capacity(line.len() + tab_width);
+    let mut column = 0;
+    let mut segments = line.split(|&b| b == b'\t').peekable();
+    while let Some(segment) = segments.next() {
+        expanded.extend_from_slice(segment);
+        column += display_width(segment);
+        if segments.peek().is_some() {
+            let n_spaces = tab_width - column % tab_width;
+            expanded.resize(expanded.len() + n_spaces, b' ');
+            column += n_spaces;
+        }
+    }
+    Cow::Owned(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_to_tab_stops() {
+        for (line, expanded) in [
+            (&b"a\tb\n"[..], &b"a   b\n"[..]),
+            (b"\tindented\n", b"    indented\n"),
+            (b"abcd\te", b"abcd    e"),
+            (b"ab\t\tc", b"ab      c"),
+            (b"\x1b[31mab\x1b[0m\tc", b"\x1b[31mab\x1b[0m  c"),
+            ("界\tb".as_bytes(), "界  b".as_bytes()),
+        ] {
+            assert_eq!(expand_tabs(line, 4), expanded, "{line:?}");
+        }
+        assert!(matches!(expand_tabs(b"no tabs\n", 4), Cow::Borrowed(_)));
+        assert_eq!(expand_tabs(b"a\tb", 1), &b"a b"[..]);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::cli::Charset;
+use crate::paths;
+use anyhow::Context;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// The number of bytes looked at to recognize UTF-16 without a byte order mark.
+pub(crate) const SAMPLE_LEN: usize = 512;
+
+/// Returns the content of `file` transcoded to UTF-8 if it's in another encoding, or `None`
+/// otherwise, in which case `file` is rewound. With `Charset::Auto`, the encoding is recognized
+/// by the first bytes of `file`.
+///
+/// Like decompressed files, the content is transcoded once into an anonymous temporary file,
+/// which has as many lines as `file`.
+pub(crate) fn transcode(
+    file: &mut File,
+    path: &Path,
+    charset: Charset,
+) -> anyhow::Result<Option<File>> {
+    let charset = match charset {
+        Charset::Auto => {
+            let mut sample = Vec::with_capacity(SAMPLE_LEN);
+            file.take(SAMPLE_LEN as u64)
+                .read_to_end(&mut sample)
+                .and_then(|_| file.seek(SeekFrom::Start(0)))
+                .context("Failed to read from file")?;
+            detect(&sample)
+        }
+        charset => charset,
+    };
+    if matches!(charset, Charset::Auto | Charset::Utf8) {
+        return Ok(None);
+    }
+
+    let mut transcoded = tempfile::tempfile().context("Couldn't create a temporary file")?;
+    let mut reader = BufReader::new(&*file);
+    let mut writer = BufWriter::new(&mut transcoded);
+    match charset {
+        Charset::Utf16Le => transcode_utf16(&mut reader, &mut writer, u16::from_le_bytes),
+        Charset::Utf16Be => transcode_utf16(&mut reader, &mut writer, u16::from_be_bytes),
+        Charset::Latin1 => transcode_latin1(&mut reader, &mut writer),
+        Charset::Auto | Charset::Utf8 => unreachable!("UTF-8 is read as is"),
+    }
+    .and_then(|()| writer.flush())
+    .with_context(|| format!("Failed to transcode `{}`", paths::display(path)))?;
+    drop(writer);
+    transcoded
+        .rewind()
+        .context("Failed to rewind the transcoded file")?;
+    Ok(Some(transcoded))
+}
+
+/// Recognizes UTF-16 by its byte order mark, or by the NUL bytes of ASCII characters on the same
+/// side of every code unit. Anything else is left to be read as is.
+pub(crate) fn detect(sample: &[u8]) -> Charset {
+    match sample {
+        [0xff, 0xfe, ..] => return Charset::Utf16Le,
+        [0xfe, 0xff, ..] => return Charset::Utf16Be,
+        _ => {}
+    }
+    let code_units = sample.len() / 2;
+    let count_nuls = |parity| {
+        sample[..code_units * 2]
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    let (even_nuls, odd_nuls) = (count_nuls(0), count_nuls(1));
+    if code_units == 0 {
+        Charset::Auto
+    } else if odd_nuls * 2 > code_units && even_nuls == 0 {
+        Charset::Utf16Le
+    } else if even_nuls * 2 > code_units && odd_nuls == 0 {
+        Charset::Utf16Be
+    } else {
+        Charset::Auto
+    }
+}
+
+/// Transcodes UTF-16 code units read with `from_bytes`, replacing unpaired surrogates with
+/// `U+FFFD`. A byte order mark is dropped.
+fn transcode_utf16(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    from_bytes: fn([u8; 2]) -> u16,
+) -> io::Result<()> {
+    let mut error = None;
+    let code_units = std::iter::from_fn(|| {
+        let mut code_unit = [0; 2];
+        match reader.read_exact(&mut code_unit) {
+            Ok(()) => Some(from_bytes(code_unit)),
+            // a trailing odd byte is dropped
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(err) => {
+                error = Some(err);
+                None
+            }
+        }
+    });
+    let mut chars = char::decode_utf16(code_units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .peekable();
+    chars.next_if_eq(&'\u{feff}');
+    let mut buf = [0; 4];
+    for c in chars {
+        writer.write_all(c.encode_utf8(&mut buf).as_bytes())?;
+    }
+    error.map_or(Ok(()), Err)
+}
+
+fn transcode_latin1(reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<()> {
+    let mut buf = [0; 4];
+    for byte in reader.bytes() {
+        writer.write_all(char::from(byte?).encode_utf8(&mut buf).as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcoded(content: &[u8], charset: Charset) -> Option<String> {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(content).unwrap();
+        file.rewind().unwrap();
+        let mut transcoded = transcode(&mut file, Path::new("file"), charset).unwrap()?;
+        let mut content = String::new();
+        transcoded.read_to_string(&mut content).unwrap();
+        Some(content)
+    }
+
+    #[test]
+    fn detects_utf16() {
+        assert_eq!(detect(b"\xff\xfea\x00"), Charset::Utf16Le);
+        assert_eq!(detect(b"\xfe\xff\x00a"), Charset::Utf16Be);
+        assert_eq!(detect(b"a\x00b\x00\n\x00"), Charset::Utf16Le);
+        assert_eq!(detect(b"\x00a\x00b\x00\n"), Charset::Utf16Be);
+        assert_eq!(detect(b"plain text\n"), Charset::Auto);
+        assert_eq!(
+            detect(b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00"),
+            Charset::Auto
+        );
+        assert_eq!(detect(b""), Charset::Auto);
+    }
+
+    #[test]
+    fn transcodes_to_utf8() {
+        assert_eq!(
+            transcoded(
+                b"\xff\xfeo\x00n\x00e\x00\n\x00\xe9\x00\n\x00",
+                Charset::Auto
+            )
+            .unwrap(),
+            "one\né\n"
+        );
+        assert_eq!(
+            transcoded(b"\x00o\x00k\x00\n\xd8\x3d\xde\x00", Charset::Utf16Be).unwrap(),
+            "ok\n😀"
+        );
+        assert_eq!(transcoded(b"caf\xe9\n", Charset::Latin1).unwrap(), "café\n");
+        assert!(transcoded(b"plain\n", Charset::Auto).is_none());
+        assert!(transcoded(b"a\x00b\x00", Charset::Utf8).is_none());
+    }
+}
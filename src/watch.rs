@@ -0,0 +1,66 @@
+use crate::cli::Cli;
+use crate::{follow, i18n, pager, paths, print_files};
+use anyhow::Context;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often the watched files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Moves the cursor to the top left corner of the terminal, then clears the screen.
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+/// What tells a version of a file from the next one: its length, modification time, and
+/// identity on its filesystem (which changes when an editor saves the file by replacing it), or
+/// `None` if the file is missing.
+type Stamp = Option<(u64, Option<SystemTime>, Option<(u64, u64)>)>;
+
+/// Prints the lines selected by `args` from `files`, then prints them again whenever one of the
+/// files changes, for `--watch`. This only returns once the output is closed (e.g.: `line --watch
+/// | head`), since errors are printed until the next change instead.
+pub(crate) fn watch(
+    args: &Cli,
+    files: &[PathBuf],
+    print_file_headers: bool,
+    is_terminal: bool,
+) -> anyhow::Result<()> {
+    let mut stamps: Vec<Stamp> = files.iter().map(|path| stamp(path)).collect();
+    loop {
+        let mut stdout = std::io::stdout().lock();
+        let result = if is_terminal {
+            write!(stdout, "{CLEAR_SCREEN}").context("Failed to clear the screen")
+        } else {
+            Ok(())
+        }
+        .and_then(|()| print_files(args, files, print_file_headers, &mut stdout))
+        .and_then(|()| stdout.flush().context("Failed to flush output"));
+        match result {
+            Err(err) if pager::is_broken_pipe(&err) => return Ok(()),
+            // the file may be half-written, e.g.: while it's being saved
+            Err(err) => eprintln!("{}: {err:?}", i18n::message("error", &[])),
+            Ok(()) => {}
+        }
+        drop(stdout);
+
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let new_stamps: Vec<Stamp> = files.iter().map(|path| stamp(path)).collect();
+            if new_stamps != stamps {
+                stamps = new_stamps;
+                break;
+            }
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(paths::for_opening(path)).ok()?;
+    Some((
+        metadata.len(),
+        metadata.modified().ok(),
+        follow::identity(&metadata),
+    ))
+}
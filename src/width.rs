@@ -0,0 +1,133 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+/// Returns the number of terminal columns `text` takes up. Escape sequences (e.g.: colors from
+/// the input) take up no columns, and wide characters (e.g.: CJK and emoji) take up two.
+pub(crate) fn display_width(text: &[u8]) -> usize {
+    let mut width = 0;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let escape_start = rest.iter().position(|&b| b == ESC).unwrap_or(rest.len());
+        width += String::from_utf8_lossy(&rest[..escape_start]).width();
+        rest = &rest[escape_start..];
+        rest = &rest[escape_len(rest)..];
+    }
+    width
+}
+
+/// Returns the length of the longest prefix of `text` that takes up at most `width` columns, but
+/// at least one character, without cutting escape sequences or characters in half. Each byte of
+/// invalid UTF-8 takes up one column.
+pub(crate) fn split_at_width(text: &[u8], width: usize) -> usize {
+    let mut columns = 0;
+    let mut offset = 0;
+    while offset < text.len() {
+        let escape_len = escape_len(&text[offset..]);
+        if escape_len > 0 {
+            offset += escape_len;
+            continue;
+        }
+        let char_len = match text[offset] {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+        let (char_len, char_width) = match text
+            .get(offset..offset + char_len)
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .and_then(|c| c.chars().next())
+        {
+            Some(c) => (char_len, c.width().unwrap_or(0)),
+            None => (1, 1),
+        };
+        if columns > 0 && columns + char_width > width {
+            break;
+        }
+        columns += char_width;
+        offset += char_len;
+    }
+    offset
+}
+
+/// Returns the length of the escape sequence at the start of `text`, or 0 if `text` doesn't start
+/// with one. An unterminated sequence runs to the end of `text`.
+fn escape_len(text: &[u8]) -> usize {
+    match text {
+        [ESC, b'[', params @ ..] => {
+            // CSI: parameter and intermediate bytes, then a final byte in `@..=~`
+            params
+                .iter()
+                .position(|b| (b'@'..=b'~').contains(b))
+                .map_or(text.len(), |i| i + 3)
+        }
+        [ESC, b']', payload @ ..] => {
+            // OSC: terminated by BEL or ST (`ESC \`)
+            payload
+                .iter()
+                .enumerate()
+                .find_map(|(i, &b)| match b {
+                    BEL => Some(i + 3),
+                    ESC if payload.get(i + 1) == Some(&b'\\') => Some(i + 4),
+                    _ => None,
+                })
+                .unwrap_or(text.len())
+        }
+        [ESC, rest @ ..] => {
+            // other sequences: intermediate bytes in ` ..=/`, then a final byte (e.g.: `ESC ( B`)
+            rest.iter()
+                .position(|b| !(b' '..=b'/').contains(b))
+                .map_or(text.len(), |i| i + 2)
+        }
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(display_width(b""), 0);
+        assert_eq!(display_width(b"abc"), 3);
+        assert_eq!(display_width("é".as_bytes()), 1);
+    }
+
+    #[test]
+    fn wide_characters() {
+        assert_eq!(display_width("日本".as_bytes()), 4);
+        assert_eq!(display_width("a🦀".as_bytes()), 3);
+    }
+
+    #[test]
+    fn escape_sequences() {
+        assert_eq!(display_width(b"\x1b[31;1mred\x1b[0m"), 3);
+        assert_eq!(
+            display_width(b"\x1b]8;;https://example.com\x07link\x1b]8;;\x07"),
+            4
+        );
+        assert_eq!(display_width(b"\x1b]0;title\x1b\\ab"), 2);
+        assert_eq!(display_width(b"a\x1b(Bb"), 2);
+    }
+
+    #[test]
+    fn splits_at_width() {
+        assert_eq!(split_at_width(b"abcdef", 4), 4);
+        assert_eq!(split_at_width(b"abc", 4), 3);
+        // the escape sequence takes up no columns, and the wide character doesn't fit
+        assert_eq!(split_at_width("\x1b[31mab日本".as_bytes(), 3), 7);
+        assert_eq!(split_at_width("日本".as_bytes(), 1), 3);
+        assert_eq!(split_at_width(b"\xff\xfeab", 3), 3);
+        assert_eq!(split_at_width(b"", 3), 0);
+    }
+
+    #[test]
+    fn unterminated_escape_sequences() {
+        assert_eq!(display_width(b"ab\x1b[31"), 2);
+        assert_eq!(display_width(b"ab\x1b"), 2);
+        assert_eq!(display_width(b"ab\x1b]0;title"), 2);
+    }
+}
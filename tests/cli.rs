@@ -270,13 +270,14 @@ fn empty_file() {
 }
 
 #[test]
-fn without_plain_flag() {
+fn plain_never_keeps_decorations_in_pipe() {
     let file = NamedTempFile::new("file").unwrap();
     file.write_str("one\ntwo\nthree").unwrap();
 
     Command::cargo_bin(BIN_NAME)
         .unwrap()
         .arg("-n=-3")
+        .arg("--plain=never")
         .arg(file.path())
         .assert()
         .success()
@@ -512,7 +513,9 @@ fn color_works() {
         .arg("-a")
         .arg("1")
         .arg("--color=always")
+        .arg("--plain=never")
         .arg(file.path())
+        .env_remove("COLORFGBG")
         .assert()
         .success()
         .stdout(format!(
@@ -526,6 +529,7 @@ fn color_works() {
         .arg("-a")
         .arg("1")
         .arg("--color=never")
+        .arg("--plain=never")
         .arg(file.path())
         .assert()
         .success()
@@ -563,3 +567,2801 @@ fn plain_arg_works() {
         .success()
         .stdout("two\nthree\n");
 }
+
+#[test]
+fn ordered_and_overlapping_selections() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\nfive\n").unwrap();
+
+    // printed in file order, so lines are streamed
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1,4:5:2")
+        .arg("-a=1")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\ntwo\n--\nfour\nfive\n");
+
+    // lines are selected in descending order, so context lines are printed twice and read ahead
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=3,2")
+        .arg("-c=1")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\nthree\nfour\n--\none\ntwo\nthree\n");
+}
+
+#[test]
+fn buffer_sizes() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2:3")
+        .arg("--read-buffer=1")
+        .arg("--write-buffer=4KiB")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\nthree\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--read-buffer=1X")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(starts_with(
+            "error: invalid value '1X' for '--read-buffer <SIZE>': Unknown size suffix `X`",
+        ));
+}
+
+#[test]
+fn bench_subcommand() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("bench")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(starts_with(format!(
+            "{}: 14 B, 3 line(s)\n",
+            file.path().display()
+        )))
+        .stdout(predicates::str::contains("count (1 thread)"))
+        .stdout(predicates::str::contains("random lines"))
+        .stdout(predicates::str::contains("dump all lines"))
+        .stdout(predicates::str::contains("mmap      count (1 thread)"))
+        .stdout(predicates::str::contains("count (cold index)"))
+        .stdout(predicates::str::contains("count (warm index)"));
+
+    // the index built by benchmarking isn't left behind
+    assert!(!file.path().with_extension("line-idx").exists());
+}
+
+#[test]
+fn checksum_of_selected_lines() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\n").unwrap();
+
+    // sha256 of "two\nthree\n"
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2:3")
+        .arg("--checksum=sha256")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(format!(
+            "f3952ccd5acbc3122b2fdc39d122b73e55f403fcb49dc411de7da4b4e987c07f  {}\n",
+            file.path().display()
+        ));
+}
+
+#[test]
+fn count_bytes() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2:3,1,-1")
+        .arg("--count-bytes")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("10 2:3\n 4 1\n 6 -1\n20 total\n");
+}
+
+#[test]
+fn summary() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\n").unwrap();
+    let summary = format!(
+        "2 selectors, 3 lines, 14 B from {}\n",
+        file.path().display()
+    );
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2:3,1")
+        .arg("--summary")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\nthree\none\n")
+        .stderr(summary.clone());
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--summary")
+        .arg("--plain=never")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(format!(
+            "Line: 1\n1: one\n\n1 selector, 1 line, 4 B from {}\n",
+            file.path().display()
+        ))
+        .stderr("");
+}
+
+#[test]
+fn show_meta() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\r\ntwo\nthree").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("--show-meta")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(format!(
+            "File: {}\nSize: 14 B\nLine count: 3\nEncoding: UTF-8\nLine endings: mixed (1 LF, 1 \
+            CRLF)\n\ntwo\n",
+            file.path().display()
+        ));
+}
+
+#[test]
+fn message_pack_output() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\n").unwrap();
+
+    let mut expected = b"\x83\xa6number\x01\xa7content\xa4one\n\xa8selected\xc2".to_vec();
+    expected.extend_from_slice(b"\x83\xa6number\x02\xa7content\xa4two\n\xa8selected\xc3");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("--before=1")
+        .arg("--output-format=msgpack")
+        .arg("--color=always")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(expected);
+}
+
+#[test]
+fn align_columns() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("name,age\nalice,30\nbob,4\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1,3")
+        .arg("--align=,")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("name  age\nbob   4\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=3")
+        .arg("--before=1")
+        .arg("--align=,")
+        .arg("--plain=never")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Line: 3\n2: alice,30\n3: bob  4\n");
+}
+
+#[test]
+fn json_fields() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str(concat!(
+        "{\"ts\": 1, \"level\": \"info\", \"msg\": \"started\"}\n",
+        "not json\n",
+        "{\"ts\": 20, \"level\": \"error\", \"msg\": \"failed\", \"code\": 2}\n",
+    ))
+    .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=:")
+        .arg("--json-fields=level,msg")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(concat!(
+            "{\"level\":\"info\",\"msg\":\"started\"}\n",
+            "not json\n",
+            "{\"level\":\"error\",\"msg\":\"failed\"}\n",
+        ));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1,3")
+        .arg("--json-fields=ts,level,msg")
+        .arg("--json-table")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("1   info   started\n20  error  failed\n");
+}
+
+#[test]
+fn themes() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\n").unwrap();
+    let light = format!(
+        "\x1b[35;1mLine: 2{CLEAR}\n{BOLD}1:{CLEAR} one\n\x1b[34;1m2:{CLEAR} {RED}two\n{CLEAR}"
+    );
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("-b=1")
+        .arg("--color=always")
+        .arg("--plain=never")
+        .arg("--theme=light")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(light.clone());
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("-b=1")
+        .arg("--color=always")
+        .arg("--plain=never")
+        .arg(file.path())
+        .env("COLORFGBG", "0;15")
+        .assert()
+        .success()
+        .stdout(light);
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("--color=always")
+        .arg("--plain=never")
+        .arg("--theme=dark")
+        .arg(file.path())
+        .env("COLORFGBG", "0;15")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{BLUE_BOLD}Line: 2{CLEAR}\n{GREEN_BOLD}2:{CLEAR} {RED}two\n{CLEAR}"
+        ));
+}
+
+#[test]
+fn grid() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2,9")
+        .arg("--after=1")
+        .arg("--grid")
+        .arg("--color=never")
+        .arg(file.path())
+        .env("COLUMNS", "12")
+        .assert()
+        .success()
+        .stdout(format!(
+            "────┬───────\n    │ File: {}\n────┼───────\n    │ Line: 2\n  2 │ two\n  3 │ \
+            three\n────┼───────\n    │ Line: 9\n  9 │ nine\n 10 │ ten\n────┴───────\n",
+            file.path().display()
+        ));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--grid")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("cannot be used with"));
+}
+
+#[test]
+fn wrap() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("abcdefghijkl\nxy\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1:2")
+        .arg("--wrap=7")
+        .arg("--plain=never")
+        .arg("--color=never")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Lines: 1:2\n1: abcd\n ↪ efgh\n ↪ ijkl\n2: xy\n");
+
+    // the width of the terminal is the default
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--wrap")
+        .arg("--grid")
+        .arg("--color=never")
+        .arg(file.path())
+        .env("COLUMNS", "10")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            " 1 │ abcde\n ↪ │ fghij\n ↪ │ kl\n",
+        ));
+}
+
+#[test]
+fn quiet() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-q")
+        .arg("-n=2")
+        .arg(file.path())
+        .assert()
+        .code(0)
+        .stdout("")
+        .stderr("");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--quiet")
+        .arg("-n=1,3")
+        .arg(file.path())
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("");
+
+    // the exit codes are the same without `--quiet`
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=3")
+        .arg(file.path())
+        .assert()
+        .code(1);
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-q")
+        .arg("-n=1")
+        .arg("missing.txt")
+        .assert()
+        .code(3)
+        .stdout("");
+
+    // invalid arguments are told apart from missing files
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-q")
+        .arg("-n=1+0")
+        .arg("missing.txt")
+        .assert()
+        .code(2)
+        .stdout("");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("3 if a file doesn't exist"));
+}
+
+#[test]
+fn format_str() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=4,2")
+        .arg("--after=1")
+        .arg("--format-str={num}\\t{offset}\\t{selected}\\t{line}")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("4\t14\ttrue\tfour\n2\t4\ttrue\ttwo\n3\t8\tfalse\tthree\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--format-str={file}: {{{line}}}")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(format!("{}: {{one}}\n", file.path().display()));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--format-str={lines}")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Unknown placeholder `{lines}`"));
+}
+
+#[test]
+fn byte_range() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\n").unwrap();
+
+    // bytes 6 to 9 are `wo\nt`
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--bytes=6:9")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\nthree\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--bytes=6:9")
+        .arg("--exact")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("wo\nt");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--bytes=-3:")
+        .arg("--exact")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("ur\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--bytes=100")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Byte 100 is out of range"));
+}
+
+#[test]
+fn theme_file() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\n").unwrap();
+    let theme_file = NamedTempFile::new("theme.toml").unwrap();
+    theme_file
+        .write_str("selected = \"#ff5f87\"\nheader = \"yellow\"\n")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--color=always")
+        .arg("--plain=never")
+        .arg("--theme=dark")
+        .arg("--theme-file")
+        .arg(theme_file.path())
+        .arg(file.path())
+        .env("COLORTERM", "truecolor")
+        .assert()
+        .success()
+        .stdout(format!(
+            "\x1b[33mLine: 1{CLEAR}\n{GREEN_BOLD}1:{CLEAR} \x1b[38;2;255;95;135mone\n{CLEAR}"
+        ));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--color=always")
+        .arg("--plain")
+        .arg("--theme-file")
+        .arg(theme_file.path())
+        .arg(file.path())
+        .env_remove("COLORTERM")
+        .env("TERM", "xterm-256color")
+        .assert()
+        .success()
+        .stdout(format!("\x1b[38;5;204mone\n{CLEAR}"));
+
+    theme_file.write_str("selected = \"pink\"\n").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--color=always")
+        .arg("--theme-file")
+        .arg(theme_file.path())
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(format!(
+            "Error: Invalid theme file '{}'\n\nCaused by:\n    0: Invalid style for `selected`\n    1: \
+            Unknown color `pink`\n",
+            theme_file.path().display()
+        ));
+}
+
+#[test]
+fn style_override() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--color=always")
+        .arg("--plain=never")
+        .arg("--style=header=yellow")
+        .arg("--style")
+        .arg("selected=blue bold")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(format!(
+            "\x1b[33mLine: 1{CLEAR}\n{GREEN_BOLD}1:{CLEAR} \x1b[34;1mone\n{CLEAR}"
+        ));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--color=always")
+        .arg("--style=selected")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid --style `selected`"));
+}
+
+#[test]
+fn background_emphasis() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\r\ntwo").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1,2")
+        .arg("--color=always")
+        .arg("--plain=never")
+        .arg("--theme=dark")
+        .arg("--emphasis=background")
+        .arg(file.path())
+        .env_remove("COLORTERM")
+        .env("TERM", "xterm-256color")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{BLUE_BOLD}Line: 1{CLEAR}\n{GREEN_BOLD}1:{CLEAR} \x1b[48;5;237mone\x1b[K{CLEAR}\r\n\
+            \n{BLUE_BOLD}Line: 2{CLEAR}\n{GREEN_BOLD}2:{CLEAR} \x1b[48;5;237mtwo\x1b[K{CLEAR}"
+        ));
+}
+
+#[test]
+fn reverse_emphasis() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo").unwrap();
+
+    for theme in ["--theme=dark", "--theme=light"] {
+        Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .arg("-n=2")
+            .arg("--color=always")
+            .arg("--plain=always")
+            .arg(theme)
+            .arg("--emphasis=reverse")
+            .arg(file.path())
+            .env("TERM", "xterm-256color")
+            .assert()
+            .success()
+            .stdout(format!("\x1b[7mtwo{CLEAR}"));
+    }
+}
+
+#[test]
+fn dumb_terminal() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--color=always")
+        .arg("--plain")
+        .arg(file.path())
+        .env("TERM", "dumb")
+        .assert()
+        .success()
+        .stdout("one\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--color=always")
+        .arg("--plain")
+        .arg("--force")
+        .arg(file.path())
+        .env("TERM", "dumb")
+        .env_remove("COLORFGBG")
+        .assert()
+        .success()
+        .stdout(format!("{RED}one\n{CLEAR}"));
+}
+
+#[test]
+fn lock() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\n").unwrap();
+
+    // shared locks don't exclude each other
+    let locked_file = std::fs::File::open(file.path()).unwrap();
+    locked_file.lock_shared().unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("--lock")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\n")
+        .stderr("");
+}
+
+#[test]
+fn lock_waits_for_writers() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\n").unwrap();
+
+    let locked_file = std::fs::File::open(file.path()).unwrap();
+    locked_file.lock().unwrap();
+    let unlocker = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        drop(locked_file);
+    });
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("--lock")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\n")
+        .stderr(format!(
+            "Waiting for another process to unlock `{}`...\n",
+            file.path().display()
+        ));
+    unlocker.join().unwrap();
+}
+
+#[test]
+fn assume_line_length() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("001\n002\n003\n004").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=-1,2,4:1:-2")
+        .arg("--assume-line-length=4")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("004002\n004002\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--assume-line-length=6")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(
+            "Error: The file size (15 bytes) doesn't match lines of 6 bytes (check the value of \
+            --assume-line-length, which includes the line ending)\n",
+        );
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--assume-line-length=5")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr("Error: Line 1 isn't 5 bytes long (check the value of --assume-line-length)\n");
+}
+
+#[test]
+fn window() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\nfive\nsix\n")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=3")
+        .arg("--window=1:2")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\nthree\nfour\nfive\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=3")
+        .arg("--window=1")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Window `1` isn't of the form B:A (e.g.: 2:5)",
+        ));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=3")
+        .arg("--window=1:2")
+        .arg("-b=1")
+        .arg(file.path())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn sort_content() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("10 pears\ncherry\n9 apples\nbanana\n")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2,4,1")
+        .arg("--sort-content")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("10 pears\nbanana\ncherry\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1,3")
+        .arg("--sort-content=numeric")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("9 apples\n10 pears\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=:")
+        .arg("--sort-content=reverse")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("cherry\nbanana\n9 apples\n10 pears\n");
+
+    // selected lines keep their line numbers
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2:3")
+        .arg("--sort-content")
+        .arg("--plain=never")
+        .arg("--color=never")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("3: 9 apples\n2: cherry\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--sort-content")
+        .arg("-c=1")
+        .arg(file.path())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn sample() {
+    let file = NamedTempFile::new("file").unwrap();
+    let content: String = (1..=100).map(|i| format!("{i}\n")).collect();
+    file.write_str(&content).unwrap();
+
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--sample=5")
+        .arg("--sample-ordered")
+        .arg("-p")
+        .arg(file.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let lines: Vec<usize> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.parse().unwrap())
+        .collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines.is_sorted_by(|a, b| a < b));
+    assert!(lines.iter().all(|line| (1..=100).contains(line)));
+
+    // every line is selected when the sample is larger than the file
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\n").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--sample=10")
+        .arg("--sample-ordered")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\ntwo\nthree\n");
+
+    // only the lines of the file are kept, however large the sample
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--sample=18446744073709551615")
+        .arg("--sample-ordered")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\ntwo\nthree\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--sample-ordered")
+        .arg(file.path())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn random_selector() {
+    let file = NamedTempFile::new("file").unwrap();
+    let content: String = (1..=100).map(|i| format!("{i}\n")).collect();
+    file.write_str(&content).unwrap();
+
+    let random_lines = || {
+        let output = Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .arg("-n=1,rand(50:60, 3),-1")
+            .arg("--seed=42")
+            .arg("-p")
+            .arg(file.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect::<Vec<usize>>()
+    };
+
+    let lines = random_lines();
+    assert_eq!(lines.len(), 5);
+    assert_eq!((lines[0], lines[4]), (1, 100));
+    assert!(lines[1..4].is_sorted_by(|a, b| a < b));
+    assert!(lines[1..4].iter().all(|line| (50..=60).contains(line)));
+    // the same seed picks the same lines
+    assert_eq!(random_lines(), lines);
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=rand(1:5)")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(starts_with(
+            "error: invalid value 'rand(1:5)' for '--line <LINE_SELECTORS>': Random selectors are \
+            of the form rand(START:END, COUNT) (e.g.: rand(100:2000, 5))",
+        ));
+}
+
+#[test]
+fn max_file_size() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str(&"line\n".repeat(300)).unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--max-file-size=1K")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(format!(
+            "Error: `{}` is 1.5 KiB, which is more than the maximum file size of 1.0 KiB (use \
+            --force to read it anyway)\n",
+            file.path().display()
+        ));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--max-file-size=1K")
+        .arg("--force")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("line\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--max-file-size=2K")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("line\n");
+}
+
+#[test]
+fn line_buffered() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1,3")
+        .arg("--line-buffered")
+        .arg("--summary")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\nthree\n");
+}
+
+#[test]
+fn null_data() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_binary(b"./a b\n.txt\0./c.txt\0./d.txt\0")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1,-1")
+        .arg("--null-data")
+        .arg("-p")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(&b"./a b\n.txt\0./d.txt\0"[..]);
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=4")
+        .arg("-z")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Line 4 is out of range (input has 3 line(s) only)",
+        ));
+}
+
+#[test]
+fn record_delimiter() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("a;b\nc;d;e").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=2,-1", "--delimiter=;", "-p"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("b\nc;e");
+
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_binary(b"x\0y\x1ez\x1e").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=2", "--delimiter=\\x1e", "-p", "--allow-binary-files"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(&b"z\x1e"[..]);
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=1", "--delimiter=\\0", "-p"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(&b"x\0"[..]);
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=1", "--delimiter=ab"])
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Delimiter `ab` isn't a single ASCII character",
+        ));
+}
+
+#[test]
+fn offset_base() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("-a=1")
+        .arg("--offset-base=1000")
+        .arg("--plain=never")
+        .arg("--color=never")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Line: 2\n1002: two\n1003: three\n");
+
+    // line numbers past `usize::MAX` are printed as `usize::MAX`
+    for format in [
+        "--plain=never",
+        "--output-format=jsonl",
+        "--format-str={num}",
+    ] {
+        Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .arg("-n=2")
+            .arg("--offset-base=18446744073709551615")
+            .arg("--color=never")
+            .arg(format)
+            .arg(file.path())
+            .assert()
+            .success()
+            .stdout(predicates::str::contains("18446744073709551615"));
+    }
+}
+
+#[test]
+fn shell_init() {
+    for (shell, binding) in [
+        (
+            "bash",
+            r#"bind -m emacs-standard -x '"\C-g": __line_widget'"#,
+        ),
+        ("zsh", "bindkey -M emacs '^G' __line_widget"),
+        ("fish", r"bind \cg __line_widget"),
+    ] {
+        Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .arg("shell-init")
+            .arg(shell)
+            .assert()
+            .success()
+            .stdout(predicates::str::contains(binding));
+    }
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("shell-init")
+        .arg("tcsh")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn escape_control_only() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_binary(b"caf\xc3\xa9\t\x1b[31mred\x1b[0m\nbad \xff\r\n")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("-a=1")
+        .arg("--escape-control-only")
+        .arg("--allow-binary-files")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("café\t\\x1b[31mred\\x1b[0m\nbad \\xff\r\n");
+}
+
+#[test]
+fn decode() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("aGVsbG8=\nnot base64!\nd29ybGQ\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1:3")
+        .arg("--decode=base64")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("hello\nnot base64!\nworld\n");
+
+    // binary content is only escaped on a terminal, so it can be piped as is
+    file.write_str("1b5b33316d6869ff\n").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--decode=hex")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(&b"\x1b[31mhi\xff\n"[..]);
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--decode=hex")
+        .arg("--escape-control-only")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("\\x1b[31mhi\\xff\n");
+}
+
+#[test]
+fn ruler() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("short\na line of 20 columns\nmid\n")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2,3")
+        .arg("-b=1")
+        .arg("--ruler")
+        .arg("--plain=never")
+        .arg("--color=never")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(
+            "Line: 2\n   ----+----1----+----2\n1: short\n2: a line of 20 columns\n\n\
+             Line: 3\n   ----+----1----+----2\n3: mid\n",
+        );
+}
+
+#[test]
+fn elide_long() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("12:00:01 GET /a/very/long/path/to/something 200\nshort\n")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("-a=1")
+        .arg("--elide-long=16")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("12:00:01…[31 bytes]…hing 200\nshort\n");
+}
+
+#[test]
+fn show_eol() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("unix\nwindows\r\nlast").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=:")
+        .arg("--show-eol")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("unix␊\nwindows␍␊\r\nlast∅");
+}
+
+#[test]
+fn stats_histogram() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("ab\nabc\r\nabc\nabcde\n\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("stats")
+        .arg("--histogram")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(
+            "Line count: 5\n\
+             Shortest line: 0 B (line 5)\n\
+             Longest line: 5 B (line 4)\n\
+             Mean line length: 2 B\n\
+             \n\
+             Length     Lines\n\
+             0 B            1  ██████████████\n\
+             1 B – 2 B      0  \n\
+             2 B – 4 B      3  ████████████████████████████████████████\n\
+             4 B – 8 B      1  ██████████████\n",
+        );
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("stats")
+        .arg("-n=2:3")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(
+            "Line count: 2\n\
+             Shortest line: 3 B (line 2)\n\
+             Longest line: 3 B (line 2)\n\
+             Mean line length: 3 B\n",
+        );
+}
+
+#[test]
+fn count() {
+    let dir = TempDir::new().unwrap();
+    let (a, b, empty) = (dir.child("a"), dir.child("b"), dir.child("empty"));
+    a.write_str("one\ntwo\nthree").unwrap();
+    b.write_str(&"line\n".repeat(12)).unwrap();
+    empty.touch().unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["count", "a", "b", "empty"])
+        .assert()
+        .success()
+        .stdout(" 3 a\n12 b\n 0 empty\n15 total\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["count", "a"])
+        .assert()
+        .success()
+        .stdout("3 a\n");
+}
+
+#[test]
+fn sed_expression() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("a\nBEGIN\nb\nEND\nc\nd\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-e=/begin/I,/END/p")
+        .arg("-e=$")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("BEGIN\nb\nEND\nd\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-e=1,5d")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Expression `1,5d` isn't a sed address expression of the form ADDR1[,ADDR2][p]",
+        ));
+}
+
+#[test]
+fn sed_compat() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\nfive").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--compat", "sed", "-n", "4,$p;2p"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\nfour\nfive");
+}
+
+#[test]
+fn every() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--every=4")
+        .arg("--starting-at=2")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("2\n6\n10\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=3:7,1")
+        .arg("--every=2")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("3\n5\n7\n1\n");
+}
+
+#[test]
+fn chunk() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("1\n2\n3\n4\n5\n6\n7\n").unwrap();
+
+    for (chunk, expected) in [("1/3", "1\n2\n3\n"), ("2/3", "4\n5\n"), ("3/3", "6\n7\n")] {
+        Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .arg(format!("--chunk={chunk}"))
+            .arg("--plain")
+            .arg(file.path())
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--chunk=4/3")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Chunk 4 doesn't exist, chunks are numbered from 1 to 3",
+        ));
+}
+
+#[test]
+fn split_every() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.child("file");
+    file.write_str("1\n2\n3\n4\n5\n6\n7\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(dir.path())
+        .arg("-n=2:6")
+        .arg("--split-every=2")
+        .arg("--output-prefix=out/part_")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Couldn't create `out/part_00`"));
+
+    dir.child("out").create_dir_all().unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(dir.path())
+        .arg("-n=2:6")
+        .arg("--split-every=2")
+        .arg("--output-prefix=out/part_")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("");
+    dir.child("out/part_00").assert("2\n3\n");
+    dir.child("out/part_01").assert("4\n5\n");
+    dir.child("out/part_02").assert("6\n");
+    dir.child("out/part_03").assert(predicates::path::missing());
+}
+
+#[test]
+fn json_lines_output() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("-b=1")
+        .arg("--output-format=jsonl")
+        .arg("--output-version=1")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(
+            "{\"content\":\"one\\n\",\"number\":1,\"selected\":false,\"version\":1}\n\
+             {\"content\":\"two\\n\",\"number\":2,\"selected\":true,\"version\":1}\n",
+        );
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("--output-format=jsonl")
+        .arg("--output-version=3")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("3 is not in 1..=2"));
+
+    // line endings are left out of `content` since version 2
+    file.write_str("one\r\ntwo").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=:")
+        .arg("--output-format=jsonl")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(
+            "{\"content\":\"one\",\"line_ending\":\"\\r\\n\",\"number\":1,\"selected\":true,\
+             \"version\":2}\n\
+             {\"content\":\"two\",\"line_ending\":\"\",\"number\":2,\"selected\":true,\
+             \"version\":2}\n",
+        );
+
+    file.write_str("one\0two\0").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("-z")
+        .arg("--output-format=jsonl")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(
+            "{\"content\":\"two\",\"line_ending\":\"\\u0000\",\"number\":2,\"selected\":true,\
+             \"version\":2}\n",
+        );
+
+    // invalid UTF-8 is replaced in `content`, and kept as is in `content_base64`
+    file.write_binary(b"caf\xe9\n").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--output-format=jsonl")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(
+            "{\"content\":\"caf\u{fffd}\",\"content_base64\":\"Y2Fm6Q==\",\"line_ending\":\"\\n\",\
+             \"number\":1,\"selected\":true,\"version\":2}\n",
+        );
+}
+
+#[test]
+fn localized_messages() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LC_ALL", "de_DE.UTF-8")
+        .args(["-n=2:3", "--plain=never", "--color=never"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("Zeilen: 2:3\n"));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LC_ALL", "fr_FR.UTF-8")
+        .arg("-n=4")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Erreur: Invalid line selector: 4\n\nCaused by:\n    La ligne 4 est hors limites (l'entrée n'a que 3 ligne(s))\n",
+        ));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LC_ALL", "C")
+        .arg("-n=4")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Error: "));
+}
+
+#[test]
+fn strict_utf8() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_binary(b"ok\ncaf\xe9\nend\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1:3")
+        .arg("--strict-utf8")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stdout("ok\n")
+        .stderr(predicates::str::contains(
+            "Line 2 isn't valid UTF-8 (invalid byte at offset 3)",
+        ));
+
+    // context lines aren't checked
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("-a=1")
+        .arg("--strict-utf8")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(&b"ok\ncaf\xe9\n"[..]);
+}
+
+#[test]
+fn check_line_endings() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\r\ntwo\nthree\r\nfour\r\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("--check-line-endings")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\n")
+        .stderr(format!(
+            "Warning: `{}` has mixed line endings (3 CRLF, 1 LF): line 2 ends with LF\n",
+            file.path().display()
+        ));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("--check-line-endings=error")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicates::str::contains("line 2 ends with LF"));
+
+    file.write_str("one\ntwo\n").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("--check-line-endings=error")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stderr("");
+}
+
+#[test]
+fn canonical() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("--canonical")
+        .arg("--color=always")
+        .arg("--plain=never")
+        .arg(file.path())
+        .env("TERM", "xterm-256color")
+        .env("COLORTERM", "truecolor")
+        .env("COLORFGBG", "0;15")
+        .env("LC_ALL", "de_DE.UTF-8")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{BLUE_BOLD}Line: 2{CLEAR}\n{GREEN_BOLD}2:{CLEAR} {RED}two\n{CLEAR}"
+        ));
+}
+
+#[test]
+fn mark_file_bounds() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2,3")
+        .arg("--context=2")
+        .arg("--mark-file-bounds")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("--- (start of file) ---\none\ntwo\nthree\nfour\n--- (end of file) ---\n");
+
+    // context that fits in the file isn't marked
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2")
+        .arg("--context=1")
+        .arg("--mark-file-bounds")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\ntwo\nthree\n");
+}
+
+#[test]
+fn context_groups() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\n")
+        .unwrap();
+
+    // the context of lines 2 and 4 overlaps, so line 3 is printed once
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2:8:2")
+        .arg("--context=1")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2:8:5")
+        .arg("--after=1")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\nthree\n--\nseven\neight\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=2:8:5")
+        .arg("--after=1")
+        .arg("--group-separator=...")
+        .arg("--plain=never")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Lines: 2:8:5\n2: two\n3: three\n...\n7: seven\n8: eight\n");
+}
+
+#[test]
+fn context_groups_across_selectors() {
+    let file = NamedTempFile::new("file").unwrap();
+    let content: String = (1..=12).map(|n| format!("{n}\n")).collect();
+    file.write_str(&content).unwrap();
+
+    for (selectors, expected) in [
+        // the context of lines 1 and 3 overlaps, so line 2 is printed once
+        ("1,3", "1 2 3 4"),
+        ("1,10", "1 2 -- 9 10 11"),
+        ("1:4:2,10", "1 2 3 4 -- 9 10 11"),
+        // lines selected in descending order are printed in separate groups
+        ("3,1", "2 3 4 -- 1 2"),
+    ] {
+        let expected = expected.replace(' ', "\n") + "\n";
+        // aligning reads the lines into memory first instead of streaming them
+        for align in [None, Some("--align=;")] {
+            Command::cargo_bin(BIN_NAME)
+                .unwrap()
+                .arg(format!("-n={selectors}"))
+                .arg("--context=1")
+                .arg("--plain")
+                .args(align)
+                .arg(file.path())
+                .assert()
+                .success()
+                .stdout(expected.clone());
+        }
+    }
+
+    // the headers of decorated output separate the groups of different selectors
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1,3")
+        .arg("--context=1")
+        .arg("--plain=never")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Line: 1\n1: 1\n\nLine: 3\n2: 2\n3: 3\n4: 4\n");
+}
+
+#[test]
+fn asymmetric_context() {
+    let file = NamedTempFile::new("file").unwrap();
+    let content: String = (1..=30).map(|n| format!("{n}\n")).collect();
+    file.write_str(&content).unwrap();
+
+    for (selector, expected) in [
+        ("10:12", "7 8 9 10 11 12 13"),
+        ("10:20:5", "7 8 9 10 11 12 13 14 15 16 17 18 19 20 21"),
+        ("10:20:6", "7 8 9 10 11 -- 13 14 15 16 17"),
+        (
+            "20:10:-5",
+            "17 18 19 20 21 -- 12 13 14 15 16 -- 7 8 9 10 11",
+        ),
+    ] {
+        let expected = expected.replace(' ', "\n") + "\n";
+        // aligning reads the lines into memory first instead of streaming them
+        for align in [None, Some("--align=;")] {
+            Command::cargo_bin(BIN_NAME)
+                .unwrap()
+                .arg(format!("-n={selector}"))
+                .arg("--before=3")
+                .arg("--after=1")
+                .arg("--plain")
+                .args(align)
+                .arg(file.path())
+                .assert()
+                .success()
+                .stdout(expected.clone());
+        }
+    }
+}
+
+#[test]
+fn unique() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1,1:3,1:1,-1")
+        .arg("--unique")
+        .arg("--plain=never")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Lines: 1:4\n1: one\n2: two\n3: three\n4: four\n");
+}
+
+#[test]
+fn expand_tabs() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("a\tb\n\tcd\tef\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=:")
+        .arg("--tabs=4")
+        .arg("--plain=never")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Lines: :\n1: a   b\n2:     cd  ef\n");
+}
+
+#[test]
+fn show_all() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_binary(b"key =\tvalue  \r\n\x1b[0m\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=:")
+        .arg("-A")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("key =→  value··␍␊\r\n␛[0m␊\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--show-all")
+        .arg("--tabs=3")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("key =→value··␍␊\r\n");
+}
+
+#[test]
+fn encoding() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_binary(b"\xff\xfeo\x00n\x00e\x00\n\x00t\x00w\x00o\x00\n\x00")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=-1")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\n");
+
+    file.write_binary(b"caf\xe9\n").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--encoding=latin1")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("café\n");
+}
+
+#[test]
+fn strip_cr() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\r\ntwo\r\nthree").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=:")
+        .arg("--strip-cr")
+        .arg("--before=1")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\ntwo\nthree");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--strip-cr")
+        .arg("--show-eol")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one␊\n");
+}
+
+#[test]
+fn max_line_length() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str(&format!("short\n{}\n", "x".repeat(2000)))
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=:")
+        .arg("--max-line-length=5")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("short\nxxxxx…[1,995 more bytes]\n");
+}
+
+#[test]
+fn head_and_tail_bytes() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\n").unwrap();
+
+    for (arg, expected) in [
+        ("--head-bytes=4", "one\n"),
+        ("--head-bytes=5", "one\ntwo\n"),
+        ("--head-bytes=1K", "one\ntwo\nthree\nfour\n"),
+        ("--tail-bytes=5", "four\n"),
+        ("--tail-bytes=6", "three\nfour\n"),
+        ("--tail-bytes=1K", "one\ntwo\nthree\nfour\n"),
+    ] {
+        Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .arg(arg)
+            .arg("--plain")
+            .arg(file.path())
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+}
+
+#[test]
+fn multiple_files() {
+    let dir = TempDir::new().unwrap();
+    let (a, b) = (dir.child("a.txt"), dir.child("b.txt"));
+    a.write_str("a1\na2\na3\n").unwrap();
+    b.write_str("b1\nb2\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["-n=-1", "--plain", "a.txt", "b.txt"])
+        .assert()
+        .success()
+        .stdout("==> a.txt <==\na3\n\n==> b.txt <==\nb2\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["-n=1", "--output-format=jsonl", "a.txt", "b.txt"])
+        .assert()
+        .success()
+        .stdout(
+            "{\"content\":\"a1\",\"line_ending\":\"\\n\",\"number\":1,\"selected\":true,\
+            \"version\":2}\n\
+            {\"content\":\"b1\",\"line_ending\":\"\\n\",\"number\":1,\"selected\":true,\
+            \"version\":2}\n",
+        );
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["-n=3", "a.txt", "b.txt"])
+        .assert()
+        .failure()
+        .stdout("==> a.txt <==\na3\n\n==> b.txt <==\n")
+        .stderr(predicates::str::contains(
+            "Line 3 is out of range (input has 2 line(s) only)",
+        ));
+}
+
+#[test]
+fn paging() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=:")
+        .arg("--paging=always")
+        .arg("--plain")
+        .arg(file.path())
+        .env("PAGER", "sed s/^/paged:/")
+        .assert()
+        .success()
+        .stdout("paged:one\npaged:two\n");
+
+    // the output goes straight to stdout if the pager can't be started
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=:")
+        .arg("--paging=always")
+        .arg("--plain")
+        .arg(file.path())
+        .env("PAGER", "no-such-pager")
+        .assert()
+        .success()
+        .stdout("one\ntwo\n");
+}
+
+#[test]
+fn color_env_vars() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--plain")
+        .arg("--theme=dark")
+        .arg(file.path())
+        .env("CLICOLOR_FORCE", "1")
+        .env("TERM", "xterm")
+        .assert()
+        .success()
+        .stdout(format!("{RED}one\n{CLEAR}"));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--plain")
+        .arg(file.path())
+        .env("CLICOLOR_FORCE", "1")
+        .env("NO_COLOR", "1")
+        .assert()
+        .success()
+        .stdout("one\n");
+
+    // `--color=always` takes precedence over NO_COLOR
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--plain")
+        .arg("--color=always")
+        .arg("--theme=dark")
+        .arg(file.path())
+        .env("NO_COLOR", "1")
+        .env("TERM", "xterm")
+        .assert()
+        .success()
+        .stdout(format!("{RED}one\n{CLEAR}"));
+}
+
+#[test]
+fn highlight() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.child("main.rs");
+    file.write_str("// a comment\nfn main() {}\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--highlight")
+        .arg("--color=always")
+        .arg("--plain")
+        .arg("--theme=dark")
+        .arg("--emphasis=bold")
+        .arg(file.path())
+        .env("COLORTERM", "truecolor")
+        .assert()
+        .success()
+        .stdout(format!(
+            "{BOLD}\x1b[38;2;101;115;126m// a comment\x1b[39m\n{CLEAR}"
+        ));
+
+    // highlighting needs colors
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--language=python")
+        .arg("--plain")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("// a comment\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-n=1")
+        .arg("--language=nope")
+        .arg("--color=always")
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Unknown language `nope`"));
+}
+
+#[test]
+fn match_pattern() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("ok\nerror: a\nerror: b\nok\nok\nwarn: c\nok\n")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--match", "^error", "--match", "warn", "--plain=never"])
+        .arg("--color=never")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Lines: 2:3\n2: error: a\n3: error: b\n\nLine: 6\n6: warn: c\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--match", "warn", "-c=1", "-n=1", "--plain"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("ok\nerror: a\n--\nok\nwarn: c\nok\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--match", "nothing", "--every=2", "--plain"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--match", "(", "--plain"])
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "invalid value '(' for '--match <PATTERN>'",
+        ));
+}
+
+#[test]
+fn invert() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("a\nb\nc\nd\ne\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=2:3", "-v", "--plain=never", "--color=never"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Line: 1\n1: a\n\nLines: 4:5\n4: d\n5: e\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--match", "[ace]", "--invert", "--plain"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("b\nd\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=:", "-v", "--plain"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn mmap() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\nfive\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=-1,4:2:-1,1", "--mmap", "-p"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("five\nfour\nthree\ntwo\none\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=2", "-c=1", "--mmap", "--plain=never", "--color=never"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Line: 2\n1: one\n2: two\n3: three\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=9", "--mmap"])
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "Line 9 is out of range (input has 5 line(s) only)",
+        ));
+}
+
+#[test]
+fn line_index() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.child("file");
+    let content: String = (1..=3000).map(|line_num| format!("{line_num}\n")).collect();
+    file.write_str(&content).unwrap();
+
+    for _ in 0..2 {
+        Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .args(["-n=2500,-1,1500", "--index", "-p"])
+            .arg(file.path())
+            .assert()
+            .success()
+            .stdout("2500\n3000\n1500\n");
+        assert!(dir.child("file.line-idx").exists());
+    }
+
+    // a stale index is rebuilt
+    file.write_str("a\nb\n").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=-1", "--index", "-p"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("b\n");
+}
+
+#[test]
+fn follow() {
+    use std::io::{BufRead, Write};
+    use std::time::Duration;
+
+    let file = NamedTempFile::new("app.log").unwrap();
+    file.write_str("one\ntwo\n").unwrap();
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_line"))
+        .args(["-n=-1:", "--follow", "--plain=never", "--color=never"])
+        .arg(file.path())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+    std::thread::spawn(move || {
+        for line in stdout.lines() {
+            sender.send(line.unwrap()).unwrap();
+        }
+    });
+    let next_line = || receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(next_line(), "Lines: -1:");
+    assert_eq!(next_line(), "2: two");
+
+    let mut appended = std::fs::OpenOptions::new()
+        .append(true)
+        .open(file.path())
+        .unwrap();
+    // a line is only printed once its line ending arrives
+    write!(appended, "thr").unwrap();
+    std::thread::sleep(Duration::from_millis(400));
+    write!(appended, "ee\nfour\n").unwrap();
+    assert_eq!(next_line(), "3: three");
+    assert_eq!(next_line(), "4: four");
+
+    file.write_str("new\n").unwrap();
+    assert_eq!(next_line(), "1: new");
+
+    // log rotation
+    let rotated = file.path().with_extension("log.1");
+    std::fs::rename(file.path(), &rotated).unwrap();
+    file.write_str("rotated\n").unwrap();
+    assert_eq!(next_line(), "1: rotated");
+    std::fs::remove_file(rotated).unwrap();
+
+    child.kill().unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("was truncated, following it from its start"));
+    assert!(stderr.contains("was replaced, following the new file"));
+}
+
+#[test]
+fn generate_completions() {
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--generate-completions", "bash"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("_line()"))
+        .stdout(predicates::str::contains("auto always never"));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--generate-completions", "fish", "-n=1", "file"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn config_file() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\n").unwrap();
+    let config = NamedTempFile::new("config.toml").unwrap();
+    config
+        .write_str("plain = true\ncontext = 1\ncolor = \"never\"\n")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LINE_CONFIG_PATH", config.path())
+        .arg("-n=2")
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\ntwo\nthree\n");
+
+    // the command line overrides the config file
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LINE_CONFIG_PATH", config.path())
+        .args(["-n=2", "--context=0", "--plain=never"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Line: 2\n2: two\n");
+
+    // an empty path turns the config file off
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LINE_CONFIG_PATH", "")
+        .args(["-n=2", "--plain"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\n");
+
+    // options given on the command line drop the defaults they conflict with
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LINE_CONFIG_PATH", config.path())
+        .args(["-n=3", "--before=2"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\ntwo\nthree\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LINE_CONFIG_PATH", config.path())
+        .args(["-n=2", "--grid", "--context=0"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("two"));
+
+    // the values of options given on the command line replace the default ones
+    config
+        .write_str("plain = true\nmatch = [\"one\"]\n")
+        .unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LINE_CONFIG_PATH", config.path())
+        .args(["--match=four"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("four\n");
+
+    config.write_str("plainn = true\n").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LINE_CONFIG_PATH", config.path())
+        .args(["-n=2"])
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Unknown option `plainn`"));
+}
+
+#[test]
+fn line_opts() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\n").unwrap();
+    let config = NamedTempFile::new("config.toml").unwrap();
+    config
+        .write_str("plain = \"never\"\ncontext = 1\n")
+        .unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LINE_CONFIG_PATH", "")
+        .env("LINE_OPTS", "--plain --color=never")
+        .args(["-n=2"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\n");
+
+    // `LINE_OPTS` overrides the config file, and the command line overrides both
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LINE_CONFIG_PATH", config.path())
+        .env("LINE_OPTS", "--plain")
+        .args(["-n=2", "--context=0"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LINE_CONFIG_PATH", "")
+        .env("LINE_OPTS", "--match 'a")
+        .args(["-n=2"])
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid `LINE_OPTS`"));
+
+    // options given on the command line drop the defaults they conflict with
+    for (opts, args, expected) in [
+        ("-c 1", ["-n=2", "-a=1"], "two\nthree\n"),
+        ("-w 1:1", ["-n=2", "-c=0"], "two\n"),
+    ] {
+        Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .env("LINE_CONFIG_PATH", "")
+            .env("LINE_OPTS", opts)
+            .args(args)
+            .arg("--plain")
+            .arg(file.path())
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+
+    // `LINE_OPTS` drops the options of the config file it conflicts with
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .env("LINE_CONFIG_PATH", config.path())
+        .env("LINE_OPTS", "--plain -b 1")
+        .args(["-n=2"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\ntwo\n");
+
+    // the last value of an option wins, with or without defaults
+    for opts in ["", "--plain"] {
+        Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .env("LINE_CONFIG_PATH", "")
+            .env("LINE_OPTS", opts)
+            .args(["-n=2", "--context=1", "--context=0", "--plain"])
+            .arg(file.path())
+            .assert()
+            .success()
+            .stdout("two\n");
+    }
+}
+
+#[test]
+fn output_file() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.child("file");
+    file.write_str("one\ntwo\nthree\n").unwrap();
+    let output = dir.child("output");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=2:3", "-o"])
+        .arg(output.path())
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("");
+    output.assert("two\nthree\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=1", "--plain=never", "--color=always", "--output"])
+        .arg(output.path())
+        .arg(file.path())
+        .assert()
+        .success();
+    output.assert(predicates::str::contains("\x1b["));
+
+    // the input file is only replaced once it's read
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=-1", "-o"])
+        .arg(file.path())
+        .arg(file.path())
+        .assert()
+        .success();
+    file.assert("three\n");
+
+    // the output file is left as is on errors
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=5", "-o"])
+        .arg(file.path())
+        .arg(file.path())
+        .assert()
+        .failure();
+    file.assert("three\n");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn delete() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\nfive").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--delete", "-n=2,4", "--plain=never", "--color=always"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\nthree\nfive");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--delete", "-n=:"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--delete", "-v", "-n=1"])
+        .arg(file.path())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn delete_in_place() {
+    let dir = TempDir::new().unwrap();
+    let first = dir.child("first");
+    first.write_str("one\ntwo\nthree\n").unwrap();
+    let second = dir.child("second");
+    second.write_str("a\nb\n").unwrap();
+    std::fs::set_permissions(first.path(), Permissions::from_mode(0o640)).unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--delete", "-n=-1", "-i"])
+        .arg(first.path())
+        .arg(second.path())
+        .assert()
+        .success()
+        .stdout("");
+    first.assert("one\ntwo\n");
+    second.assert("a\n");
+    let mode = std::fs::metadata(first.path())
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o640);
+
+    // files are left as is on errors
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--delete", "-n=3", "--in-place"])
+        .arg(first.path())
+        .assert()
+        .failure();
+    first.assert("one\ntwo\n");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=1", "-i"])
+        .arg(first.path())
+        .assert()
+        .failure();
+
+    // the file a symbolic link points to is edited, and the link is kept
+    let link = dir.child("link");
+    link.symlink_to_file(first.path()).unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--delete", "-n=1", "-i"])
+        .arg(link.path())
+        .assert()
+        .success();
+    first.assert("two\n");
+    assert!(link.path().is_symlink());
+    link.assert("two\n");
+}
+
+#[test]
+fn counted_range() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\nfive\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=2+3", "--plain=never"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("Lines: 2+3\n2: two\n3: three\n4: four\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=4+3"])
+        .arg(file.path())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn line_file() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("one\ntwo\nthree\nfour\n").unwrap();
+    let line_file = NamedTempFile::new("selectors").unwrap();
+    line_file.write_str("4\n\n2\n").unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=1", "--plain", "--line-file"])
+        .arg(line_file.path())
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("one\nfour\ntwo\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--plain", "--line-file", "-"])
+        .arg(file.path())
+        .write_stdin("2:3\n")
+        .assert()
+        .success()
+        .stdout("two\nthree\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["--line-file", "-"])
+        .arg(file.path())
+        .write_stdin("1\none\n")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Invalid line selector on line 2"));
+}
+
+#[test]
+fn highlight_pattern() {
+    let file = NamedTempFile::new("file").unwrap();
+    file.write_str("a TODO\nplain\n").unwrap();
+    let matched = "\x1b[30;43;1m";
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=2", "-b=1", "--plain", "--color=always", "--canonical"])
+        .args(["--highlight-pattern", "TO+DO"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout(format!("a {matched}TODO{CLEAR}\n{RED}plain\n{CLEAR}"));
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args([
+            "-n=1",
+            "--plain",
+            "--color=never",
+            "--highlight-pattern=TODO",
+        ])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("a TODO\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=1", "--highlight-pattern=("])
+        .arg(file.path())
+        .assert()
+        .failure();
+}
+
+#[test]
+fn decompress() {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(b"one\ntwo\nthree\n").unwrap();
+    let file = NamedTempFile::new("file.gz").unwrap();
+    file.write_binary(&encoder.finish().unwrap()).unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=-2:", "--plain"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\nthree\n");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=1", "--no-decompress"])
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("binary"));
+
+    // only the selected lines at the start of the file are decompressed
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=2", "--plain"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\n");
+
+    let file = NamedTempFile::new("file.zst").unwrap();
+    file.write_binary(&zstd::encode_all(&b"one\ntwo\nthree\n"[..], 0).unwrap())
+        .unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=-1", "--plain"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("three\n");
+
+    let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    encoder.write_all(b"one\ntwo\nthree\n").unwrap();
+    let file = NamedTempFile::new("file.bz2").unwrap();
+    file.write_binary(&encoder.finish().unwrap()).unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=2:", "--plain"])
+        .arg(file.path())
+        .assert()
+        .success()
+        .stdout("two\nthree\n");
+
+    let file = NamedTempFile::new("file.zst").unwrap();
+    file.write_binary(b"\x28\xb5\x2f\xfd\x00").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["-n=1"])
+        .arg(file.path())
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Failed to decompress zstd file"));
+}
+
+#[test]
+fn watch() {
+    use std::io::BufRead;
+    use std::time::Duration;
+
+    let file = NamedTempFile::new("config").unwrap();
+    file.write_str("one\ntwo\n").unwrap();
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_line"))
+        .args(["-n=2", "--watch", "--plain"])
+        .arg(file.path())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+    std::thread::spawn(move || {
+        for line in stdout.lines() {
+            sender.send(line.unwrap()).unwrap();
+        }
+    });
+    let next_line = || receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(next_line(), "two");
+    file.write_str("one\nthe second\n").unwrap();
+    assert_eq!(next_line(), "the second");
+
+    // errors are printed until the next change
+    file.write_str("one\n").unwrap();
+    std::thread::sleep(Duration::from_millis(400));
+    file.write_str("1\n2\n").unwrap();
+    assert_eq!(next_line(), "2");
+
+    child.kill().unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Line 2 is out of range"));
+}